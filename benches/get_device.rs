@@ -0,0 +1,239 @@
+//! Measures how long it takes to parse the WG_CMD_GET_DEVICE dump of a device
+//! with many peers. The dump is generated once up front and replayed from
+//! memory, so only parsing is measured.
+
+#[cfg(target_os = "linux")]
+mod get_device {
+    use criterion::{BenchmarkId, Criterion, Throughput};
+    use std::io;
+    use wireguard_uapi::{codec, DeviceInterface, Transport, WgSocket};
+
+    const PEER_COUNTS: [usize; 3] = [1_000, 10_000, 100_000];
+
+    const FAMILY_ID: u16 = 0x20;
+    const GENL_ID_CTRL: u16 = 0x10;
+    const CTRL_CMD_NEWFAMILY: u8 = 1;
+    const CTRL_ATTR_FAMILY_ID: u16 = 1;
+    const WG_CMD_GET_DEVICE: u8 = 0;
+
+    const NLMSG_DONE: u16 = 3;
+    const NLM_F_MULTI: u16 = 2;
+    const NLA_F_NESTED: u16 = 1 << 15;
+
+    /// The kernel's dump messages are about a page large.
+    const MAX_MESSAGE_SIZE: usize = 4096;
+
+    fn put_attr(buf: &mut Vec<u8>, nla_type: u16, payload: &[u8]) {
+        buf.extend(&((4 + payload.len()) as u16).to_ne_bytes());
+        buf.extend(&nla_type.to_ne_bytes());
+        buf.extend(payload);
+        buf.resize((buf.len() + 3) & !3, 0);
+    }
+
+    fn put_nested(buf: &mut Vec<u8>, nla_type: u16, payload: &[u8]) {
+        put_attr(buf, nla_type | NLA_F_NESTED, payload);
+    }
+
+    fn message(nl_type: u16, nl_flags: u16, payload: &[u8]) -> Vec<u8> {
+        let mut message = vec![];
+        message.extend(&((16 + payload.len()) as u32).to_ne_bytes());
+        message.extend(&nl_type.to_ne_bytes());
+        message.extend(&nl_flags.to_ne_bytes());
+        message.extend(&[0u8; 8]);
+        message.extend(payload);
+        message
+    }
+
+    fn genl_message(nl_type: u16, nl_flags: u16, cmd: u8, attrs: &[u8]) -> Vec<u8> {
+        let mut payload = vec![cmd, 1, 0, 0];
+        payload.extend(attrs);
+        message(nl_type, nl_flags, &payload)
+    }
+
+    /// The attributes of a peer with an IPv4 endpoint, an IPv4 and an IPv6
+    /// allowed IP, and traffic counters, like a typical road warrior.
+    fn peer(index: usize) -> Vec<u8> {
+        let index = index as u32;
+        let mut public_key = [0u8; 32];
+        public_key[..4].copy_from_slice(&index.to_be_bytes());
+
+        let mut endpoint = vec![];
+        endpoint.extend(&(libc::AF_INET as u16).to_ne_bytes());
+        endpoint.extend(&51820u16.to_be_bytes());
+        endpoint.extend(&(0xc000_0000 | index).to_be_bytes());
+        endpoint.extend(&[0u8; 8]);
+
+        let mut last_handshake_time = vec![];
+        last_handshake_time.extend(&1_600_000_000i64.to_ne_bytes());
+        last_handshake_time.extend(&0i64.to_ne_bytes());
+
+        let mut allowed_ips = vec![];
+        let mut allowed_ip = vec![];
+        put_attr(&mut allowed_ip, 1, &(libc::AF_INET as u16).to_ne_bytes());
+        put_attr(&mut allowed_ip, 2, &(0x0a00_0000 | index).to_be_bytes());
+        put_attr(&mut allowed_ip, 3, &[32]);
+        put_nested(&mut allowed_ips, 0, &allowed_ip);
+        let mut allowed_ip = vec![];
+        let mut ipv6 = [0u8; 16];
+        ipv6[0] = 0xfd;
+        ipv6[12..].copy_from_slice(&index.to_be_bytes());
+        put_attr(&mut allowed_ip, 1, &(libc::AF_INET6 as u16).to_ne_bytes());
+        put_attr(&mut allowed_ip, 2, &ipv6);
+        put_attr(&mut allowed_ip, 3, &[128]);
+        put_nested(&mut allowed_ips, 0, &allowed_ip);
+
+        let mut peer = vec![];
+        put_attr(&mut peer, 1, &public_key);
+        put_attr(&mut peer, 2, &[0u8; 32]);
+        put_attr(&mut peer, 4, &endpoint);
+        put_attr(&mut peer, 5, &25u16.to_ne_bytes());
+        put_attr(&mut peer, 6, &last_handshake_time);
+        put_attr(&mut peer, 7, &123_456u64.to_ne_bytes());
+        put_attr(&mut peer, 8, &654_321u64.to_ne_bytes());
+        put_nested(&mut peer, 9, &allowed_ips);
+        put_attr(&mut peer, 10, &1u32.to_ne_bytes());
+        peer
+    }
+
+    /// The datagrams the kernel sends in reply to a dump of a device with
+    /// `peer_count` peers, one message per datagram.
+    fn dump(peer_count: usize) -> Vec<Vec<u8>> {
+        let mut device_attrs = vec![];
+        put_attr(&mut device_attrs, 1, &7u32.to_ne_bytes());
+        put_attr(&mut device_attrs, 2, b"wgbench0\0");
+        put_attr(&mut device_attrs, 3, &[1u8; 32]);
+        put_attr(&mut device_attrs, 4, &[2u8; 32]);
+        put_attr(&mut device_attrs, 6, &51820u16.to_ne_bytes());
+        put_attr(&mut device_attrs, 7, &0u32.to_ne_bytes());
+
+        let mut datagrams = vec![];
+        let mut attrs = device_attrs;
+        let mut peers = vec![];
+        for index in 0..peer_count {
+            let peer = peer(index);
+            if 16 + 4 + attrs.len() + 4 + peers.len() + 4 + peer.len() > MAX_MESSAGE_SIZE {
+                put_nested(&mut attrs, 8, &peers);
+                datagrams.push(genl_message(
+                    FAMILY_ID,
+                    NLM_F_MULTI,
+                    WG_CMD_GET_DEVICE,
+                    &attrs,
+                ));
+                attrs.clear();
+                peers.clear();
+            }
+            put_nested(&mut peers, 0, &peer);
+        }
+        put_nested(&mut attrs, 8, &peers);
+        datagrams.push(genl_message(
+            FAMILY_ID,
+            NLM_F_MULTI,
+            WG_CMD_GET_DEVICE,
+            &attrs,
+        ));
+
+        let done = message(NLMSG_DONE, NLM_F_MULTI, &0i32.to_ne_bytes());
+        datagrams.last_mut().unwrap().extend(done);
+        datagrams
+    }
+
+    /// Answers the family lookup of `WgSocket::from_transport`, and every
+    /// request after that with the same dump.
+    struct ReplayTransport<'a> {
+        dump: &'a [Vec<u8>],
+        replies: Vec<&'a [u8]>,
+        family_reply: Vec<u8>,
+        resolved: bool,
+    }
+
+    impl<'a> ReplayTransport<'a> {
+        fn new(dump: &'a [Vec<u8>]) -> Self {
+            let mut attrs = vec![];
+            put_attr(&mut attrs, CTRL_ATTR_FAMILY_ID, &FAMILY_ID.to_ne_bytes());
+            Self {
+                dump,
+                replies: vec![],
+                family_reply: genl_message(GENL_ID_CTRL, 0, CTRL_CMD_NEWFAMILY, &attrs),
+                resolved: false,
+            }
+        }
+    }
+
+    impl Transport for ReplayTransport<'_> {
+        fn send(&mut self, _buf: &[u8]) -> io::Result<()> {
+            self.replies = self.dump.iter().rev().map(Vec::as_slice).collect();
+            Ok(())
+        }
+
+        fn recv(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let reply = if self.resolved {
+                self.replies.pop().unwrap_or_default()
+            } else {
+                self.resolved = true;
+                &self.family_reply
+            };
+            buf[..reply.len()].copy_from_slice(reply);
+            Ok(reply.len())
+        }
+    }
+
+    fn decode_dump(dump: &[Vec<u8>]) -> wireguard_uapi::get::Device {
+        let mut device = None;
+        for datagram in dump {
+            for message in codec::decode(datagram).unwrap() {
+                if let codec::Message::Device { fragment, .. } = message {
+                    device = Some(fragment.merge(device).unwrap());
+                }
+            }
+        }
+        device.unwrap()
+    }
+
+    pub fn bench(c: &mut Criterion) {
+        let mut group = c.benchmark_group("get_device");
+        group.sample_size(10);
+
+        for peer_count in PEER_COUNTS {
+            let dump = dump(peer_count);
+            assert_eq!(decode_dump(&dump).peers.len(), peer_count);
+            group.throughput(Throughput::Elements(peer_count as u64));
+
+            group.bench_with_input(
+                BenchmarkId::new("wg_socket", peer_count),
+                &dump,
+                |b, dump| {
+                    let mut wg = WgSocket::from_transport(ReplayTransport::new(dump)).unwrap();
+                    b.iter(|| wg.get_device(DeviceInterface::from_index(7)).unwrap())
+                },
+            );
+
+            group.bench_with_input(
+                BenchmarkId::new("peer_iter", peer_count),
+                &dump,
+                |b, dump| {
+                    let mut wg = WgSocket::from_transport(ReplayTransport::new(dump)).unwrap();
+                    b.iter(|| {
+                        wg.get_device_peers(DeviceInterface::from_index(7))
+                            .unwrap()
+                            .map(Result::unwrap)
+                            .count()
+                    })
+                },
+            );
+
+            group.bench_with_input(BenchmarkId::new("codec", peer_count), &dump, |b, dump| {
+                b.iter(|| decode_dump(dump))
+            });
+        }
+
+        group.finish();
+    }
+}
+
+#[cfg(target_os = "linux")]
+criterion::criterion_group!(benches, get_device::bench);
+#[cfg(target_os = "linux")]
+criterion::criterion_main!(benches);
+
+#[cfg(not(target_os = "linux"))]
+fn main() {}
@@ -14,14 +14,17 @@ fn main() -> anyhow::Result<()> {
 
 #[cfg(target_os = "linux")]
 fn main_linux() -> anyhow::Result<()> {
-    let device_names = wireguard_uapi::RouteSocket::connect()?.list_device_names()?;
-
+    let mut route = wireguard_uapi::RouteSocket::connect()?;
     let mut wg = wireguard_uapi::WgSocket::connect()?;
-    for device_name in &device_names {
-        let device = wg.get_device(wireguard_uapi::DeviceInterface::from_name(device_name))?;
-        print_device(&device);
 
-        if Some(device_name) != device_names.last() {
+    let devices = wg.get_all_devices(&mut route)?;
+    for (index, (device_name, device)) in devices.iter().enumerate() {
+        match device {
+            Ok(device) => print_device(device),
+            Err(err) => eprintln!("{}: {}", device_name, err),
+        }
+
+        if index + 1 != devices.len() {
             println!();
         }
     }
@@ -55,7 +58,7 @@ fn print_peer(peer: &Peer) {
     println!(
         "{}: {}",
         "peer".yellow(),
-        base64::encode(&peer.public_key).yellow()
+        base64::encode(peer.public_key).yellow()
     );
     if let Some(endpoint) = peer.endpoint {
         println!("  {}: {}", "endpoint".black().bold(), endpoint);
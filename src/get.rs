@@ -3,7 +3,7 @@ use std::net::{IpAddr, SocketAddr};
 use std::str::FromStr;
 use std::time::Duration;
 
-#[derive(Builder, Debug, PartialEq, Eq)]
+#[derive(Builder, Clone, Debug, PartialEq, Eq)]
 pub struct Device {
     pub ifindex: u32,
     pub ifname: String,
@@ -18,6 +18,7 @@ pub struct Device {
 }
 
 #[derive(Builder, Clone, Debug, PartialEq, Eq)]
+#[builder(derive(Debug))]
 pub struct Peer {
     // The public_key and allowed_ips fields are public to
     // make peer coalescing easier.
@@ -1,7 +1,14 @@
 #[cfg(target_os = "linux")]
 pub mod linux;
+#[cfg(all(target_os = "linux", feature = "fake"))]
+pub use linux::fake;
 #[cfg(target_os = "linux")]
-pub use linux::{err, set, DeviceInterface, RouteSocket, WgSocket};
+pub use linux::{
+    codec, err, link, set, stats, DeviceInterface, DeviceNamesRequest, DeviceRequest,
+    DeviceResults, NetlinkTransport, PeerIter, RouteSocket, Transport, WgSocket,
+};
+#[cfg(all(target_os = "linux", feature = "tokio"))]
+pub use linux::{AsyncRouteSocket, AsyncWgSocket};
 
 pub mod get;
 
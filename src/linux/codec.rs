@@ -0,0 +1,442 @@
+//! Socket-free encoding and decoding of WireGuard netlink messages.
+//!
+//! [`WgSocket`](crate::WgSocket) owns a blocking netlink socket. The functions
+//! here produce and consume the same bytes without doing any IO, so requests
+//! can be driven from any event loop. The caller is responsible for opening a
+//! `NETLINK_GENERIC` socket and resolving the id of the `"wireguard"` family.
+use crate::get;
+use crate::linux::consts::NLM_F_CAPPED;
+use crate::linux::err::{GetDeviceError, KernelError, ParseDeviceError, SetDeviceError};
+use crate::linux::set::{self, create_set_device_messages};
+use crate::linux::socket::ext_ack::parse_nlmsgerr_parts;
+use crate::linux::socket::get_device_utils::get_device_msg;
+use crate::linux::socket::parse::{
+    extend_device_with_peers, is_first_device_message, parse_device, parse_peer_builders,
+};
+use crate::linux::DeviceInterface;
+use neli::{consts::nl::Nlmsg, err::DeError, nl::Nlmsghdr, Size, ToBytes};
+use std::convert::TryInto;
+use std::io::Cursor;
+
+const NL_HEADER_SIZE: usize = 16;
+const NLMSGERR_HEADER_SIZE: usize = 4 + NL_HEADER_SIZE;
+
+/// Serializes a set request into one or more netlink messages. Devices too
+/// large for a single message are split up the same way
+/// [`WgSocket::set_device`](crate::WgSocket::set_device) does it.
+///
+/// Messages are at most `max_message_size` bytes long. The kernel rejects
+/// messages that don't fit into the send buffer of the socket (`SO_SNDBUF`)
+/// minus 32 bytes.
+///
+/// The messages are numbered `seq`, `seq + 1`, ... and each asks for an ACK.
+/// They have to be sent in order, waiting for the ACK of one message before
+/// sending the next.
+pub fn encode_set_device(
+    device: set::Device,
+    family_id: u16,
+    seq: u32,
+    max_message_size: usize,
+) -> Result<Vec<Vec<u8>>, SetDeviceError> {
+    create_set_device_messages(device, family_id, max_message_size)?
+        .into_iter()
+        .zip(0..)
+        .map(|(fragment, index)| {
+            let mut message = fragment.message;
+            message.nl_seq = seq.wrapping_add(index);
+            Ok(serialize(&message)?)
+        })
+        .collect()
+}
+
+/// Serializes a `WG_CMD_GET_DEVICE` request. The kernel answers with a dump
+/// that can be fed to [`decode`].
+pub fn encode_get_device(
+    interface: DeviceInterface,
+    family_id: u16,
+    seq: u32,
+) -> Result<Vec<u8>, GetDeviceError> {
+    let mut message = get_device_msg(interface, family_id)?;
+    message.nl_seq = seq;
+    Ok(serialize(&message)?)
+}
+
+fn serialize<T, P>(message: &Nlmsghdr<T, P>) -> Result<Vec<u8>, neli::err::SerError>
+where
+    T: neli::consts::nl::NlType,
+    P: Size + ToBytes,
+{
+    let mut buffer = Cursor::new(Vec::with_capacity(message.padded_size()));
+    message.to_bytes(&mut buffer)?;
+    Ok(buffer.into_inner())
+}
+
+/// A message received in reply to an encoded request.
+#[derive(Debug)]
+pub enum Message {
+    /// One message of a `WG_CMD_GET_DEVICE` dump.
+    Device { seq: u32, fragment: DeviceFragment },
+    /// The end of a dump.
+    Done { seq: u32 },
+    /// The request with this sequence number succeeded.
+    Ack { seq: u32 },
+    /// The request with this sequence number failed.
+    Error { seq: u32, error: KernelError },
+}
+
+/// The kernel splits devices with many peers across several messages. Only
+/// the first one carries the device attributes.
+#[derive(Debug)]
+pub enum DeviceFragment {
+    First(get::Device),
+    /// The peers of a following message. The first of them may only be a
+    /// continuation of the last peer of the previous message, in which case
+    /// it's missing everything but its public key and allowed IPs.
+    Continuation(Vec<get::PeerBuilder>),
+}
+
+impl DeviceFragment {
+    /// Folds this fragment into the device assembled from the previous
+    /// fragments of the same dump.
+    pub fn merge(self, device: Option<get::Device>) -> Result<get::Device, ParseDeviceError> {
+        match (self, device) {
+            (DeviceFragment::First(device), None) => Ok(device),
+            (DeviceFragment::Continuation(peers), Some(device)) => {
+                extend_device_with_peers(device, peers)
+            }
+            (DeviceFragment::First(_), Some(_)) => Err(ParseDeviceError::String(
+                "Received the device attributes twice".to_string(),
+            )),
+            (DeviceFragment::Continuation(_), None) => Err(ParseDeviceError::String(
+                "Received peers before the device attributes".to_string(),
+            )),
+        }
+    }
+}
+
+/// Splits a received datagram into its netlink messages.
+///
+/// Error messages are decoded including their extended ACK attributes. Control
+/// messages other than errors and the end of a dump are skipped.
+pub fn decode(mut buf: &[u8]) -> Result<Vec<Message>, ParseDeviceError> {
+    let mut messages = vec![];
+
+    while !buf.is_empty() {
+        let header = buf.get(..NL_HEADER_SIZE).ok_or(DeError::UnexpectedEOB)?;
+        let nl_len = u32::from_ne_bytes(header[0..4].try_into().unwrap()) as usize;
+        let nl_type = u16::from_ne_bytes(header[4..6].try_into().unwrap());
+        let nl_flags = u16::from_ne_bytes(header[6..8].try_into().unwrap());
+        let seq = u32::from_ne_bytes(header[8..12].try_into().unwrap());
+
+        if nl_len < NL_HEADER_SIZE {
+            return Err(DeError::new("Netlink message length is too small").into());
+        }
+        let payload = buf
+            .get(NL_HEADER_SIZE..nl_len)
+            .ok_or(DeError::UnexpectedEOB)?;
+        buf = buf.get((nl_len + 3) & !3..).unwrap_or_default();
+
+        if nl_type == u16::from(Nlmsg::Error) {
+            messages.push(decode_error(seq, nl_flags, payload)?);
+        } else if nl_type == u16::from(Nlmsg::Done) {
+            messages.push(Message::Done { seq });
+        } else if nl_type >= libc::NLMSG_MIN_TYPE as u16 {
+            let fragment = decode_device_fragment(payload)?;
+            messages.push(Message::Device { seq, fragment });
+        }
+    }
+
+    Ok(messages)
+}
+
+fn decode_error(seq: u32, nl_flags: u16, payload: &[u8]) -> Result<Message, ParseDeviceError> {
+    let header = payload
+        .get(..NLMSGERR_HEADER_SIZE)
+        .ok_or(DeError::UnexpectedEOB)?;
+    let error = i32::from_ne_bytes(header[0..4].try_into().unwrap());
+    if error == 0 {
+        return Ok(Message::Ack { seq });
+    }
+    let request_len = u32::from_ne_bytes(header[4..8].try_into().unwrap());
+    let capped = nl_flags & NLM_F_CAPPED != 0;
+
+    Ok(Message::Error {
+        seq,
+        error: parse_nlmsgerr_parts(error, request_len, &payload[NLMSGERR_HEADER_SIZE..], capped),
+    })
+}
+
+fn decode_device_fragment(payload: &[u8]) -> Result<DeviceFragment, ParseDeviceError> {
+    // Only the first message of a dump identifies the device.
+    if is_first_device_message(payload)? {
+        Ok(DeviceFragment::First(parse_device(payload)?))
+    } else {
+        Ok(DeviceFragment::Continuation(parse_peer_builders(payload)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::linux::attr::{NlaNested, WgDeviceAttribute, WgPeerAttribute, NLA_F_NESTED};
+    use crate::linux::cmd::WgCmd;
+    use crate::linux::consts::NLMSGERR_ATTR_MSG;
+    use crate::linux::err::ParseAttributeError;
+    use neli::{
+        consts::nl::{NlmF, NlmFFlags},
+        genl::{Genlmsghdr, Nlattr},
+        nl::NlPayload,
+        types::{Buffer, GenlBuffer},
+    };
+    use std::time::Duration;
+
+    const FAMILY_ID: u16 = 0x20;
+
+    fn header(nl_len: usize, nl_type: u16, nl_flags: u16, seq: u32) -> Vec<u8> {
+        let mut header = vec![];
+        header.extend(&(nl_len as u32).to_ne_bytes());
+        header.extend(&nl_type.to_ne_bytes());
+        header.extend(&nl_flags.to_ne_bytes());
+        header.extend(&seq.to_ne_bytes());
+        header.extend(&0u32.to_ne_bytes());
+        header
+    }
+
+    fn device_message(seq: u32, attrs: GenlBuffer<WgDeviceAttribute, Buffer>) -> Vec<u8> {
+        let message = Nlmsghdr::new(
+            None,
+            FAMILY_ID,
+            NlmFFlags::new(&[NlmF::Multi]),
+            Some(seq),
+            None,
+            NlPayload::Payload(Genlmsghdr::new(WgCmd::GetDevice, 1, attrs)),
+        );
+        serialize(&message).unwrap()
+    }
+
+    fn continuation_peers_attr(
+        public_key: &[u8; 32],
+    ) -> anyhow::Result<Nlattr<WgDeviceAttribute, Buffer>> {
+        let mut peer =
+            Nlattr::new::<Vec<u8>>(false, false, NlaNested::Unspec | NLA_F_NESTED, vec![])?;
+        peer.add_nested_attribute(&Nlattr::new(
+            false,
+            false,
+            WgPeerAttribute::PublicKey,
+            public_key.to_vec(),
+        )?)?;
+        let mut peers = Nlattr::new::<Vec<u8>>(
+            false,
+            false,
+            WgDeviceAttribute::Peers | NLA_F_NESTED,
+            vec![],
+        )?;
+        peers.add_nested_attribute(&peer)?;
+        Ok(peers)
+    }
+
+    #[test]
+    fn encode_set_device_numbers_messages() -> anyhow::Result<()> {
+        let public_keys: Vec<[u8; 32]> = (0..2000u16)
+            .map(|i| {
+                let mut public_key = [0u8; 32];
+                public_key[..2].copy_from_slice(&i.to_ne_bytes());
+                public_key
+            })
+            .collect();
+        let device = set::Device::from_ifname("wgtest0")
+            .peers(public_keys.iter().map(set::Peer::from_public_key).collect());
+
+        let messages = encode_set_device(device, FAMILY_ID, 7, 65_536)?;
+
+        assert!(messages.len() > 1);
+        for (index, message) in messages.iter().enumerate() {
+            let nl_len = u32::from_ne_bytes(message[0..4].try_into()?) as usize;
+            let nl_type = u16::from_ne_bytes(message[4..6].try_into()?);
+            let seq = u32::from_ne_bytes(message[8..12].try_into()?);
+            assert_eq!(nl_len, message.len());
+            assert_eq!(nl_type, FAMILY_ID);
+            assert_eq!(seq, 7 + index as u32);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn encode_get_device_sets_seq() -> anyhow::Result<()> {
+        let message = encode_get_device(DeviceInterface::from_index(3), FAMILY_ID, 42)?;
+
+        assert_eq!(u16::from_ne_bytes(message[4..6].try_into()?), FAMILY_ID);
+        assert_eq!(u32::from_ne_bytes(message[8..12].try_into()?), 42);
+
+        Ok(())
+    }
+
+    #[test]
+    fn decode_dump_with_continuation() -> anyhow::Result<()> {
+        let public_key = [1u8; 32];
+        let mut first = GenlBuffer::new();
+        first.push(Nlattr::new(false, false, WgDeviceAttribute::Ifindex, 3u32)?);
+        first.push(Nlattr::new(
+            false,
+            false,
+            WgDeviceAttribute::Ifname,
+            "wgtest0",
+        )?);
+        first.push(Nlattr::new(
+            false,
+            false,
+            WgDeviceAttribute::ListenPort,
+            51820u16,
+        )?);
+        first.push(Nlattr::new(false, false, WgDeviceAttribute::Fwmark, 0u32)?);
+        let mut continuation = GenlBuffer::new();
+        continuation.push(continuation_peers_attr(&public_key)?);
+
+        let mut datagram = device_message(5, first);
+        datagram.extend(device_message(5, continuation));
+        datagram.extend(header(NL_HEADER_SIZE + 4, Nlmsg::Done.into(), 0, 5));
+        datagram.extend(&0i32.to_ne_bytes());
+
+        let mut messages = decode(&datagram)?.into_iter();
+        let mut device = match messages.next() {
+            Some(Message::Device { seq: 5, fragment }) => fragment.merge(None)?,
+            message => panic!("Unexpected message {:?}", message),
+        };
+        assert_eq!(device.ifindex, 3);
+        assert_eq!(device.ifname, "wgtest0");
+        assert_eq!(device.listen_port, 51820);
+
+        // Pretend the first message ended with the peer that's continued.
+        let peer = get::Peer {
+            public_key,
+            preshared_key: [0u8; 32],
+            endpoint: None,
+            persistent_keepalive_interval: 0,
+            last_handshake_time: Duration::new(0, 0),
+            rx_bytes: 0,
+            tx_bytes: 0,
+            allowed_ips: vec!["10.0.0.1/32".parse()?],
+            protocol_version: 1,
+        };
+        device.peers.push(peer.clone());
+
+        let device = match messages.next() {
+            Some(Message::Device { seq: 5, fragment }) => fragment.merge(Some(device))?,
+            message => panic!("Unexpected message {:?}", message),
+        };
+        assert_eq!(device.peers, vec![peer]);
+        assert!(matches!(messages.next(), Some(Message::Done { seq: 5 })));
+        assert!(messages.next().is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn decode_ack_and_capped_error() -> anyhow::Result<()> {
+        let msg = b"Invalid public key\0";
+        let mut attrs = vec![];
+        attrs.extend(&(4 + msg.len() as u16).to_ne_bytes());
+        attrs.extend(&NLMSGERR_ATTR_MSG.to_ne_bytes());
+        attrs.extend(msg.iter());
+        attrs.resize((attrs.len() + 3) & !3, 0);
+
+        let mut datagram = header(
+            NL_HEADER_SIZE + NLMSGERR_HEADER_SIZE,
+            Nlmsg::Error.into(),
+            0,
+            1,
+        );
+        datagram.extend(&0i32.to_ne_bytes());
+        datagram.extend(header(100, FAMILY_ID, 0, 1));
+
+        let error_len = NL_HEADER_SIZE + NLMSGERR_HEADER_SIZE + attrs.len();
+        datagram.extend(header(error_len, Nlmsg::Error.into(), NLM_F_CAPPED, 2));
+        datagram.extend(&(-libc::EINVAL).to_ne_bytes());
+        datagram.extend(header(100, FAMILY_ID, 0, 2));
+        datagram.extend(attrs);
+
+        let messages = decode(&datagram)?;
+
+        assert!(matches!(messages[0], Message::Ack { seq: 1 }));
+        match &messages[1] {
+            Message::Error { seq: 2, error } => {
+                assert_eq!(error.raw_os_error(), libc::EINVAL);
+                assert_eq!(error.message(), Some("Invalid public key"));
+            }
+            message => panic!("Unexpected message {:?}", message),
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn decode_error_with_min_errno() -> anyhow::Result<()> {
+        let mut datagram = header(
+            NL_HEADER_SIZE + NLMSGERR_HEADER_SIZE,
+            Nlmsg::Error.into(),
+            NLM_F_CAPPED,
+            1,
+        );
+        datagram.extend(&i32::MIN.to_ne_bytes());
+        datagram.extend(header(100, FAMILY_ID, 0, 1));
+
+        match &decode(&datagram)?[..] {
+            [Message::Error { seq: 1, error }] => assert_eq!(error.raw_os_error(), i32::MIN),
+            messages => panic!("Unexpected messages {:?}", messages),
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn decode_truncated_endpoint() -> anyhow::Result<()> {
+        // The family and port of an IPv6 endpoint without its address.
+        let mut endpoint: Vec<u8> = vec![];
+        endpoint.extend(&(libc::AF_INET6 as u16).to_ne_bytes());
+        endpoint.extend(&51820u16.to_be_bytes());
+
+        let mut peer =
+            Nlattr::new::<Vec<u8>>(false, false, NlaNested::Unspec | NLA_F_NESTED, vec![])?;
+        peer.add_nested_attribute(&Nlattr::new(
+            false,
+            false,
+            WgPeerAttribute::PublicKey,
+            vec![1u8; 32],
+        )?)?;
+        peer.add_nested_attribute(&Nlattr::new(
+            false,
+            false,
+            WgPeerAttribute::Endpoint,
+            endpoint,
+        )?)?;
+        let mut peers = Nlattr::new::<Vec<u8>>(
+            false,
+            false,
+            WgDeviceAttribute::Peers | NLA_F_NESTED,
+            vec![],
+        )?;
+        peers.add_nested_attribute(&peer)?;
+        let mut attrs = GenlBuffer::new();
+        attrs.push(peers);
+
+        match decode(&device_message(1, attrs)) {
+            Err(ParseDeviceError::ParseAttributeError(
+                ParseAttributeError::StaticLengthError { expected, found },
+            )) => {
+                assert_eq!(expected, std::mem::size_of::<libc::sockaddr_in6>());
+                assert_eq!(found, 4);
+            }
+            result => panic!("Unexpected result {:?}", result),
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn decode_truncated_message() {
+        let datagram = header(64, FAMILY_ID, 0, 1);
+
+        assert!(decode(&datagram).is_err());
+    }
+}
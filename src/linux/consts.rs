@@ -5,3 +5,12 @@ pub const WG_GENL_VERSION: u8 = 1;
 /// Never create Netlink attributes with network byte order. Communication with
 /// the WireGuard kernel module is expected to be use native endian.
 pub(crate) const NLA_NETWORK_ORDER: bool = false;
+
+// netlink.h extended ACK support. These are defined here since older versions
+// of the libc crate don't export them.
+// https://github.com/torvalds/linux/blob/v5.15/include/uapi/linux/netlink.h#L125-L147
+pub(crate) const NETLINK_CAP_ACK: libc::c_int = 10;
+pub(crate) const NETLINK_EXT_ACK: libc::c_int = 11;
+pub(crate) const NLMSGERR_ATTR_MSG: u16 = 1;
+pub(crate) const NLMSGERR_ATTR_OFFS: u16 = 2;
+pub(crate) const NLM_F_CAPPED: u16 = 0x100;
@@ -0,0 +1,12 @@
+use crate::xplatform::protocol::SetKey;
+use thiserror::Error;
+
+/// A cross-platform set request that can't be expressed as a Linux one.
+#[derive(Error, Debug)]
+pub enum FromXplatformError {
+    /// Flags of the cross-platform protocol only have a `true` value. They're
+    /// unset by leaving them out, which is the only way a Linux request can
+    /// express it too.
+    #[error("Expected `{0}=true` or no `{0}` at all. Observed value: `false`")]
+    FalseFlag(SetKey),
+}
@@ -0,0 +1,14 @@
+use super::{GetDeviceError, ListDevicesError};
+use thiserror::Error;
+
+/// Failures that prevent [`WgSocket::get_all_devices`](crate::WgSocket::get_all_devices)
+/// from returning anything at all. Errors specific to a single device are
+/// returned alongside the other devices instead.
+#[derive(Error, Debug)]
+pub enum GetAllDevicesError {
+    #[error(transparent)]
+    ListDevicesError(#[from] ListDevicesError),
+
+    #[error(transparent)]
+    GetDeviceError(#[from] GetDeviceError),
+}
@@ -1,4 +1,4 @@
-use super::ParseDeviceError;
+use super::{is_timeout, is_would_block, KernelError, ParseDeviceError};
 use neli::err::{NlError, SerError};
 use thiserror::Error;
 
@@ -7,6 +7,18 @@ pub enum GetDeviceError {
     #[error(transparent)]
     NlError(NlError),
 
+    /// No reply arrived within the read timeout. See [timeouts](super#timeouts).
+    #[error("Timed out waiting for a reply from the kernel")]
+    Timeout,
+
+    /// No reply was queued yet on a non-blocking socket. See
+    /// [non-blocking mode](super#non-blocking-mode).
+    #[error("No reply from the kernel is queued yet")]
+    WouldBlock,
+
+    #[error(transparent)]
+    KernelError(KernelError),
+
     #[error(transparent)]
     NlSerError(SerError),
 
@@ -24,6 +36,12 @@ pub enum GetDeviceError {
 
 impl From<NlError> for GetDeviceError {
     fn from(error: NlError) -> Self {
+        if is_timeout(&error) {
+            return GetDeviceError::Timeout;
+        }
+        if is_would_block(&error) {
+            return GetDeviceError::WouldBlock;
+        }
         GetDeviceError::NlError(error)
     }
 }
@@ -39,3 +57,9 @@ impl From<ParseDeviceError> for GetDeviceError {
         GetDeviceError::ParseDeviceError(error)
     }
 }
+
+impl From<KernelError> for GetDeviceError {
+    fn from(error: KernelError) -> Self {
+        GetDeviceError::KernelError(error)
+    }
+}
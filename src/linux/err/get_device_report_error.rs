@@ -0,0 +1,11 @@
+use super::{GetDeviceError, GetLinkStatsError};
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum GetDeviceReportError {
+    #[error(transparent)]
+    GetDeviceError(#[from] GetDeviceError),
+
+    #[error(transparent)]
+    GetLinkStatsError(#[from] GetLinkStatsError),
+}
@@ -0,0 +1,75 @@
+use super::{is_timeout, is_would_block, KernelError, ParseAttributeError};
+use neli::err::{DeError, NlError, SerError};
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum GetLinkStatsError {
+    #[error(transparent)]
+    NlError(NlError),
+
+    /// No reply arrived within the read timeout. See [timeouts](super#timeouts).
+    #[error("Timed out waiting for a reply from the kernel")]
+    Timeout,
+
+    /// No reply was queued yet on a non-blocking socket. See
+    /// [non-blocking mode](super#non-blocking-mode).
+    #[error("No reply from the kernel is queued yet")]
+    WouldBlock,
+
+    #[error(transparent)]
+    KernelError(KernelError),
+
+    #[error(transparent)]
+    NlDeError(DeError),
+
+    #[error(transparent)]
+    NlSerError(SerError),
+
+    #[error(transparent)]
+    ParseAttributeError(ParseAttributeError),
+
+    #[error("Interface names must be 1 to IFNAMSIZ-1 characters")]
+    InvalidInterfaceName,
+
+    #[error("Interface with index {0} is not a WireGuard device")]
+    NotWireGuard(u32),
+
+    #[error("The kernel did not report IFLA_STATS64 for interface with index {0}")]
+    MissingStats(u32),
+}
+
+impl From<NlError> for GetLinkStatsError {
+    fn from(error: NlError) -> Self {
+        if is_timeout(&error) {
+            return Self::Timeout;
+        }
+        if is_would_block(&error) {
+            return Self::WouldBlock;
+        }
+        Self::NlError(error)
+    }
+}
+
+impl From<DeError> for GetLinkStatsError {
+    fn from(error: DeError) -> Self {
+        Self::NlDeError(error)
+    }
+}
+
+impl From<SerError> for GetLinkStatsError {
+    fn from(error: SerError) -> Self {
+        Self::NlSerError(error)
+    }
+}
+
+impl From<ParseAttributeError> for GetLinkStatsError {
+    fn from(error: ParseAttributeError) -> Self {
+        Self::ParseAttributeError(error)
+    }
+}
+
+impl From<KernelError> for GetLinkStatsError {
+    fn from(error: KernelError) -> Self {
+        GetLinkStatsError::KernelError(error)
+    }
+}
@@ -0,0 +1,70 @@
+use std::fmt;
+use std::io;
+
+/// An error the kernel sent back in a `NLMSG_ERROR` message.
+///
+/// Kernels with extended ACK support (4.12 and later) may attach a
+/// human-readable message to the error and point at the attribute that caused
+/// it. Both are exposed here when present.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct KernelError {
+    errno: i32,
+    message: Option<String>,
+    offset: Option<u32>,
+}
+
+impl KernelError {
+    pub fn new(errno: i32, message: Option<String>, offset: Option<u32>) -> Self {
+        Self {
+            // The kernel sends negated errno values. Callers are likely to
+            // compare against libc constants, so always store the positive one.
+            // Wrapping keeps untrusted input such as i32::MIN from panicking.
+            errno: errno.wrapping_abs(),
+            message,
+            offset,
+        }
+    }
+
+    /// The errno value reported by the kernel. This is positive for any errno
+    /// the kernel sends and can be compared against constants such as
+    /// [`libc::ENODEV`].
+    pub fn raw_os_error(&self) -> i32 {
+        self.errno
+    }
+
+    pub fn kind(&self) -> io::ErrorKind {
+        io::Error::from_raw_os_error(self.errno).kind()
+    }
+
+    /// The extended ACK message (`NLMSGERR_ATTR_MSG`) if the kernel sent one.
+    pub fn message(&self) -> Option<&str> {
+        self.message.as_deref()
+    }
+
+    /// Byte offset of the offending attribute within the request
+    /// (`NLMSGERR_ATTR_OFFS`) if the kernel sent one.
+    pub fn offset(&self) -> Option<u32> {
+        self.offset
+    }
+}
+
+impl fmt::Display for KernelError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", io::Error::from_raw_os_error(self.errno))?;
+        if let Some(message) = &self.message {
+            write!(f, ": {}", message)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for KernelError {}
+
+impl From<KernelError> for io::Error {
+    fn from(error: KernelError) -> Self {
+        match error.message {
+            Some(_) => io::Error::new(error.kind(), error),
+            None => io::Error::from_raw_os_error(error.errno),
+        }
+    }
+}
@@ -1,4 +1,5 @@
-use neli::err::{NlError, SerError};
+use super::{is_timeout, is_would_block, KernelError, ParseAttributeError};
+use neli::err::{DeError, NlError, SerError};
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -6,12 +7,36 @@ pub enum LinkDeviceError {
     #[error(transparent)]
     NlError(NlError),
 
+    /// No reply arrived within the read timeout. See [timeouts](super#timeouts).
+    #[error("Timed out waiting for a reply from the kernel")]
+    Timeout,
+
+    /// No reply was queued yet on a non-blocking socket. See
+    /// [non-blocking mode](super#non-blocking-mode).
+    #[error("No reply from the kernel is queued yet")]
+    WouldBlock,
+
+    #[error(transparent)]
+    KernelError(KernelError),
+
+    #[error(transparent)]
+    NlDeError(DeError),
+
     #[error(transparent)]
     NlSerError(SerError),
 
+    #[error(transparent)]
+    ParseAttributeError(ParseAttributeError),
+
     #[error("Interface names must be 1 to IFNAMSIZ-1 characters")]
     InvalidInterfaceName,
 
+    #[error("Interface with index {0} is not a WireGuard device")]
+    NotWireGuard(u32),
+
+    #[error("Creating a device in another network namespace requires an explicit ifindex and can't be combined with ensure_exists")]
+    UnsupportedNetNsOptions,
+
     #[error(
         "Unable to get interface from WireGuard. Make sure it exists and you have permissions to access it."
     )]
@@ -20,10 +45,22 @@ pub enum LinkDeviceError {
 
 impl From<NlError> for LinkDeviceError {
     fn from(error: NlError) -> Self {
+        if is_timeout(&error) {
+            return LinkDeviceError::Timeout;
+        }
+        if is_would_block(&error) {
+            return LinkDeviceError::WouldBlock;
+        }
         LinkDeviceError::NlError(error)
     }
 }
 
+impl From<DeError> for LinkDeviceError {
+    fn from(error: DeError) -> Self {
+        LinkDeviceError::NlDeError(error)
+    }
+}
+
 impl From<SerError> for LinkDeviceError {
     fn from(error: SerError) -> Self {
         LinkDeviceError::NlSerError(error)
@@ -32,6 +69,18 @@ impl From<SerError> for LinkDeviceError {
 
 impl From<std::io::Error> for LinkDeviceError {
     fn from(error: std::io::Error) -> Self {
-        LinkDeviceError::NlError(error.into())
+        NlError::from(error).into()
+    }
+}
+
+impl From<KernelError> for LinkDeviceError {
+    fn from(error: KernelError) -> Self {
+        LinkDeviceError::KernelError(error)
+    }
+}
+
+impl From<ParseAttributeError> for LinkDeviceError {
+    fn from(error: ParseAttributeError) -> Self {
+        LinkDeviceError::ParseAttributeError(error)
     }
 }
@@ -1,4 +1,4 @@
-use super::ParseAttributeError;
+use super::{is_timeout, is_would_block, KernelError, ParseAttributeError};
 use neli::err::{DeError, NlError, SerError};
 use thiserror::Error;
 
@@ -7,6 +7,18 @@ pub enum ListDevicesError {
     #[error(transparent)]
     NlError(NlError),
 
+    /// No reply arrived within the read timeout. See [timeouts](super#timeouts).
+    #[error("Timed out waiting for a reply from the kernel")]
+    Timeout,
+
+    /// No reply was queued yet on a non-blocking socket. See
+    /// [non-blocking mode](super#non-blocking-mode).
+    #[error("No reply from the kernel is queued yet")]
+    WouldBlock,
+
+    #[error(transparent)]
+    KernelError(KernelError),
+
     #[error(transparent)]
     NlDeError(DeError),
 
@@ -15,14 +27,16 @@ pub enum ListDevicesError {
 
     #[error(transparent)]
     ParseAttributeError(ParseAttributeError),
-
-    // TODO: Print netlink error message when neli exposes it.
-    #[error("Unknown netlink error while reading devices.")]
-    Unknown,
 }
 
 impl From<NlError> for ListDevicesError {
     fn from(error: NlError) -> Self {
+        if is_timeout(&error) {
+            return Self::Timeout;
+        }
+        if is_would_block(&error) {
+            return Self::WouldBlock;
+        }
         Self::NlError(error)
     }
 }
@@ -44,3 +58,9 @@ impl From<ParseAttributeError> for ListDevicesError {
         Self::ParseAttributeError(error)
     }
 }
+
+impl From<KernelError> for ListDevicesError {
+    fn from(error: KernelError) -> Self {
+        ListDevicesError::KernelError(error)
+    }
+}
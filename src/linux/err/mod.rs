@@ -1,9 +1,65 @@
+//! Errors returned by [`WgSocket`](crate::WgSocket) and
+//! [`RouteSocket`](crate::RouteSocket).
+//!
+//! # Timeouts
+//!
+//! Once a read timeout is set with
+//! [`WgSocket::set_read_timeout`](crate::WgSocket::set_read_timeout) or
+//! [`RouteSocket::set_read_timeout`](crate::RouteSocket::set_read_timeout), a
+//! request whose reply doesn't arrive in time fails with the `Timeout` variant
+//! of its error, such as [`GetDeviceError::Timeout`].
+//!
+//! Timing out doesn't cancel the request. The kernel may still carry it out,
+//! so a change requested through
+//! [`WgSocket::set_device`](crate::WgSocket::set_device) can be applied after
+//! all. Read the device back to find out.
+//!
+//! The socket can be used for the next request right away. Every request is
+//! numbered, and replies that arrive late are discarded when the socket looks
+//! for the reply to a later request.
+//!
+//! # Non-blocking mode
+//!
+//! A socket switched to non-blocking mode with
+//! [`WgSocket::set_nonblocking`](crate::WgSocket::set_nonblocking) or
+//! [`RouteSocket::set_nonblocking`](crate::RouteSocket::set_nonblocking)
+//! doesn't wait for replies. Receiving a reply that isn't queued yet fails with
+//! the `WouldBlock` variant of an error, such as [`GetDeviceError::WouldBlock`].
+//!
+//! Reading a device and listing device names are split into a call that sends
+//! the request and one that receives the reply:
+//! [`WgSocket::send_get_device`](crate::WgSocket::send_get_device) and
+//! [`WgSocket::poll_get_device`](crate::WgSocket::poll_get_device), and
+//! [`RouteSocket::send_list_device_names`](crate::RouteSocket::send_list_device_names)
+//! and [`RouteSocket::poll_list_device_names`](crate::RouteSocket::poll_list_device_names).
+//! After `WouldBlock`, wait for the socket's fd to become readable and poll
+//! again. Polling picks up the reply where it left off.
+//!
+//! Every other request sends and receives in a single call. If its reply isn't
+//! queued yet, the request is abandoned like one that timed out.
 mod connect_error;
 pub use connect_error::ConnectError;
 
+#[cfg(feature = "xplatform")]
+mod from_xplatform_error;
+#[cfg(feature = "xplatform")]
+pub use from_xplatform_error::FromXplatformError;
+
+mod get_all_devices_error;
+pub use get_all_devices_error::GetAllDevicesError;
+
 mod get_device_error;
 pub use get_device_error::GetDeviceError;
 
+mod get_device_report_error;
+pub use get_device_report_error::GetDeviceReportError;
+
+mod get_link_stats_error;
+pub use get_link_stats_error::GetLinkStatsError;
+
+mod kernel_error;
+pub use kernel_error::KernelError;
+
 mod link_device_error;
 pub use link_device_error::LinkDeviceError;
 
@@ -13,6 +69,9 @@ pub use list_devices_error::ListDevicesError;
 mod set_device_error;
 pub use set_device_error::SetDeviceError;
 
+mod set_device_fragment_error;
+pub use set_device_fragment_error::SetDeviceFragmentError;
+
 mod parse_device_error;
 pub use parse_device_error::ParseDeviceError;
 
@@ -20,3 +79,22 @@ mod parse_attribute_error;
 pub use parse_attribute_error::{ParseAttributeError, ParseIpAddrError, ParseSockAddrError};
 
 pub use neli::err::NlError;
+
+/// Whether receiving a reply failed because the socket's read timeout expired.
+/// See [timeouts](self#timeouts).
+fn is_timeout(error: &NlError) -> bool {
+    is_io_error_kind(error, std::io::ErrorKind::TimedOut)
+}
+
+/// Whether receiving a reply failed because none was queued yet on a
+/// non-blocking socket. See [non-blocking mode](self#non-blocking-mode).
+fn is_would_block(error: &NlError) -> bool {
+    is_io_error_kind(error, std::io::ErrorKind::WouldBlock)
+}
+
+fn is_io_error_kind(error: &NlError, kind: std::io::ErrorKind) -> bool {
+    match error {
+        NlError::Wrapped(neli::err::WrappedError::IOError(error)) => error.kind() == kind,
+        _ => false,
+    }
+}
@@ -1,3 +1,4 @@
+use super::{is_timeout, is_would_block, KernelError, SetDeviceFragmentError};
 use neli::err::{NlError, SerError};
 use thiserror::Error;
 
@@ -6,12 +7,35 @@ pub enum SetDeviceError {
     #[error(transparent)]
     NlError(NlError),
 
+    /// No reply arrived within the read timeout. See [timeouts](super#timeouts).
+    #[error("Timed out waiting for a reply from the kernel")]
+    Timeout,
+
+    /// No reply was queued yet on a non-blocking socket. See
+    /// [non-blocking mode](super#non-blocking-mode).
+    #[error("No reply from the kernel is queued yet")]
+    WouldBlock,
+
+    #[error(transparent)]
+    KernelError(KernelError),
+
+    /// One of the messages of a request that was split into several was
+    /// rejected. The messages before it were applied.
+    #[error(transparent)]
+    FragmentRejected(SetDeviceFragmentError),
+
     #[error(transparent)]
     NlSerError(SerError),
 }
 
 impl From<NlError> for SetDeviceError {
     fn from(error: NlError) -> Self {
+        if is_timeout(&error) {
+            return SetDeviceError::Timeout;
+        }
+        if is_would_block(&error) {
+            return SetDeviceError::WouldBlock;
+        }
         SetDeviceError::NlError(error)
     }
 }
@@ -21,3 +45,15 @@ impl From<SerError> for SetDeviceError {
         SetDeviceError::NlSerError(error)
     }
 }
+
+impl From<KernelError> for SetDeviceError {
+    fn from(error: KernelError) -> Self {
+        SetDeviceError::KernelError(error)
+    }
+}
+
+impl From<SetDeviceFragmentError> for SetDeviceError {
+    fn from(error: SetDeviceFragmentError) -> Self {
+        SetDeviceError::FragmentRejected(error)
+    }
+}
@@ -0,0 +1,66 @@
+use super::KernelError;
+use std::fmt;
+
+/// The kernel rejected one message of a set request that had to be split into
+/// several messages.
+///
+/// The messages before the rejected one were applied, and the kernel may have
+/// applied some of the rejected message's peers before it hit the error. The
+/// messages after it were never sent.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SetDeviceFragmentError {
+    index: usize,
+    count: usize,
+    public_keys: Vec<[u8; 32]>,
+    error: KernelError,
+}
+
+impl SetDeviceFragmentError {
+    pub fn new(index: usize, count: usize, public_keys: Vec<[u8; 32]>, error: KernelError) -> Self {
+        Self {
+            index,
+            count,
+            public_keys,
+            error,
+        }
+    }
+
+    /// The zero-based index of the rejected message.
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// How many messages the request was split into.
+    pub fn count(&self) -> usize {
+        self.count
+    }
+
+    /// The public keys of the peers in the rejected message. A peer whose
+    /// allowed IPs were split across messages appears in each of them.
+    pub fn public_keys(&self) -> &[[u8; 32]] {
+        &self.public_keys
+    }
+
+    pub fn kernel_error(&self) -> &KernelError {
+        &self.error
+    }
+}
+
+impl fmt::Display for SetDeviceFragmentError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Message {} of {} with {} peers was rejected: {}",
+            self.index + 1,
+            self.count,
+            self.public_keys.len(),
+            self.error
+        )
+    }
+}
+
+impl std::error::Error for SetDeviceFragmentError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.error)
+    }
+}
@@ -0,0 +1,75 @@
+use crate::linux::attr::{NLA_F_NESTED, NLA_TYPE_MASK};
+use crate::linux::socket::ext_ack::iter_attrs;
+
+const NLA_HEADER_SIZE: usize = 4;
+
+fn align(len: usize) -> usize {
+    (len + 3) & !3
+}
+
+/// Indexes a buffer of netlink attributes by type, the way the kernel's
+/// `nla_parse` does. Later attributes of the same type win.
+pub(super) fn collect_attrs(buf: &[u8], max_type: u16) -> Vec<Option<&[u8]>> {
+    let mut attrs = vec![None; usize::from(max_type) + 1];
+    for (nla_type, payload) in iter_attrs(buf) {
+        if let Some(slot) = attrs.get_mut(usize::from(nla_type & NLA_TYPE_MASK)) {
+            *slot = Some(payload);
+        }
+    }
+    attrs
+}
+
+/// The payloads of the attributes in a nested list, such as the peers of a
+/// device or the allowed IPs of a peer.
+pub(super) fn nested_list(buf: &[u8]) -> impl Iterator<Item = &[u8]> {
+    iter_attrs(buf).map(|(_, payload)| payload)
+}
+
+/// Builds a buffer of netlink attributes that can't grow past a size limit.
+/// Writes that don't fit leave the buffer untouched and return `false`.
+pub(super) struct AttrWriter {
+    buf: Vec<u8>,
+    limit: usize,
+}
+
+impl AttrWriter {
+    pub fn new(limit: usize) -> Self {
+        Self { buf: vec![], limit }
+    }
+
+    pub fn put(&mut self, nla_type: u16, payload: &[u8]) -> bool {
+        if self.buf.len() + NLA_HEADER_SIZE + payload.len() > self.limit {
+            return false;
+        }
+        let nla_len = (NLA_HEADER_SIZE + payload.len()) as u16;
+        self.buf.extend(&nla_len.to_ne_bytes());
+        self.buf.extend(&nla_type.to_ne_bytes());
+        self.buf.extend(payload);
+        self.buf.resize(align(self.buf.len()), 0);
+        true
+    }
+
+    /// Starts a nested attribute. Returns its offset for
+    /// [`nest_end`](Self::nest_end) or [`nest_cancel`](Self::nest_cancel).
+    pub fn nest_start(&mut self, nla_type: u16) -> Option<usize> {
+        let start = self.buf.len();
+        if self.put(nla_type | NLA_F_NESTED, &[]) {
+            Some(start)
+        } else {
+            None
+        }
+    }
+
+    pub fn nest_end(&mut self, start: usize) {
+        let nla_len = (self.buf.len() - start) as u16;
+        self.buf[start..start + 2].copy_from_slice(&nla_len.to_ne_bytes());
+    }
+
+    pub fn nest_cancel(&mut self, start: usize) {
+        self.buf.truncate(start);
+    }
+
+    pub fn into_inner(self) -> Vec<u8> {
+        self.buf
+    }
+}
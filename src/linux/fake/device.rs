@@ -0,0 +1,248 @@
+//! The semantics of WG_CMD_SET_DEVICE, following `wg_set_device` and
+//! `set_peer` in the kernel's `drivers/net/wireguard/netlink.c`. Errors are
+//! positive errno values. Like in the kernel, changes made before an error
+//! was hit stay in effect.
+use super::attrs::{collect_attrs, nested_list};
+use crate::get;
+use crate::linux::attr::{WgAllowedIpAttribute, WgDeviceAttribute, WgPeerAttribute};
+use crate::linux::set::{WgDeviceF, WgPeerF};
+use crate::linux::socket::parse::{
+    parse_device_key, parse_nla_u16, parse_nla_u32, parse_nla_u8, parse_sockaddr_in,
+};
+use libc::{AF_INET, AF_INET6, EINVAL, EOPNOTSUPP, EPFNOSUPPORT};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::time::Duration;
+use x25519_dalek::{x25519, X25519_BASEPOINT_BYTES};
+
+const WGDEVICE_F_ALL: u32 = WgDeviceF::ReplacePeers as u32;
+const WGPEER_F_ALL: u32 =
+    WgPeerF::RemoveMe as u32 | WgPeerF::ReplaceAllowedIps as u32 | WgPeerF::UpdateOnly as u32;
+
+const SOCKADDR_IN_LEN: usize = 16;
+const SOCKADDR_IN6_LEN: usize = 28;
+
+fn attr<'a, T: Into<u16>>(attrs: &[Option<&'a [u8]>], nla_type: T) -> Option<&'a [u8]> {
+    attrs.get(usize::from(nla_type.into())).copied().flatten()
+}
+
+/// Parses an attribute the kernel's netlink policy would validate before the
+/// request is handled.
+fn parse_attr<'a, T, R, E>(
+    attrs: &[Option<&'a [u8]>],
+    nla_type: T,
+    parse: impl FnOnce(&'a [u8]) -> Result<R, E>,
+) -> Result<Option<R>, i32>
+where
+    T: Into<u16>,
+{
+    attr(attrs, nla_type)
+        .map(|payload| parse(payload).map_err(|_| EINVAL))
+        .transpose()
+}
+
+pub(super) fn set_device(device: &mut get::Device, attrs: &[Option<&[u8]>]) -> Result<(), i32> {
+    let flags = parse_attr(attrs, WgDeviceAttribute::Flags, parse_nla_u32)?.unwrap_or(0);
+    let listen_port = parse_attr(attrs, WgDeviceAttribute::ListenPort, parse_nla_u16)?;
+    let fwmark = parse_attr(attrs, WgDeviceAttribute::Fwmark, parse_nla_u32)?;
+    let private_key = parse_attr(attrs, WgDeviceAttribute::PrivateKey, parse_device_key)?;
+
+    if flags & !WGDEVICE_F_ALL != 0 {
+        return Err(EOPNOTSUPP);
+    }
+
+    if let Some(listen_port) = listen_port {
+        device.listen_port = listen_port;
+    }
+    if let Some(fwmark) = fwmark {
+        device.fwmark = fwmark;
+    }
+
+    if flags & WgDeviceF::ReplacePeers as u32 != 0 {
+        device.peers.clear();
+    }
+
+    if let Some(private_key) = private_key {
+        set_private_key(device, private_key);
+    }
+
+    if let Some(peers) = attr(attrs, WgDeviceAttribute::Peers) {
+        for peer in nested_list(peers) {
+            set_peer(
+                device,
+                &collect_attrs(peer, WgPeerAttribute::ProtocolVersion.into()),
+            )?;
+        }
+    }
+
+    Ok(())
+}
+
+fn set_private_key(device: &mut get::Device, mut private_key: [u8; 32]) {
+    if device.private_key == Some(private_key) {
+        return;
+    }
+
+    // An all-zero key removes the identity of the device.
+    if private_key == [0u8; 32] {
+        device.private_key = None;
+        device.public_key = None;
+        return;
+    }
+
+    let public_key = x25519(private_key, X25519_BASEPOINT_BYTES);
+
+    // A device can't be its own peer.
+    device.peers.retain(|peer| peer.public_key != public_key);
+
+    private_key[0] &= 248;
+    private_key[31] &= 127;
+    private_key[31] |= 64;
+    device.private_key = Some(private_key);
+    device.public_key = Some(public_key);
+}
+
+fn set_peer(device: &mut get::Device, attrs: &[Option<&[u8]>]) -> Result<(), i32> {
+    let public_key = parse_attr(attrs, WgPeerAttribute::PublicKey, parse_device_key)?;
+    let preshared_key = parse_attr(attrs, WgPeerAttribute::PresharedKey, parse_device_key)?;
+    let mut flags = parse_attr(attrs, WgPeerAttribute::Flags, parse_nla_u32)?.unwrap_or(0);
+    let protocol_version = parse_attr(attrs, WgPeerAttribute::ProtocolVersion, parse_nla_u32)?;
+    let persistent_keepalive_interval = parse_attr(
+        attrs,
+        WgPeerAttribute::PersistentKeepaliveInterval,
+        parse_nla_u16,
+    )?;
+
+    let public_key = public_key.ok_or(EINVAL)?;
+    if flags & !WGPEER_F_ALL != 0 {
+        return Err(EOPNOTSUPP);
+    }
+    if protocol_version.is_some_and(|version| version != 1) {
+        return Err(EPFNOSUPPORT);
+    }
+
+    let index = match device
+        .peers
+        .iter()
+        .position(|peer| peer.public_key == public_key)
+    {
+        Some(index) => index,
+        None => {
+            if flags & (WgPeerF::RemoveMe as u32 | WgPeerF::UpdateOnly as u32) != 0 {
+                return Ok(());
+            }
+            // The peer is new, so there aren't any allowed IPs to replace.
+            flags &= !(WgPeerF::ReplaceAllowedIps as u32);
+
+            // Peers with the public key of the device itself are silently
+            // ignored.
+            if device.public_key == Some(public_key) {
+                return Ok(());
+            }
+
+            device.peers.push(get::Peer {
+                public_key,
+                preshared_key: preshared_key.unwrap_or_default(),
+                endpoint: None,
+                persistent_keepalive_interval: 0,
+                last_handshake_time: Duration::new(0, 0),
+                rx_bytes: 0,
+                tx_bytes: 0,
+                allowed_ips: vec![],
+                protocol_version: 1,
+            });
+            device.peers.len() - 1
+        }
+    };
+
+    if flags & WgPeerF::RemoveMe as u32 != 0 {
+        device.peers.remove(index);
+        return Ok(());
+    }
+
+    let peer = &mut device.peers[index];
+    if let Some(preshared_key) = preshared_key {
+        peer.preshared_key = preshared_key;
+    }
+
+    // Endpoints of an unexpected size or family are ignored.
+    if let Some(endpoint) = attr(attrs, WgPeerAttribute::Endpoint) {
+        let family = endpoint
+            .get(0..2)
+            .map(|family| i32::from(u16::from_ne_bytes([family[0], family[1]])));
+        let valid = matches!(
+            (endpoint.len(), family),
+            (SOCKADDR_IN_LEN, Some(AF_INET)) | (SOCKADDR_IN6_LEN, Some(AF_INET6))
+        );
+        if valid {
+            peer.endpoint = parse_sockaddr_in(endpoint).ok();
+        }
+    }
+
+    if flags & WgPeerF::ReplaceAllowedIps as u32 != 0 {
+        peer.allowed_ips.clear();
+    }
+
+    if let Some(allowed_ips) = attr(attrs, WgPeerAttribute::AllowedIps) {
+        for allowed_ip in nested_list(allowed_ips) {
+            let attrs = collect_attrs(allowed_ip, WgAllowedIpAttribute::CidrMask.into());
+            set_allowed_ip(device, index, &attrs)?;
+        }
+    }
+
+    if let Some(persistent_keepalive_interval) = persistent_keepalive_interval {
+        device.peers[index].persistent_keepalive_interval = persistent_keepalive_interval;
+    }
+
+    Ok(())
+}
+
+fn set_allowed_ip(
+    device: &mut get::Device,
+    index: usize,
+    attrs: &[Option<&[u8]>],
+) -> Result<(), i32> {
+    let family = parse_attr(attrs, WgAllowedIpAttribute::Family, parse_nla_u16)?;
+    let cidr_mask = parse_attr(attrs, WgAllowedIpAttribute::CidrMask, parse_nla_u8)?;
+    let (family, cidr_mask) = family.zip(cidr_mask).ok_or(EINVAL)?;
+    let ipaddr = attr(attrs, WgAllowedIpAttribute::IpAddr).unwrap_or_default();
+
+    let ipaddr = match (i32::from(family), ipaddr.len()) {
+        (AF_INET, 4) if cidr_mask <= 32 => {
+            let mut octets = [0u8; 4];
+            octets.copy_from_slice(ipaddr);
+            IpAddr::V4(mask_ipv4(Ipv4Addr::from(octets), cidr_mask))
+        }
+        (AF_INET6, 16) if cidr_mask <= 128 => {
+            let mut octets = [0u8; 16];
+            octets.copy_from_slice(ipaddr);
+            IpAddr::V6(mask_ipv6(Ipv6Addr::from(octets), cidr_mask))
+        }
+        _ => return Err(EINVAL),
+    };
+    let allowed_ip = get::AllowedIp {
+        family,
+        ipaddr,
+        cidr_mask,
+    };
+
+    // An allowed IP belongs to at most one peer. Adding it to another peer
+    // takes it away from the previous one.
+    for peer in device.peers.iter_mut() {
+        peer.allowed_ips.retain(|existing| existing != &allowed_ip);
+    }
+    device.peers[index].allowed_ips.push(allowed_ip);
+
+    Ok(())
+}
+
+fn mask_ipv4(addr: Ipv4Addr, cidr_mask: u8) -> Ipv4Addr {
+    let mask = u32::MAX.checked_shl(32 - u32::from(cidr_mask)).unwrap_or(0);
+    Ipv4Addr::from(u32::from(addr) & mask)
+}
+
+fn mask_ipv6(addr: Ipv6Addr, cidr_mask: u8) -> Ipv6Addr {
+    let mask = u128::MAX
+        .checked_shl(128 - u32::from(cidr_mask))
+        .unwrap_or(0);
+    Ipv6Addr::from(u128::from(addr) & mask)
+}
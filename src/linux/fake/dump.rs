@@ -0,0 +1,209 @@
+//! The messages of a WG_CMD_GET_DEVICE dump, following `wg_get_device_dump`
+//! and `get_peer` in the kernel's `drivers/net/wireguard/netlink.c`.
+use super::attrs::AttrWriter;
+use crate::get;
+use crate::linux::attr::{WgAllowedIpAttribute, WgDeviceAttribute, WgPeerAttribute};
+use crate::linux::cmd::WgCmd;
+use crate::linux::consts::WG_GENL_VERSION;
+use std::net::{IpAddr, SocketAddr};
+
+const GENL_HEADER_SIZE: usize = 4;
+
+/// Splits a device into the payloads of the messages of its dump. Each payload
+/// is at most `limit` bytes long, including the generic netlink header.
+///
+/// Only the first message carries the device attributes. A peer whose allowed
+/// IPs don't fit into a message is continued in the next one, where it only
+/// carries its public key and the remaining allowed IPs.
+///
+/// Fails with `EMSGSIZE` like the kernel does if a message can't make
+/// progress, because the device attributes or a single peer are larger than
+/// the limit.
+pub(super) fn dump_device(device: &get::Device, limit: usize) -> Result<Vec<Vec<u8>>, i32> {
+    let mut payloads = vec![];
+    let mut next_peer = 0;
+    // The index of the next allowed IP if the next peer was cut off.
+    let mut allowed_ips_cursor = None;
+
+    loop {
+        let first = payloads.is_empty();
+        let progress = (next_peer, allowed_ips_cursor);
+
+        let mut writer = AttrWriter::new(limit.saturating_sub(GENL_HEADER_SIZE));
+        if first && !put_device_attrs(&mut writer, device) {
+            return Err(libc::EMSGSIZE);
+        }
+
+        let peers = writer
+            .nest_start(WgDeviceAttribute::Peers.into())
+            .ok_or(libc::EMSGSIZE)?;
+        let mut done = true;
+        while let Some(peer) = device.peers.get(next_peer) {
+            match put_peer(&mut writer, peer, allowed_ips_cursor) {
+                Ok(()) => {
+                    next_peer += 1;
+                    allowed_ips_cursor = None;
+                }
+                Err(cursor) => {
+                    allowed_ips_cursor = cursor;
+                    done = false;
+                    break;
+                }
+            }
+        }
+        writer.nest_end(peers);
+
+        if !first && progress == (next_peer, allowed_ips_cursor) {
+            return Err(libc::EMSGSIZE);
+        }
+
+        let mut payload = vec![u8::from(WgCmd::GetDevice), WG_GENL_VERSION, 0, 0];
+        payload.extend(writer.into_inner());
+        payloads.push(payload);
+
+        if done {
+            return Ok(payloads);
+        }
+    }
+}
+
+/// Writes the device attributes. Returns whether all of them fit.
+fn put_device_attrs(writer: &mut AttrWriter, device: &get::Device) -> bool {
+    let mut ifname = device.ifname.as_bytes().to_vec();
+    ifname.push(0);
+
+    let mut fits = writer.put(
+        WgDeviceAttribute::Ifindex.into(),
+        &device.ifindex.to_ne_bytes(),
+    ) && writer.put(WgDeviceAttribute::Ifname.into(), &ifname);
+    if let (Some(private_key), Some(public_key)) = (device.private_key, device.public_key) {
+        fits = fits
+            && writer.put(WgDeviceAttribute::PrivateKey.into(), &private_key)
+            && writer.put(WgDeviceAttribute::PublicKey.into(), &public_key);
+    }
+    fits && writer.put(
+        WgDeviceAttribute::ListenPort.into(),
+        &device.listen_port.to_ne_bytes(),
+    ) && writer.put(
+        WgDeviceAttribute::Fwmark.into(),
+        &device.fwmark.to_ne_bytes(),
+    )
+}
+
+/// Writes a peer starting at the allowed IP at `cursor`. Returns where to
+/// continue if the peer didn't fit completely.
+fn put_peer(
+    writer: &mut AttrWriter,
+    peer: &get::Peer,
+    cursor: Option<usize>,
+) -> Result<(), Option<usize>> {
+    let start = writer.nest_start(0).ok_or(cursor)?;
+
+    let fits = writer.put(WgPeerAttribute::PublicKey.into(), &peer.public_key)
+        && (cursor.is_some() || put_peer_attrs(writer, peer));
+    if !fits {
+        writer.nest_cancel(start);
+        return Err(cursor);
+    }
+
+    let allowed_ips = match writer.nest_start(WgPeerAttribute::AllowedIps.into()) {
+        Some(allowed_ips) => allowed_ips,
+        None => {
+            writer.nest_cancel(start);
+            return Err(cursor);
+        }
+    };
+
+    let first_allowed_ip = cursor.unwrap_or(0);
+    let mut result = Ok(());
+    for (index, allowed_ip) in peer.allowed_ips.iter().enumerate().skip(first_allowed_ip) {
+        if !put_allowed_ip(writer, allowed_ip) {
+            result = Err(Some(index));
+            break;
+        }
+    }
+
+    writer.nest_end(allowed_ips);
+    writer.nest_end(start);
+    result
+}
+
+fn put_peer_attrs(writer: &mut AttrWriter, peer: &get::Peer) -> bool {
+    let mut last_handshake_time = vec![];
+    last_handshake_time.extend(&(peer.last_handshake_time.as_secs() as i64).to_ne_bytes());
+    last_handshake_time.extend(&i64::from(peer.last_handshake_time.subsec_nanos()).to_ne_bytes());
+
+    writer.put(WgPeerAttribute::PresharedKey.into(), &peer.preshared_key)
+        && writer.put(
+            WgPeerAttribute::LastHandshakeTime.into(),
+            &last_handshake_time,
+        )
+        && writer.put(
+            WgPeerAttribute::PersistentKeepaliveInterval.into(),
+            &peer.persistent_keepalive_interval.to_ne_bytes(),
+        )
+        && writer.put(
+            WgPeerAttribute::TxBytes.into(),
+            &peer.tx_bytes.to_ne_bytes(),
+        )
+        && writer.put(
+            WgPeerAttribute::RxBytes.into(),
+            &peer.rx_bytes.to_ne_bytes(),
+        )
+        && writer.put(
+            WgPeerAttribute::ProtocolVersion.into(),
+            &peer.protocol_version.to_ne_bytes(),
+        )
+        && match peer.endpoint {
+            Some(endpoint) => writer.put(WgPeerAttribute::Endpoint.into(), &sockaddr(endpoint)),
+            None => true,
+        }
+}
+
+fn put_allowed_ip(writer: &mut AttrWriter, allowed_ip: &get::AllowedIp) -> bool {
+    let start = match writer.nest_start(0) {
+        Some(start) => start,
+        None => return false,
+    };
+
+    let ipaddr = match allowed_ip.ipaddr {
+        IpAddr::V4(addr) => addr.octets().to_vec(),
+        IpAddr::V6(addr) => addr.octets().to_vec(),
+    };
+    let fits = writer.put(
+        WgAllowedIpAttribute::Family.into(),
+        &allowed_ip.family.to_ne_bytes(),
+    ) && writer.put(WgAllowedIpAttribute::IpAddr.into(), &ipaddr)
+        && writer.put(
+            WgAllowedIpAttribute::CidrMask.into(),
+            &[allowed_ip.cidr_mask],
+        );
+
+    if fits {
+        writer.nest_end(start);
+    } else {
+        writer.nest_cancel(start);
+    }
+    fits
+}
+
+/// Encodes an endpoint as a `struct sockaddr_in` or `struct sockaddr_in6`.
+fn sockaddr(endpoint: SocketAddr) -> Vec<u8> {
+    let mut payload = vec![];
+    match endpoint {
+        SocketAddr::V4(addr) => {
+            payload.extend(&(libc::AF_INET as u16).to_ne_bytes());
+            payload.extend(&addr.port().to_be_bytes());
+            payload.extend(&addr.ip().octets());
+            payload.extend(&[0u8; 8]);
+        }
+        SocketAddr::V6(addr) => {
+            payload.extend(&(libc::AF_INET6 as u16).to_ne_bytes());
+            payload.extend(&addr.port().to_be_bytes());
+            payload.extend(&addr.flowinfo().to_ne_bytes());
+            payload.extend(&addr.ip().octets());
+            payload.extend(&addr.scope_id().to_ne_bytes());
+        }
+    }
+    payload
+}
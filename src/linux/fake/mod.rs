@@ -0,0 +1,361 @@
+//! An in-memory stand-in for the WireGuard kernel module.
+//!
+//! [`FakeKernel`] answers the generic netlink requests [`WgSocket`] sends the
+//! same way the kernel does, so code built on top of [`WgSocket`] can be
+//! tested without root privileges or the kernel module:
+//!
+//! ```
+//! use wireguard_uapi::{fake::FakeKernel, set, DeviceInterface, WgSocket};
+//!
+//! let kernel = FakeKernel::new();
+//! kernel.add_device("wgtest0");
+//!
+//! let mut wg = WgSocket::from_transport(kernel.transport())?;
+//! wg.set_device(set::Device::from_ifname("wgtest0").listen_port(51820))?;
+//!
+//! let device = wg.get_device(DeviceInterface::from_name("wgtest0"))?;
+//! assert_eq!(device.listen_port, 51820);
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+//!
+//! Devices are created and deleted through [`FakeKernel`] directly since
+//! `NETLINK_ROUTE` requests aren't supported.
+//!
+//! [`WgSocket`]: crate::WgSocket
+mod attrs;
+mod device;
+mod dump;
+
+use crate::get;
+use crate::linux::attr::WgDeviceAttribute;
+use crate::linux::cmd::WgCmd;
+use crate::linux::consts::{NLM_F_CAPPED, WG_GENL_NAME};
+use crate::linux::socket::parse::{parse_nla_nul_string, parse_nla_u32};
+use crate::linux::socket::Transport;
+use crate::linux::DeviceInterface;
+use attrs::{collect_attrs, AttrWriter};
+use std::collections::VecDeque;
+use std::convert::TryInto;
+use std::io;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+/// The id the fake kernel assigns to the WireGuard generic netlink family.
+pub const WG_FAMILY_ID: u16 = 0x20;
+
+/// Dump messages are about as large as the kernel makes them on systems with
+/// 4 KiB pages.
+const DEFAULT_MAX_MESSAGE_SIZE: usize = 4096;
+
+/// Leaves room for the device attributes of an interface with the longest
+/// name the kernel allows, or for a peer with all of its attributes and its
+/// first allowed IP.
+pub const MIN_MAX_MESSAGE_SIZE: usize = 512;
+
+const NL_HEADER_SIZE: usize = 16;
+const GENL_HEADER_SIZE: usize = 4;
+
+/// The devices of a fake WireGuard kernel module. Clones share the same
+/// devices, so the kernel can be inspected while sockets are connected to it.
+#[derive(Clone)]
+pub struct FakeKernel {
+    state: Arc<Mutex<KernelState>>,
+}
+
+struct KernelState {
+    devices: Vec<get::Device>,
+    next_ifindex: u32,
+    max_message_size: usize,
+}
+
+impl Default for FakeKernel {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FakeKernel {
+    pub fn new() -> Self {
+        Self {
+            state: Arc::new(Mutex::new(KernelState {
+                devices: vec![],
+                next_ifindex: 1,
+                max_message_size: DEFAULT_MAX_MESSAGE_SIZE,
+            })),
+        }
+    }
+
+    fn state(&self) -> MutexGuard<'_, KernelState> {
+        // The state is never left inconsistent, so a panic in another thread
+        // doesn't matter.
+        self.state.lock().unwrap_or_else(|err| err.into_inner())
+    }
+
+    /// Creates a WireGuard device unless one with the same name exists
+    /// already. Returns the ifindex of the device.
+    pub fn add_device(&self, ifname: &str) -> u32 {
+        let mut state = self.state();
+        if let Some(device) = state.devices.iter().find(|device| device.ifname == ifname) {
+            return device.ifindex;
+        }
+
+        let ifindex = state.next_ifindex;
+        state.next_ifindex += 1;
+        state.devices.push(get::Device {
+            ifindex,
+            ifname: ifname.to_string(),
+            private_key: None,
+            public_key: None,
+            listen_port: 0,
+            fwmark: 0,
+            peers: vec![],
+        });
+        ifindex
+    }
+
+    /// Deletes a device. Returns whether it existed.
+    pub fn del_device(&self, interface: DeviceInterface) -> bool {
+        let mut state = self.state();
+        let len = state.devices.len();
+        state
+            .devices
+            .retain(|device| !interface_matches(&interface, device));
+        state.devices.len() != len
+    }
+
+    /// The current configuration of a device, as a dump would return it.
+    pub fn device(&self, interface: DeviceInterface) -> Option<get::Device> {
+        self.state()
+            .devices
+            .iter()
+            .find(|device| interface_matches(&interface, device))
+            .cloned()
+    }
+
+    /// Limits the size of the messages of a dump, which controls how many
+    /// peers and allowed IPs fit into each of them. Defaults to 4096 bytes.
+    /// Sizes below [`MIN_MAX_MESSAGE_SIZE`] are raised to it.
+    pub fn set_max_message_size(&self, max_message_size: usize) {
+        self.state().max_message_size = max_message_size.max(MIN_MAX_MESSAGE_SIZE);
+    }
+
+    /// Creates a transport for
+    /// [`WgSocket::from_transport`](crate::WgSocket::from_transport).
+    pub fn transport(&self) -> FakeTransport {
+        FakeTransport {
+            kernel: self.clone(),
+            replies: VecDeque::new(),
+        }
+    }
+
+    /// Handles a single request and returns the datagrams of the reply.
+    fn handle(&self, request: &[u8]) -> Vec<Vec<u8>> {
+        let header = match NlHeader::parse(request) {
+            Some(header) => header,
+            // The kernel drops messages it can't even find the header of.
+            None => return vec![],
+        };
+        let payload = &request[NL_HEADER_SIZE..header.len];
+
+        let result = match header.nl_type {
+            nl_type if nl_type == libc::GENL_ID_CTRL as u16 => self.handle_ctrl(&header, payload),
+            WG_FAMILY_ID => self.handle_wg(&header, payload),
+            _ => Err(libc::EOPNOTSUPP),
+        };
+
+        match result {
+            Ok(mut datagrams) => {
+                let is_dump = header.flags & libc::NLM_F_DUMP as u16 != 0;
+                if header.flags & libc::NLM_F_ACK as u16 != 0 && !is_dump {
+                    datagrams.push(error_message(&header, request, 0));
+                }
+                datagrams
+            }
+            Err(errno) => vec![error_message(&header, request, errno)],
+        }
+    }
+
+    fn handle_ctrl(&self, header: &NlHeader, payload: &[u8]) -> Result<Vec<Vec<u8>>, i32> {
+        let cmd = *payload.first().ok_or(libc::EINVAL)?;
+        if cmd != libc::CTRL_CMD_GETFAMILY as u8 {
+            return Err(libc::EOPNOTSUPP);
+        }
+
+        let attrs = collect_attrs(
+            payload.get(GENL_HEADER_SIZE..).unwrap_or_default(),
+            libc::CTRL_ATTR_FAMILY_NAME as u16,
+        );
+        let family_name = attrs[libc::CTRL_ATTR_FAMILY_NAME as usize]
+            .and_then(|name| parse_nla_nul_string(name).ok());
+        if family_name.as_deref() != Some(WG_GENL_NAME) {
+            return Err(libc::ENOENT);
+        }
+
+        let mut writer = AttrWriter::new(usize::MAX);
+        writer.put(
+            libc::CTRL_ATTR_FAMILY_ID as u16,
+            &WG_FAMILY_ID.to_ne_bytes(),
+        );
+        writer.put(
+            libc::CTRL_ATTR_FAMILY_NAME as u16,
+            format!("{}\0", WG_GENL_NAME).as_bytes(),
+        );
+        let mut reply = vec![libc::CTRL_CMD_NEWFAMILY as u8, 2, 0, 0];
+        reply.extend(writer.into_inner());
+
+        Ok(vec![message(
+            libc::GENL_ID_CTRL as u16,
+            0,
+            header.seq,
+            &reply,
+        )])
+    }
+
+    fn handle_wg(&self, header: &NlHeader, payload: &[u8]) -> Result<Vec<Vec<u8>>, i32> {
+        let cmd = *payload.first().ok_or(libc::EINVAL)?;
+        let attrs = collect_attrs(
+            payload.get(GENL_HEADER_SIZE..).unwrap_or_default(),
+            WgDeviceAttribute::Peers.into(),
+        );
+
+        let mut state = self.state();
+        let max_message_size = state.max_message_size;
+        let device = lookup_interface(&mut state.devices, &attrs)?;
+
+        match cmd {
+            cmd if cmd == u8::from(WgCmd::GetDevice) => {
+                // WG_CMD_GET_DEVICE is only implemented as a dump.
+                if header.flags & libc::NLM_F_DUMP as u16 == 0 {
+                    return Err(libc::EOPNOTSUPP);
+                }
+
+                let multi = libc::NLM_F_MULTI as u16;
+                let mut datagrams: Vec<Vec<u8>> =
+                    dump::dump_device(device, max_message_size - NL_HEADER_SIZE)?
+                        .iter()
+                        .map(|payload| message(WG_FAMILY_ID, multi, header.seq, payload))
+                        .collect();
+                let done = message(
+                    libc::NLMSG_DONE as u16,
+                    multi,
+                    header.seq,
+                    &0i32.to_ne_bytes(),
+                );
+                if let Some(last) = datagrams.last_mut() {
+                    last.extend(done);
+                }
+                Ok(datagrams)
+            }
+            cmd if cmd == u8::from(WgCmd::SetDevice) => {
+                device::set_device(device, &attrs)?;
+                Ok(vec![])
+            }
+            _ => Err(libc::EOPNOTSUPP),
+        }
+    }
+}
+
+/// A connection to a [`FakeKernel`]. Requests are handled as soon as they're
+/// sent and the replies are queued up until they're received.
+pub struct FakeTransport {
+    kernel: FakeKernel,
+    replies: VecDeque<Vec<u8>>,
+}
+
+impl Transport for FakeTransport {
+    fn send(&mut self, buf: &[u8]) -> io::Result<()> {
+        self.replies.extend(self.kernel.handle(buf));
+        Ok(())
+    }
+
+    fn recv(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let reply = self
+            .replies
+            .pop_front()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::WouldBlock, "No reply is pending"))?;
+        // Like a datagram socket, this truncates replies that don't fit.
+        let len = reply.len().min(buf.len());
+        buf[..len].copy_from_slice(&reply[..len]);
+        Ok(len)
+    }
+
+    fn capped_acks(&self) -> bool {
+        true
+    }
+}
+
+struct NlHeader {
+    len: usize,
+    nl_type: u16,
+    flags: u16,
+    seq: u32,
+}
+
+impl NlHeader {
+    fn parse(buf: &[u8]) -> Option<Self> {
+        let field = |range: std::ops::Range<usize>| buf.get(range);
+        let len = u32::from_ne_bytes(field(0..4)?.try_into().ok()?) as usize;
+        if len < NL_HEADER_SIZE || len > buf.len() {
+            return None;
+        }
+
+        Some(Self {
+            len,
+            nl_type: u16::from_ne_bytes(field(4..6)?.try_into().ok()?),
+            flags: u16::from_ne_bytes(field(6..8)?.try_into().ok()?),
+            seq: u32::from_ne_bytes(field(8..12)?.try_into().ok()?),
+        })
+    }
+}
+
+fn message(nl_type: u16, flags: u16, seq: u32, payload: &[u8]) -> Vec<u8> {
+    let len = NL_HEADER_SIZE + payload.len();
+    let mut message = Vec::with_capacity(len);
+    message.extend(&(len as u32).to_ne_bytes());
+    message.extend(&nl_type.to_ne_bytes());
+    message.extend(&flags.to_ne_bytes());
+    message.extend(&seq.to_ne_bytes());
+    message.extend(&0u32.to_ne_bytes());
+    message.extend(payload);
+    message.resize((message.len() + 3) & !3, 0);
+    message
+}
+
+/// Creates an `NLMSG_ERROR` reply, which is an ACK if `errno` is 0. Only the
+/// header of the request is echoed back since the transport reports capped
+/// ACKs.
+fn error_message(header: &NlHeader, request: &[u8], errno: i32) -> Vec<u8> {
+    let mut payload = (-errno).to_ne_bytes().to_vec();
+    payload.extend(&request[..NL_HEADER_SIZE]);
+    message(libc::NLMSG_ERROR as u16, NLM_F_CAPPED, header.seq, &payload)
+}
+
+fn interface_matches(interface: &DeviceInterface, device: &get::Device) -> bool {
+    match interface {
+        DeviceInterface::Index(ifindex) => device.ifindex == *ifindex,
+        DeviceInterface::Name(ifname) => device.ifname == *ifname,
+    }
+}
+
+/// Finds the device a request is for by exactly one of its ifindex or ifname.
+fn lookup_interface<'a>(
+    devices: &'a mut [get::Device],
+    attrs: &[Option<&[u8]>],
+) -> Result<&'a mut get::Device, i32> {
+    let ifindex = attrs[usize::from(u16::from(WgDeviceAttribute::Ifindex))];
+    let ifname = attrs[usize::from(u16::from(WgDeviceAttribute::Ifname))];
+
+    let interface = match (ifindex, ifname) {
+        (Some(ifindex), None) => {
+            DeviceInterface::from_index(parse_nla_u32(ifindex).map_err(|_| libc::EINVAL)?)
+        }
+        (None, Some(ifname)) => {
+            DeviceInterface::from_name(parse_nla_nul_string(ifname).map_err(|_| libc::EINVAL)?)
+        }
+        _ => return Err(libc::EBADR),
+    };
+
+    devices
+        .iter_mut()
+        .find(|device| interface_matches(&interface, device))
+        .ok_or(libc::ENODEV)
+}
@@ -18,6 +18,21 @@ impl<'a> DeviceInterface<'a> {
     pub fn from_name<T: Into<Cow<'a, str>>>(name: T) -> Self {
         DeviceInterface::Name(name.into())
     }
+
+    /// Whether the kernel could accept this interface. Indexes are checked by
+    /// the kernel, so only names are checked here.
+    pub(crate) fn is_valid(&self) -> bool {
+        match self {
+            DeviceInterface::Index(_) => true,
+            DeviceInterface::Name(name) => is_valid_ifname(name),
+        }
+    }
+}
+
+/// Interface names must be 1 to IFNAMSIZ-1 bytes long, leaving room for the
+/// terminating NUL.
+pub(crate) fn is_valid_ifname(ifname: &str) -> bool {
+    !ifname.is_empty() && ifname.len() < libc::IFNAMSIZ
 }
 
 impl<'a> TryFrom<&DeviceInterface<'a>> for Nlattr<WgDeviceAttribute, Buffer> {
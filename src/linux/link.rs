@@ -0,0 +1,68 @@
+use std::borrow::Cow;
+use std::os::unix::io::RawFd;
+
+/// The network namespace a new device interface should be created in.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum NetNs {
+    /// A file descriptor referring to a namespace, such as an open handle to
+    /// `/proc/<pid>/ns/net` or `/var/run/netns/<name>`.
+    Fd(RawFd),
+    /// The namespace of the process with this PID.
+    Pid(u32),
+}
+
+/// Options for creating a WireGuard device interface through
+/// [`RouteSocket::create_device`](crate::RouteSocket::create_device).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Device<'a> {
+    pub ifname: Cow<'a, str>,
+    /// Leave unset to use the kernel's default MTU for WireGuard devices.
+    pub mtu: Option<u32>,
+    /// Leave unset to let the kernel pick the next free ifindex.
+    pub ifindex: Option<u32>,
+    pub netns: Option<NetNs>,
+    /// Whether the interface should be brought up (IFF_UP) once it's created.
+    pub up: bool,
+    /// Succeed without changing anything if a WireGuard device interface with
+    /// this name already exists. Other kinds of interfaces with the same name
+    /// are still reported as an error.
+    pub ensure_exists: bool,
+}
+
+impl<'a> Device<'a> {
+    pub fn from_ifname<T: Into<Cow<'a, str>>>(ifname: T) -> Self {
+        Self {
+            ifname: ifname.into(),
+            mtu: None,
+            ifindex: None,
+            netns: None,
+            up: false,
+            ensure_exists: false,
+        }
+    }
+
+    pub fn mtu(mut self, mtu: u32) -> Self {
+        self.mtu = Some(mtu);
+        self
+    }
+
+    pub fn ifindex(mut self, ifindex: u32) -> Self {
+        self.ifindex = Some(ifindex);
+        self
+    }
+
+    pub fn netns(mut self, netns: NetNs) -> Self {
+        self.netns = Some(netns);
+        self
+    }
+
+    pub fn up(mut self, up: bool) -> Self {
+        self.up = up;
+        self
+    }
+
+    pub fn ensure_exists(mut self, ensure_exists: bool) -> Self {
+        self.ensure_exists = ensure_exists;
+        self
+    }
+}
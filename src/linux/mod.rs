@@ -1,10 +1,21 @@
 mod attr;
 mod cmd;
+pub mod codec;
 mod consts;
 pub mod err;
+#[cfg(feature = "fake")]
+pub mod fake;
 mod interface;
+pub mod link;
 pub mod set;
 mod socket;
+pub mod stats;
 
+pub(crate) use interface::is_valid_ifname;
 pub use interface::DeviceInterface;
-pub use socket::{RouteSocket, WgSocket};
+#[cfg(feature = "tokio")]
+pub use socket::{AsyncRouteSocket, AsyncWgSocket};
+pub use socket::{
+    DeviceNamesRequest, DeviceRequest, DeviceResults, NetlinkTransport, PeerIter, RouteSocket,
+    Transport, WgSocket,
+};
@@ -1,4 +1,4 @@
-use super::{AllowedIp, Device, Peer};
+use super::{AllowedIp, Device, Peer, WgPeerF};
 use crate::linux::attr::NLA_F_NESTED;
 use crate::linux::attr::{NlaNested, WgDeviceAttribute, WgPeerAttribute};
 use crate::linux::cmd::WgCmd;
@@ -15,21 +15,37 @@ use neli::{
     Size,
 };
 use std::convert::TryInto;
+use std::mem::size_of;
 use std::net::SocketAddr;
 
-// TODO: Remove these constants and use something from libc.
-const NETLINK_HEADER_SIZE: usize = 16;
-const GENL_HEADER_SIZE: usize = 4;
-const NETLINK_MSG_LIMIT: usize = 65_536; // 2^16
+const NETLINK_HEADER_SIZE: usize = size_of::<libc::nlmsghdr>();
+const GENL_HEADER_SIZE: usize = size_of::<libc::genlmsghdr>();
+const NLA_HEADER_SIZE: usize = size_of::<libc::nlattr>();
+
+/// All peers of a message are nested in a single attribute, whose length has
+/// to fit into the u16 of its header.
+pub(crate) const NETLINK_MSG_LIMIT: usize = 65_536; // 2^16
+
+/// The smallest message size that still fits the attributes of a device, one
+/// peer with all of its attributes, and one allowed IP.
+const MIN_NETLINK_MSG_SIZE: usize = 512;
 
 type NlWgMessage = Nlmsghdr<NlWgMsgType, Genlmsghdr<WgCmd, WgDeviceAttribute>>;
 
+/// One message of a set request, along with the public keys of the peers it
+/// carries, so errors can be traced back to the peers the kernel rejected.
+pub(crate) struct SetDeviceMessage {
+    pub message: NlWgMessage,
+    pub public_keys: Vec<[u8; 32]>,
+}
+
 /// A struct containing information necessary to build a set_device message fragment. It keeps
 /// track of an initial bag of partial_device but keeps peers separate until they're ready to be
 // added in.
 struct IncubatingDeviceFragment {
     partial_device: GenlBuffer<WgDeviceAttribute, Buffer>,
     peers: Nlattr<WgDeviceAttribute, Buffer>,
+    public_keys: Vec<[u8; 32]>,
 }
 
 impl IncubatingDeviceFragment {
@@ -92,6 +108,7 @@ impl IncubatingDeviceFragment {
                 WgDeviceAttribute::Peers | NLA_F_NESTED,
                 vec![],
             )?,
+            public_keys: vec![],
         };
 
         Ok((incubating_device, device.peers))
@@ -112,9 +129,20 @@ impl IncubatingDeviceFragment {
                 WgDeviceAttribute::Peers | NLA_F_NESTED,
                 vec![],
             )?,
+            public_keys: vec![],
         })
     }
 
+    fn add_peer(
+        &mut self,
+        public_key: &[u8; 32],
+        peer: &Nlattr<NlaNested, Buffer>,
+    ) -> Result<(), NlError> {
+        self.peers.add_nested_attribute(peer)?;
+        self.public_keys.push(*public_key);
+        Ok(())
+    }
+
     fn incubating_size(&self) -> usize {
         let attrs_size: usize = self
             .partial_device
@@ -125,11 +153,11 @@ impl IncubatingDeviceFragment {
         NETLINK_HEADER_SIZE + GENL_HEADER_SIZE + attrs_size + self.peers.padded_size()
     }
 
-    fn finalize(self, family_id: NlWgMsgType) -> Result<NlWgMessage, NlError> {
+    fn finalize(self, family_id: NlWgMsgType) -> Result<SetDeviceMessage, NlError> {
         let mut device_attrs = self.partial_device;
 
         // TODO: Condition this behavior on whether peers have ever been added.
-        if self.peers.unpadded_size() > GENL_HEADER_SIZE {
+        if self.peers.unpadded_size() > NLA_HEADER_SIZE {
             device_attrs.push(self.peers);
         }
 
@@ -148,7 +176,10 @@ impl IncubatingDeviceFragment {
             Nlmsghdr::new(size, nl_type, flags, seq, pid, payload)
         };
 
-        Ok(nlhdr)
+        Ok(SetDeviceMessage {
+            message: nlhdr,
+            public_keys: self.public_keys,
+        })
     }
 }
 
@@ -262,7 +293,11 @@ impl IncubatingPeerFragment {
         Ok((incubating_peer_fragment, peer.allowed_ips))
     }
 
-    fn from_public_key(public_key: &[u8; 32]) -> Result<Self, NlError> {
+    /// Starts a peer that continues the allowed IPs of a peer from the previous
+    /// message. Its other attributes and flags were applied by that message
+    /// already. WGPEER_F_UPDATE_ONLY keeps the continuation from creating the
+    /// peer if the previous message skipped or removed it.
+    fn continuation(public_key: &[u8; 32]) -> Result<Self, NlError> {
         let mut partial_peer =
             Nlattr::new::<Vec<u8>>(false, false, NlaNested::Unspec | NLA_F_NESTED, vec![])?;
         let allowed_ips = Nlattr::new::<Vec<u8>>(
@@ -279,6 +314,12 @@ impl IncubatingPeerFragment {
             public_key.to_vec(),
         )?;
         partial_peer.add_nested_attribute(&public_key)?;
+        partial_peer.add_nested_attribute(&Nlattr::new(
+            false,
+            NLA_NETWORK_ORDER,
+            WgPeerAttribute::Flags,
+            WgPeerF::UpdateOnly as u32,
+        )?)?;
 
         Ok(IncubatingPeerFragment {
             partial_peer,
@@ -292,17 +333,27 @@ impl IncubatingPeerFragment {
 
     fn finalize(self) -> Result<Nlattr<NlaNested, Buffer>, NlError> {
         let mut partial_peer = self.partial_peer;
-        if self.allowed_ips.padded_size() > GENL_HEADER_SIZE {
+        if self.allowed_ips.padded_size() > NLA_HEADER_SIZE {
             partial_peer.add_nested_attribute(&self.allowed_ips)?;
         }
         Ok(partial_peer)
     }
 }
 
+/// Raises or lowers a message size limit into the range set requests can be
+/// split into.
+pub fn clamp_message_size(max_message_size: usize) -> usize {
+    max_message_size.clamp(MIN_NETLINK_MSG_SIZE, NETLINK_MSG_LIMIT)
+}
+
+/// Splits a set request into messages of at most `max_message_size` bytes,
+/// after passing it through [`clamp_message_size`].
 pub fn create_set_device_messages(
     device: Device,
     family_id: NlWgMsgType,
-) -> Result<Vec<NlWgMessage>, NlError> {
+    max_message_size: usize,
+) -> Result<Vec<SetDeviceMessage>, NlError> {
+    let limit = clamp_message_size(max_message_size);
     let mut messages = vec![];
 
     // All the device fragments we generate here will have the same interface. Before moving the
@@ -319,7 +370,7 @@ pub fn create_set_device_messages(
 
         let next_size = incubating_device_fragment.incubating_size()
             + incubating_peer_fragment.incubating_size();
-        if next_size > NETLINK_MSG_LIMIT {
+        if next_size > limit {
             let device_message = incubating_device_fragment.finalize(family_id)?;
             messages.push(device_message);
             incubating_device_fragment = IncubatingDeviceFragment::from_interface(&interface)?;
@@ -331,17 +382,15 @@ pub fn create_set_device_messages(
             let next_size = incubating_device_fragment.incubating_size()
                 + incubating_peer_fragment.incubating_size()
                 + allowed_ip_attr.padded_size();
-            if next_size > NETLINK_MSG_LIMIT {
+            if next_size > limit {
                 let peer_fragment = incubating_peer_fragment.finalize()?;
-                incubating_device_fragment
-                    .peers
-                    .add_nested_attribute(&peer_fragment)?;
+                incubating_device_fragment.add_peer(public_key, &peer_fragment)?;
 
                 let device_message = incubating_device_fragment.finalize(family_id)?;
                 messages.push(device_message);
 
                 incubating_device_fragment = IncubatingDeviceFragment::from_interface(&interface)?;
-                incubating_peer_fragment = IncubatingPeerFragment::from_public_key(public_key)?;
+                incubating_peer_fragment = IncubatingPeerFragment::continuation(public_key)?;
             }
 
             incubating_peer_fragment
@@ -350,9 +399,7 @@ pub fn create_set_device_messages(
         }
 
         let peer_attr = incubating_peer_fragment.finalize()?;
-        incubating_device_fragment
-            .peers
-            .add_nested_attribute(&peer_attr)?;
+        incubating_device_fragment.add_peer(public_key, &peer_attr)?;
     }
 
     let device_message = incubating_device_fragment.finalize(family_id)?;
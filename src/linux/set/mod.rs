@@ -4,6 +4,10 @@ mod device;
 pub use device::{Device, WgDeviceF};
 mod peer;
 pub use peer::{Peer, WgPeerF};
+#[cfg(feature = "xplatform")]
+mod xplatform_conversion;
 
 mod create_set_device_messages;
-pub(crate) use create_set_device_messages::create_set_device_messages;
+pub(crate) use create_set_device_messages::{
+    clamp_message_size, create_set_device_messages, SetDeviceMessage, NETLINK_MSG_LIMIT,
+};
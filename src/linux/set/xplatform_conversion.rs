@@ -0,0 +1,233 @@
+//! Conversions between the set requests of the Linux and the cross-platform
+//! APIs. The boolean options of the cross-platform requests correspond to the
+//! flags of the Linux requests.
+//!
+//! A cross-platform request survives the round trip through a Linux request
+//! unchanged. Its options are only ever `Some(true)` or `None`, since flags
+//! can't be set to `false` in the protocol, and requests with `Some(false)`
+//! are rejected.
+//!
+//! The other way around, a Linux request comes back as one the kernel can't
+//! tell apart from the original: the flags come back in a fixed order, and an
+//! allowed IP without a CIDR mask comes back with the full-length mask that's
+//! sent to the kernel in its place.
+
+use crate::err::FromXplatformError;
+use crate::set::{AllowedIp, Device, Peer, WgDeviceF, WgPeerF};
+use crate::xplatform::protocol::SetKey;
+use crate::xplatform::set as xplatform;
+use crate::DeviceInterface;
+use std::convert::TryFrom;
+use std::net::IpAddr;
+
+impl<'a> Device<'a> {
+    /// Borrows a cross-platform set request as a request for `interface`.
+    pub fn try_from_xplatform(
+        interface: DeviceInterface<'a>,
+        device: &'a xplatform::Device,
+    ) -> Result<Self, FromXplatformError> {
+        let mut flags = vec![];
+        if flag(SetKey::ReplacePeers, device.replace_peers)? {
+            flags.push(WgDeviceF::ReplacePeers);
+        }
+
+        Ok(Self {
+            interface,
+            flags,
+            private_key: device.private_key.as_ref(),
+            listen_port: device.listen_port,
+            fwmark: device.fwmark,
+            peers: device
+                .peers
+                .iter()
+                .map(Peer::try_from)
+                .collect::<Result<_, _>>()?,
+        })
+    }
+}
+
+impl<'a> TryFrom<&'a xplatform::Peer> for Peer<'a> {
+    type Error = FromXplatformError;
+
+    fn try_from(peer: &'a xplatform::Peer) -> Result<Self, Self::Error> {
+        let mut flags = vec![];
+        if flag(SetKey::Remove, peer.remove)? {
+            flags.push(WgPeerF::RemoveMe);
+        }
+        if flag(SetKey::ReplaceAllowedIps, peer.replace_allowed_ips)? {
+            flags.push(WgPeerF::ReplaceAllowedIps);
+        }
+        if flag(SetKey::UpdateOnly, peer.update_only)? {
+            flags.push(WgPeerF::UpdateOnly);
+        }
+
+        Ok(Self {
+            public_key: &peer.public_key,
+            flags,
+            preshared_key: peer.preshared_key.as_ref(),
+            endpoint: peer.endpoint.as_ref(),
+            persistent_keepalive_interval: peer.persistent_keepalive_interval,
+            allowed_ips: peer.allowed_ips.iter().map(AllowedIp::from).collect(),
+            protocol_version: peer.protocol_version,
+        })
+    }
+}
+
+impl<'a> From<&'a xplatform::AllowedIp> for AllowedIp<'a> {
+    fn from(allowed_ip: &'a xplatform::AllowedIp) -> Self {
+        Self {
+            ipaddr: &allowed_ip.ipaddr,
+            cidr_mask: Some(allowed_ip.cidr_mask),
+        }
+    }
+}
+
+/// Whether a flag is set. `false` isn't a value flags can have in the
+/// protocol.
+fn flag(key: SetKey, value: Option<bool>) -> Result<bool, FromXplatformError> {
+    match value {
+        Some(true) => Ok(true),
+        Some(false) => Err(FromXplatformError::FalseFlag(key)),
+        None => Ok(false),
+    }
+}
+
+/// The interface isn't part of a cross-platform request, since it's given by
+/// the socket the request is sent to.
+impl From<&Device<'_>> for xplatform::Device {
+    fn from(device: &Device<'_>) -> Self {
+        Self {
+            private_key: device.private_key.copied(),
+            listen_port: device.listen_port,
+            fwmark: device.fwmark,
+            replace_peers: option(device.flags.contains(&WgDeviceF::ReplacePeers)),
+            peers: device.peers.iter().map(xplatform::Peer::from).collect(),
+        }
+    }
+}
+
+impl From<&Peer<'_>> for xplatform::Peer {
+    fn from(peer: &Peer<'_>) -> Self {
+        Self {
+            public_key: *peer.public_key,
+            remove: option(peer.flags.contains(&WgPeerF::RemoveMe)),
+            update_only: option(peer.flags.contains(&WgPeerF::UpdateOnly)),
+            preshared_key: peer.preshared_key.copied(),
+            endpoint: peer.endpoint.copied(),
+            persistent_keepalive_interval: peer.persistent_keepalive_interval,
+            replace_allowed_ips: option(peer.flags.contains(&WgPeerF::ReplaceAllowedIps)),
+            allowed_ips: peer
+                .allowed_ips
+                .iter()
+                .map(xplatform::AllowedIp::from)
+                .collect(),
+            protocol_version: peer.protocol_version,
+        }
+    }
+}
+
+/// A missing mask is sent to the kernel as the full-length mask, so that's
+/// what it becomes here.
+impl From<&AllowedIp<'_>> for xplatform::AllowedIp {
+    fn from(allowed_ip: &AllowedIp<'_>) -> Self {
+        Self {
+            ipaddr: *allowed_ip.ipaddr,
+            cidr_mask: allowed_ip.cidr_mask.unwrap_or(match allowed_ip.ipaddr {
+                IpAddr::V4(_) => 32,
+                IpAddr::V6(_) => 128,
+            }),
+        }
+    }
+}
+
+fn option(is_set: bool) -> Option<bool> {
+    is_set.then_some(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::err::FromXplatformError;
+    use crate::set::{AllowedIp, Device, Peer, WgDeviceF, WgPeerF};
+    use crate::xplatform::protocol::SetKey;
+    use crate::xplatform::set as xplatform;
+    use crate::DeviceInterface;
+
+    fn xplatform_device() -> xplatform::Device {
+        xplatform::Device {
+            private_key: Some([1u8; 32]),
+            listen_port: Some(51820),
+            fwmark: Some(0),
+            replace_peers: Some(true),
+            peers: vec![
+                xplatform::Peer::from_public_key([2u8; 32])
+                    .update_only(true)
+                    .replace_allowed_ips(true)
+                    .preshared_key([3u8; 32])
+                    .endpoint("[::1]:51820".parse().unwrap())
+                    .persistent_keepalive_interval(25)
+                    .allowed_ips(vec![
+                        xplatform::AllowedIp {
+                            ipaddr: "10.0.0.0".parse().unwrap(),
+                            cidr_mask: 8,
+                        },
+                        xplatform::AllowedIp {
+                            ipaddr: "fd00::1".parse().unwrap(),
+                            cidr_mask: 128,
+                        },
+                    ])
+                    .protocol_version(1),
+                xplatform::Peer::from_public_key([4u8; 32]).remove(true),
+            ],
+        }
+    }
+
+    #[test]
+    fn xplatform_round_trip() -> anyhow::Result<()> {
+        let expected = xplatform_device();
+        let device = Device::try_from_xplatform(DeviceInterface::from_name("wgtest0"), &expected)?;
+
+        assert_eq!(device.interface, DeviceInterface::from_name("wgtest0"));
+        assert_eq!(device.flags, vec![WgDeviceF::ReplacePeers]);
+        assert_eq!(
+            device.peers[0].flags,
+            vec![WgPeerF::ReplaceAllowedIps, WgPeerF::UpdateOnly]
+        );
+        assert_eq!(device.peers[1].flags, vec![WgPeerF::RemoveMe]);
+
+        assert_eq!(xplatform::Device::from(&device), expected);
+
+        Ok(())
+    }
+
+    #[test]
+    fn false_flags_are_rejected() {
+        let mut device = xplatform_device();
+        device.peers[1].remove = Some(false);
+
+        let result = Device::try_from_xplatform(DeviceInterface::from_index(4), &device);
+        assert!(matches!(
+            result,
+            Err(FromXplatformError::FalseFlag(SetKey::Remove))
+        ));
+    }
+
+    #[test]
+    fn linux_to_xplatform() {
+        let (public_key, ipaddr) = ([5u8; 32], "192.0.2.1".parse().unwrap());
+        let device = Device::from_ifindex(4).peers(vec![Peer::from_public_key(&public_key)
+            .flags(vec![WgPeerF::UpdateOnly])
+            .allowed_ips(vec![AllowedIp::from_ipaddr(&ipaddr)])]);
+
+        let converted = xplatform::Device::from(&device);
+        assert_eq!(converted.replace_peers, None);
+        assert_eq!(converted.peers[0].update_only, Some(true));
+        assert_eq!(converted.peers[0].remove, None);
+        assert_eq!(
+            converted.peers[0].allowed_ips,
+            vec![xplatform::AllowedIp {
+                ipaddr,
+                cidr_mask: 32
+            }]
+        );
+    }
+}
@@ -0,0 +1,149 @@
+use super::ext_ack::{enable_ext_ack, parse_nlmsgerr};
+use super::transport::max_send_size;
+use crate::err::KernelError;
+use neli::{
+    consts::{nl::Nlmsg, socket::NlFamily},
+    err::{DeError, NlError, SerError},
+    nl::{NlPayload, Nlmsghdr},
+    socket::{tokio::NlSocket as TokioNlSocket, NlSocket},
+    types::Buffer,
+    Size, ToBytes,
+};
+use std::io;
+
+/// A non-blocking netlink socket registered with the tokio reactor. This is
+/// the async counterpart of `NlSocketHandle::{send, recv, iter}` as the sync
+/// sockets use them, including the decoding of `NLMSG_ERROR` replies.
+///
+/// Every request gets its own sequence number and replies are matched on it,
+/// so replies to a request whose future was dropped don't end up being read
+/// as the reply to the next one.
+pub struct AsyncNlSocket {
+    sock: TokioNlSocket,
+    capped_acks: bool,
+    max_message_size: usize,
+    buffer: Vec<u8>,
+    seq: u32,
+}
+
+impl AsyncNlSocket {
+    pub fn connect(family: NlFamily) -> io::Result<Self> {
+        // Autoselect a PID
+        let pid = None;
+        let groups = &[];
+        let sock = NlSocket::connect(family, pid, groups)?;
+        let capped_acks = enable_ext_ack(&sock);
+        let max_message_size = max_send_size(&sock);
+
+        Ok(Self {
+            sock: TokioNlSocket::new(sock)?,
+            capped_acks,
+            max_message_size,
+            buffer: vec![],
+            seq: 0,
+        })
+    }
+
+    /// See `NlConnection::next_seq`.
+    fn next_seq(&mut self) -> u32 {
+        self.seq = self.seq.wrapping_add(1);
+        self.seq
+    }
+
+    pub fn max_message_size(&self) -> usize {
+        self.max_message_size
+    }
+
+    /// Numbers `msg` with the next sequence number and sends it. Pass the
+    /// returned number to one of the `recv_*` calls to receive the reply.
+    pub async fn send<T, P>(&mut self, mut msg: Nlmsghdr<T, P>) -> Result<u32, SerError>
+    where
+        T: neli::consts::nl::NlType,
+        P: Size + ToBytes,
+    {
+        let seq = self.next_seq();
+        msg.nl_seq = seq;
+        self.sock.send(&msg).await?;
+        Ok(seq)
+    }
+
+    /// Receives the replies to the request numbered `seq` that arrived in a
+    /// single datagram, as they are. Replies to other requests are skipped.
+    pub async fn recv_messages(&mut self, seq: u32) -> Result<Vec<Nlmsghdr<u16, Buffer>>, DeError> {
+        loop {
+            let messages = self.sock.recv::<u16, Buffer>(&mut self.buffer).await?;
+            let replies: Vec<_> = messages
+                .into_iter()
+                .filter(|message| message.nl_seq == seq)
+                .collect();
+            if !replies.is_empty() {
+                return Ok(replies);
+            }
+        }
+    }
+
+    /// Like [`recv_messages`](Self::recv_messages), but a `NLMSG_ERROR`
+    /// message with a non-zero errno is returned as an error.
+    async fn recv_replies<E>(&mut self, seq: u32) -> Result<Vec<Nlmsghdr<u16, Buffer>>, E>
+    where
+        E: From<NlError> + From<KernelError>,
+    {
+        let mut result = vec![];
+        for message in self.recv_messages(seq).await.map_err(NlError::from)? {
+            if let NlPayload::Err(err) = &message.nl_payload {
+                return Err(parse_nlmsgerr(err, self.capped_acks).into());
+            }
+            result.push(message);
+        }
+        Ok(result)
+    }
+
+    /// Waits for the ACK of the request numbered `seq`, which was sent with
+    /// `NLM_F_ACK`.
+    pub async fn recv_ack<E>(&mut self, seq: u32) -> Result<(), E>
+    where
+        E: From<NlError> + From<KernelError>,
+    {
+        loop {
+            for message in self.recv_replies::<E>(seq).await? {
+                if let NlPayload::Ack(_) = message.nl_payload {
+                    return Ok(());
+                }
+            }
+        }
+    }
+
+    /// Receives the reply to the request numbered `seq`, which is answered
+    /// with a single message.
+    pub async fn recv_one<E>(&mut self, seq: u32) -> Result<Buffer, E>
+    where
+        E: From<NlError> + From<KernelError>,
+    {
+        loop {
+            for message in self.recv_replies::<E>(seq).await? {
+                if let NlPayload::Payload(payload) = message.nl_payload {
+                    return Ok(payload);
+                }
+            }
+        }
+    }
+
+    /// Receives the payloads of the dump requested by the request numbered
+    /// `seq` up until the `NLMSG_DONE` message.
+    pub async fn recv_dump<E>(&mut self, seq: u32) -> Result<Vec<Buffer>, E>
+    where
+        E: From<NlError> + From<KernelError>,
+    {
+        let mut payloads = vec![];
+        loop {
+            for message in self.recv_replies::<E>(seq).await? {
+                if message.nl_type == Nlmsg::Done.into() {
+                    return Ok(payloads);
+                }
+                if let NlPayload::Payload(payload) = message.nl_payload {
+                    payloads.push(payload);
+                }
+            }
+        }
+    }
+}
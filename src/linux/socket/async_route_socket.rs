@@ -0,0 +1,169 @@
+use super::async_nl_socket::AsyncNlSocket;
+use super::link_info_utils::{get_link_msg, LinkInfo};
+use super::list_device_names_utils;
+use super::{del_link_message, new_link_message};
+use crate::err::{
+    ConnectError, GetLinkStatsError, KernelError, LinkDeviceError, ListDevicesError,
+    ParseAttributeError,
+};
+use crate::linux::link;
+use crate::linux::stats::LinkStats;
+use crate::linux::{is_valid_ifname, DeviceInterface};
+use neli::{
+    consts::socket::NlFamily,
+    err::{DeError, NlError, SerError},
+};
+
+/// The async version of [`RouteSocket`](crate::RouteSocket).
+///
+/// Like [`AsyncWgSocket`](crate::AsyncWgSocket), the socket can be used again
+/// after one of its futures is dropped, but the dropped request may still be
+/// carried out by the kernel.
+pub struct AsyncRouteSocket {
+    sock: AsyncNlSocket,
+}
+
+impl AsyncRouteSocket {
+    pub async fn connect() -> Result<Self, ConnectError> {
+        let sock = AsyncNlSocket::connect(NlFamily::Route)?;
+        Ok(Self { sock })
+    }
+
+    pub async fn add_device(&mut self, ifname: &str) -> Result<(), LinkDeviceError> {
+        self.create_device(&link::Device::from_ifname(ifname))
+            .await?;
+        Ok(())
+    }
+
+    /// See [`RouteSocket::create_device`](crate::RouteSocket::create_device).
+    pub async fn create_device(
+        &mut self,
+        device: &link::Device<'_>,
+    ) -> Result<u32, LinkDeviceError> {
+        if !is_valid_ifname(&device.ifname) {
+            return Err(LinkDeviceError::InvalidInterfaceName);
+        }
+
+        if device.netns.is_some() && (device.ifindex.is_none() || device.ensure_exists) {
+            return Err(LinkDeviceError::UnsupportedNetNsOptions);
+        }
+
+        let interface = DeviceInterface::from_name(device.ifname.as_ref());
+
+        if device.ensure_exists {
+            match self.get_link_info::<LinkDeviceError>(&interface).await {
+                Ok(link_info) if link_info.is_wireguard() => return Ok(link_info.ifindex),
+                Ok(link_info) => return Err(LinkDeviceError::NotWireGuard(link_info.ifindex)),
+                Err(LinkDeviceError::KernelError(err)) if err.raw_os_error() == libc::ENODEV => {}
+                Err(err) => return Err(err),
+            }
+        }
+
+        let seq = self.sock.send(new_link_message(device)?).await?;
+        self.sock.recv_ack::<LinkDeviceError>(seq).await?;
+
+        match device.ifindex {
+            Some(ifindex) => Ok(ifindex),
+            None => Ok(self
+                .get_link_info::<LinkDeviceError>(&interface)
+                .await?
+                .ifindex),
+        }
+    }
+
+    /// See [`RouteSocket::del_device`](crate::RouteSocket::del_device).
+    pub async fn del_device(&mut self, ifname: &str) -> Result<(), LinkDeviceError> {
+        self.del_wireguard_device(DeviceInterface::from_name(ifname))
+            .await
+    }
+
+    /// See [`RouteSocket::del_device_by_index`](crate::RouteSocket::del_device_by_index).
+    pub async fn del_device_by_index(&mut self, ifindex: u32) -> Result<(), LinkDeviceError> {
+        self.del_wireguard_device(DeviceInterface::from_index(ifindex))
+            .await
+    }
+
+    /// Deletes an interface without checking that it's a WireGuard device.
+    pub async fn del_device_unchecked(
+        &mut self,
+        interface: DeviceInterface<'_>,
+    ) -> Result<(), LinkDeviceError> {
+        if !interface.is_valid() {
+            return Err(LinkDeviceError::InvalidInterfaceName);
+        }
+
+        let seq = self.sock.send(del_link_message(&interface)?).await?;
+        self.sock.recv_ack::<LinkDeviceError>(seq).await
+    }
+
+    async fn del_wireguard_device(
+        &mut self,
+        interface: DeviceInterface<'_>,
+    ) -> Result<(), LinkDeviceError> {
+        if !interface.is_valid() {
+            return Err(LinkDeviceError::InvalidInterfaceName);
+        }
+
+        let link_info = self.get_link_info::<LinkDeviceError>(&interface).await?;
+        if !link_info.is_wireguard() {
+            return Err(LinkDeviceError::NotWireGuard(link_info.ifindex));
+        }
+
+        // Delete by the index that was just checked so a rename in between
+        // can't redirect the request to another interface.
+        self.del_device_unchecked(DeviceInterface::from_index(link_info.ifindex))
+            .await
+    }
+
+    /// See [`RouteSocket::list_device_names`](crate::RouteSocket::list_device_names).
+    pub async fn list_device_names(&mut self) -> Result<Vec<String>, ListDevicesError> {
+        let seq = self
+            .sock
+            .send(list_device_names_utils::get_list_device_names_msg())
+            .await?;
+
+        let mut result_names = vec![];
+
+        for payload in self.sock.recv_dump::<ListDevicesError>(seq).await? {
+            let link_info = LinkInfo::parse::<ListDevicesError>(payload.as_ref())?;
+
+            if link_info.is_wireguard() {
+                if let Some(ifname) = link_info.ifname {
+                    result_names.push(ifname);
+                }
+            }
+        }
+
+        Ok(result_names)
+    }
+
+    /// See [`RouteSocket::get_link_stats`](crate::RouteSocket::get_link_stats).
+    pub async fn get_link_stats(
+        &mut self,
+        interface: DeviceInterface<'_>,
+    ) -> Result<LinkStats, GetLinkStatsError> {
+        if !interface.is_valid() {
+            return Err(GetLinkStatsError::InvalidInterfaceName);
+        }
+
+        let link_info = self.get_link_info::<GetLinkStatsError>(&interface).await?;
+
+        if !link_info.is_wireguard() {
+            return Err(GetLinkStatsError::NotWireGuard(link_info.ifindex));
+        }
+
+        link_info
+            .stats
+            .ok_or(GetLinkStatsError::MissingStats(link_info.ifindex))
+    }
+
+    async fn get_link_info<E>(&mut self, interface: &DeviceInterface<'_>) -> Result<LinkInfo, E>
+    where
+        E: From<NlError> + From<KernelError> + From<SerError> + From<DeError>,
+        E: From<ParseAttributeError>,
+    {
+        let seq = self.sock.send(get_link_msg(interface)?).await?;
+        let payload = self.sock.recv_one::<E>(seq).await?;
+        LinkInfo::parse::<E>(payload.as_ref())
+    }
+}
@@ -0,0 +1,153 @@
+use super::async_nl_socket::AsyncNlSocket;
+use super::genl_family_utils::{
+    family_not_found, get_family_msg, parse_family_id, ResolveFamilyError,
+};
+use super::get_device_utils::{extend_device_with_payload, get_device_msg};
+use super::wg_socket::{fails_all_devices, reject_fragment};
+use super::{AsyncRouteSocket, NlWgMsgType};
+use crate::get;
+use crate::linux::consts::WG_GENL_NAME;
+use crate::linux::err::{
+    ConnectError, GetAllDevicesError, GetDeviceError, GetDeviceReportError, SetDeviceError,
+};
+use crate::linux::set;
+use crate::linux::set::{clamp_message_size, create_set_device_messages, SetDeviceMessage};
+use crate::linux::stats::DeviceReport;
+use crate::linux::{DeviceInterface, DeviceResults};
+use neli::{consts::socket::NlFamily, nl::NlPayload};
+
+/// The async version of [`WgSocket`](crate::WgSocket). Requests are sent on a
+/// non-blocking netlink socket driven by the tokio reactor, so awaiting a
+/// reply doesn't block a runtime thread.
+///
+/// The socket can be used again after a future returned by one of its
+/// methods is dropped, for example by a timeout, since replies are matched to
+/// the request they answer. The request may still be carried out by the
+/// kernel though, and a dropped `set_device` may have applied only some of
+/// the messages it was split into.
+///
+/// There's no read timeout to set. Wrap calls in `tokio::time::timeout`
+/// instead, which is safe for the reason above. There's no counterpart of
+/// [`WgSocket::get_device_peers`](crate::WgSocket::get_device_peers) either,
+/// so the peers of a device are always collected in memory.
+pub struct AsyncWgSocket {
+    sock: AsyncNlSocket,
+    family_id: NlWgMsgType,
+    max_message_size: usize,
+}
+
+impl AsyncWgSocket {
+    pub async fn connect() -> Result<Self, ConnectError> {
+        let mut sock = AsyncNlSocket::connect(NlFamily::Generic)?;
+        let family_id = resolve_genl_family(&mut sock, WG_GENL_NAME)
+            .await
+            .map_err(ConnectError::ResolveFamilyError)?;
+        let max_message_size = clamp_message_size(sock.max_message_size());
+
+        Ok(Self {
+            sock,
+            family_id,
+            max_message_size,
+        })
+    }
+
+    /// See [`WgSocket::max_message_size`](crate::WgSocket::max_message_size).
+    pub fn max_message_size(&self) -> usize {
+        self.max_message_size
+    }
+
+    /// See
+    /// [`WgSocket::set_max_message_size`](crate::WgSocket::set_max_message_size).
+    pub fn set_max_message_size(&mut self, max_message_size: usize) {
+        self.max_message_size = clamp_message_size(max_message_size);
+    }
+
+    pub async fn get_device(
+        &mut self,
+        interface: DeviceInterface<'_>,
+    ) -> Result<get::Device, GetDeviceError> {
+        let seq = self
+            .sock
+            .send(get_device_msg(interface, self.family_id)?)
+            .await?;
+
+        let mut device = None;
+        for payload in self.sock.recv_dump::<GetDeviceError>(seq).await? {
+            device = Some(extend_device_with_payload(device, payload.as_ref())?);
+        }
+
+        device.ok_or(GetDeviceError::AccessError)
+    }
+
+    /// See [`WgSocket::get_all_devices`](crate::WgSocket::get_all_devices).
+    pub async fn get_all_devices(
+        &mut self,
+        route: &mut AsyncRouteSocket,
+    ) -> Result<DeviceResults, GetAllDevicesError> {
+        let names = route.list_device_names().await?;
+
+        let mut devices = Vec::with_capacity(names.len());
+        for name in names {
+            match self.get_device(DeviceInterface::from_name(&name)).await {
+                // The device was deleted after it was listed.
+                Err(GetDeviceError::KernelError(err)) if err.raw_os_error() == libc::ENODEV => {}
+                Err(err) if fails_all_devices(&err) => return Err(err.into()),
+                result => devices.push((name, result)),
+            }
+        }
+
+        Ok(devices)
+    }
+
+    /// See [`WgSocket::get_device_report`](crate::WgSocket::get_device_report).
+    pub async fn get_device_report(
+        &mut self,
+        route: &mut AsyncRouteSocket,
+        interface: DeviceInterface<'_>,
+    ) -> Result<DeviceReport, GetDeviceReportError> {
+        let device = self.get_device(interface).await?;
+        let stats = route
+            .get_link_stats(DeviceInterface::from_index(device.ifindex))
+            .await?;
+        Ok(DeviceReport { device, stats })
+    }
+
+    /// See [`WgSocket::set_device`](crate::WgSocket::set_device). Devices too
+    /// large for a single netlink message are split up the same way.
+    pub async fn set_device(&mut self, device: set::Device<'_>) -> Result<(), SetDeviceError> {
+        let fragments = create_set_device_messages(device, self.family_id, self.max_message_size)?;
+        let count = fragments.len();
+        for (index, fragment) in fragments.into_iter().enumerate() {
+            let SetDeviceMessage {
+                message,
+                public_keys,
+            } = fragment;
+
+            let seq = self.sock.send(message).await?;
+            self.sock
+                .recv_ack::<SetDeviceError>(seq)
+                .await
+                .map_err(|err| reject_fragment(err, index, count, public_keys))?;
+        }
+
+        Ok(())
+    }
+}
+
+/// The async equivalent of `NlSocketHandle::resolve_genl_family`.
+async fn resolve_genl_family(
+    sock: &mut AsyncNlSocket,
+    family_name: &str,
+) -> Result<NlWgMsgType, ResolveFamilyError> {
+    let seq = sock.send(get_family_msg(family_name)?).await?;
+
+    for message in sock.recv_messages(seq).await? {
+        if let NlPayload::Payload(payload) = message.nl_payload {
+            if let Some(family_id) = parse_family_id(&payload)? {
+                return Ok(family_id);
+            }
+        }
+    }
+
+    Err(family_not_found(family_name))
+}
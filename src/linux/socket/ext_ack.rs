@@ -0,0 +1,172 @@
+use crate::err::KernelError;
+use crate::linux::attr::NLA_TYPE_MASK;
+use crate::linux::consts::{
+    NETLINK_CAP_ACK, NETLINK_EXT_ACK, NLMSGERR_ATTR_MSG, NLMSGERR_ATTR_OFFS,
+};
+use neli::err::{NlError, Nlmsgerr};
+use neli::types::Buffer;
+use std::convert::TryInto;
+use std::os::unix::io::AsRawFd;
+
+const NL_HEADER_SIZE: usize = 16;
+
+fn enable_netlink_option(sock: &impl AsRawFd, option: libc::c_int) -> bool {
+    let enable: libc::c_int = 1;
+    let result = unsafe {
+        libc::setsockopt(
+            sock.as_raw_fd(),
+            libc::SOL_NETLINK,
+            option,
+            &enable as *const libc::c_int as *const libc::c_void,
+            std::mem::size_of::<libc::c_int>() as libc::socklen_t,
+        )
+    };
+    result == 0
+}
+
+/// Asks the kernel to attach extended ACK attributes to error messages and to
+/// stop echoing the full request back in them. Both options are best effort
+/// since older kernels don't know about them.
+///
+/// Returns whether error messages will be capped, which changes where the
+/// extended ACK attributes start.
+pub(crate) fn enable_ext_ack(sock: &impl AsRawFd) -> bool {
+    enable_netlink_option(sock, NETLINK_EXT_ACK);
+    enable_netlink_option(sock, NETLINK_CAP_ACK)
+}
+
+/// Decodes the errno and extended ACK attributes of a `NLMSG_ERROR` message.
+pub(crate) fn parse_nlmsgerr<T>(err: &Nlmsgerr<T, Buffer>, capped: bool) -> KernelError {
+    parse_nlmsgerr_parts(
+        err.error,
+        err.nlmsg.nl_len,
+        err.nlmsg.nl_payload.as_ref(),
+        capped,
+    )
+}
+
+/// Decodes a `NLMSG_ERROR` message from its errno, the length field of the
+/// echoed request header and everything that follows that header.
+pub(crate) fn parse_nlmsgerr_parts(
+    error: i32,
+    request_len: u32,
+    payload: &[u8],
+    capped: bool,
+) -> KernelError {
+    // Uncapped errors contain the payload of the original request before the
+    // extended ACK attributes.
+    let tlvs = if capped {
+        payload
+    } else {
+        let request_payload_len = (request_len as usize).saturating_sub(NL_HEADER_SIZE);
+        payload.get(request_payload_len..).unwrap_or_default()
+    };
+
+    let mut message = None;
+    let mut offset = None;
+    for (nla_type, nla_payload) in iter_attrs(tlvs) {
+        match nla_type & NLA_TYPE_MASK {
+            NLMSGERR_ATTR_MSG => {
+                let msg = nla_payload.split(|&byte| byte == 0).next();
+                message = msg.map(|msg| String::from_utf8_lossy(msg).into_owned());
+            }
+            NLMSGERR_ATTR_OFFS => {
+                offset = nla_payload.try_into().ok().map(u32::from_ne_bytes);
+            }
+            _ => {}
+        }
+    }
+
+    KernelError::new(error, message, offset)
+}
+
+/// Splits a buffer of netlink attributes into (type, payload) pairs. Iteration
+/// stops at the first malformed attribute.
+pub(crate) fn iter_attrs(mut buf: &[u8]) -> impl Iterator<Item = (u16, &[u8])> {
+    std::iter::from_fn(move || {
+        let nla_len = u16::from_ne_bytes(buf.get(0..2)?.try_into().ok()?) as usize;
+        let nla_type = u16::from_ne_bytes(buf.get(2..4)?.try_into().ok()?);
+        let payload = buf.get(4..nla_len)?;
+        let aligned_len = (nla_len + 3) & !3;
+        buf = buf.get(aligned_len..).unwrap_or_default();
+        Some((nla_type, payload))
+    })
+}
+
+/// Routes `NLMSG_ERROR` replies into [`KernelError`] and every other neli error
+/// into the caller's `NlError` variant.
+pub(crate) fn decode_nl_error<E>(err: NlError, capped: bool) -> E
+where
+    E: From<NlError> + From<KernelError>,
+{
+    match err {
+        NlError::Nlmsgerr(err) => parse_nlmsgerr(&err, capped).into(),
+        err => err.into(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use neli::err::NlmsghdrErr;
+
+    fn nlmsgerr(error: i32, nl_len: u32, payload: Vec<u8>) -> Nlmsgerr<u16, Buffer> {
+        use neli::consts::nl::NlmFFlags;
+        Nlmsgerr {
+            error,
+            nlmsg: NlmsghdrErr {
+                nl_len,
+                nl_type: 0x18,
+                nl_flags: NlmFFlags::empty(),
+                nl_seq: 0,
+                nl_pid: 0,
+                nl_payload: Buffer::from(payload),
+            },
+        }
+    }
+
+    fn ext_ack_attrs() -> Vec<u8> {
+        let mut attrs = vec![];
+        // NLMSGERR_ATTR_MSG: "Unknown device type\0", padded to 4 bytes.
+        let msg = b"Unknown device type\0";
+        attrs.extend(&(4 + msg.len() as u16).to_ne_bytes());
+        attrs.extend(&NLMSGERR_ATTR_MSG.to_ne_bytes());
+        attrs.extend(msg.iter());
+        attrs.resize((attrs.len() + 3) & !3, 0);
+        // NLMSGERR_ATTR_OFFS: 36
+        attrs.extend(&8u16.to_ne_bytes());
+        attrs.extend(&NLMSGERR_ATTR_OFFS.to_ne_bytes());
+        attrs.extend(&36u32.to_ne_bytes());
+        attrs
+    }
+
+    #[test]
+    fn parse_capped_error_with_ext_ack() {
+        let err = parse_nlmsgerr(&nlmsgerr(-libc::EOPNOTSUPP, 52, ext_ack_attrs()), true);
+
+        assert_eq!(err.raw_os_error(), libc::EOPNOTSUPP);
+        assert_eq!(err.message(), Some("Unknown device type"));
+        assert_eq!(err.offset(), Some(36));
+    }
+
+    #[test]
+    fn parse_uncapped_error_skips_request() {
+        let mut payload = vec![0xffu8; 36];
+        payload.extend(ext_ack_attrs());
+        let err = parse_nlmsgerr(&nlmsgerr(-libc::EINVAL, 52, payload), false);
+
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+        assert_eq!(err.message(), Some("Unknown device type"));
+        assert_eq!(err.offset(), Some(36));
+    }
+
+    #[test]
+    fn parse_error_without_ext_ack() {
+        let err = parse_nlmsgerr(&nlmsgerr(-libc::ENODEV, 20, vec![]), true);
+
+        assert_eq!(err.raw_os_error(), libc::ENODEV);
+        assert_eq!(err.message(), None);
+        assert_eq!(err.offset(), None);
+        assert_eq!(err.to_string(), "No such device (os error 19)");
+    }
+}
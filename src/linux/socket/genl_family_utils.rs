@@ -0,0 +1,58 @@
+use super::NlWgMsgType;
+use neli::{
+    consts::{
+        genl::{CtrlAttr, CtrlCmd},
+        nl::{GenlId, NlmF, NlmFFlags},
+    },
+    err::NlError,
+    genl::{Genlmsghdr, Nlattr},
+    nl::{NlPayload, Nlmsghdr},
+    types::{Buffer, GenlBuffer},
+    FromBytesWithInput,
+};
+use std::io::Cursor;
+
+pub type ResolveFamilyError = NlError<GenlId, Genlmsghdr<CtrlCmd, CtrlAttr>>;
+
+/// Creates a CTRL_CMD_GETFAMILY request. It doesn't ask for an ACK, so the
+/// kernel answers with exactly one message: the family or an error.
+pub fn get_family_msg(
+    family_name: &str,
+) -> Result<Nlmsghdr<GenlId, Genlmsghdr<CtrlCmd, CtrlAttr>>, ResolveFamilyError> {
+    let mut attrs = GenlBuffer::new();
+    attrs.push(Nlattr::new(
+        false,
+        false,
+        CtrlAttr::FamilyName,
+        family_name,
+    )?);
+    let genlhdr = Genlmsghdr::new(CtrlCmd::Getfamily, 2, attrs);
+    Ok(Nlmsghdr::new(
+        None,
+        GenlId::Ctrl,
+        NlmFFlags::new(&[NlmF::Request]),
+        None,
+        None,
+        NlPayload::Payload(genlhdr),
+    ))
+}
+
+/// Reads the family id out of the reply to [`get_family_msg`].
+pub fn parse_family_id(payload: &Buffer) -> Result<Option<NlWgMsgType>, ResolveFamilyError> {
+    let payload = payload.as_ref();
+    let genlhdr = Genlmsghdr::<CtrlCmd, CtrlAttr>::from_bytes_with_input(
+        &mut Cursor::new(payload),
+        payload.len(),
+    )?;
+    Ok(genlhdr
+        .get_attr_handle()
+        .get_attr_payload_as::<u16>(CtrlAttr::FamilyId)
+        .ok())
+}
+
+pub fn family_not_found(family_name: &str) -> ResolveFamilyError {
+    NlError::new(format!(
+        "Generic netlink family {} was not found",
+        family_name
+    ))
+}
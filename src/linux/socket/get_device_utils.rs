@@ -0,0 +1,72 @@
+use super::parse::{extend_device, parse_device};
+use super::NlWgMsgType;
+use crate::get;
+use crate::linux::attr::WgDeviceAttribute;
+use crate::linux::cmd::WgCmd;
+use crate::linux::consts::{NLA_NETWORK_ORDER, WG_GENL_VERSION};
+use crate::linux::err::GetDeviceError;
+use crate::linux::{is_valid_ifname, DeviceInterface};
+use neli::{
+    consts::nl::{NlmF, NlmFFlags},
+    genl::{Genlmsghdr, Nlattr},
+    nl::{NlPayload, Nlmsghdr},
+    types::GenlBuffer,
+};
+
+pub fn get_device_msg(
+    interface: DeviceInterface,
+    family_id: NlWgMsgType,
+) -> Result<Nlmsghdr<NlWgMsgType, Genlmsghdr<WgCmd, WgDeviceAttribute>>, GetDeviceError> {
+    let attr = match interface {
+        DeviceInterface::Name(name) => {
+            if !is_valid_ifname(&name) {
+                return Err(GetDeviceError::InvalidInterfaceName);
+            }
+            Nlattr::new(
+                false,
+                NLA_NETWORK_ORDER,
+                WgDeviceAttribute::Ifname,
+                name.as_ref(),
+            )?
+        }
+        DeviceInterface::Index(index) => {
+            Nlattr::new(false, NLA_NETWORK_ORDER, WgDeviceAttribute::Ifindex, index)?
+        }
+    };
+    let genlhdr = {
+        let cmd = WgCmd::GetDevice;
+        let version = WG_GENL_VERSION;
+        let mut attrs = GenlBuffer::new();
+
+        attrs.push(attr);
+        Genlmsghdr::new(cmd, version, attrs)
+    };
+    let nlhdr = {
+        let size = None;
+        let nl_type = family_id;
+        let flags = NlmFFlags::new(&[NlmF::Request, NlmF::Ack, NlmF::Dump]);
+        let seq = None;
+        let pid = None;
+        let payload = NlPayload::Payload(genlhdr);
+        Nlmsghdr::new(size, nl_type, flags, seq, pid, payload)
+    };
+
+    Ok(nlhdr)
+}
+
+/// Folds one message of a WG_CMD_GET_DEVICE dump into the device parsed so
+/// far. Devices with many peers are split across several messages by the
+/// kernel. `payload` is parsed in place, so it can point straight into the
+/// receive buffer.
+pub fn extend_device_with_payload(
+    device: Option<get::Device>,
+    payload: &[u8],
+) -> Result<get::Device, GetDeviceError> {
+    Ok(match device {
+        Some(mut device) => {
+            extend_device(&mut device, payload)?;
+            device
+        }
+        None => parse_device(payload)?,
+    })
+}
@@ -0,0 +1,112 @@
+use crate::err::ParseAttributeError;
+use crate::linux::consts::WG_GENL_NAME;
+use crate::linux::socket::parse::parse_link_stats64;
+use crate::linux::stats::LinkStats;
+use crate::linux::DeviceInterface;
+use neli::attr::Attribute;
+use neli::err::{DeError, SerError};
+use neli::rtnl::Rtattr;
+use neli::{
+    consts::{
+        nl::{NlmF, NlmFFlags},
+        rtnl::{Arphrd, Iff, IffFlags, Ifla, IflaInfo, RtAddrFamily, Rtm},
+    },
+    nl::{NlPayload, Nlmsghdr},
+    rtnl::Ifinfomsg,
+    types::RtBuffer,
+    FromBytesWithInput,
+};
+use std::io::Cursor;
+
+/// Creates a RTM_GETLINK request for a single interface. Unlike
+/// [`get_list_device_names_msg`](super::list_device_names_utils::get_list_device_names_msg)
+/// this isn't a dump request. The kernel answers with exactly one RTM_NEWLINK
+/// message or an error.
+pub fn get_link_msg(interface: &DeviceInterface) -> Result<Nlmsghdr<Rtm, Ifinfomsg>, SerError> {
+    let infomsg = {
+        let ifi_family = RtAddrFamily::Unspecified;
+        // Arphrd::Netrom corresponds to 0. Not sure why 0 is necessary here but this is what the
+        // embedded C library does.
+        let ifi_type = Arphrd::Netrom;
+        let ifi_flags = IffFlags::empty();
+        let ifi_change = IffFlags::new(&[Iff::Up]);
+        let mut rtattrs = RtBuffer::new();
+        let ifi_index = match interface {
+            DeviceInterface::Index(index) => *index as libc::c_int,
+            DeviceInterface::Name(name) => {
+                rtattrs.push(Rtattr::new(None, Ifla::Ifname, name.as_bytes())?);
+                0
+            }
+        };
+
+        Ifinfomsg::new(
+            ifi_family, ifi_type, ifi_index, ifi_flags, ifi_change, rtattrs,
+        )
+    };
+
+    let len = None;
+    let nl_type = Rtm::Getlink;
+    let flags = NlmFFlags::new(&[NlmF::Request]);
+    let seq = None;
+    let pid = None;
+    let payload = infomsg;
+    Ok(Nlmsghdr::new(
+        len,
+        nl_type,
+        flags,
+        seq,
+        pid,
+        NlPayload::Payload(payload),
+    ))
+}
+
+/// The subset of a RTM_NEWLINK message this library cares about.
+pub struct LinkInfo {
+    pub ifindex: u32,
+    pub ifname: Option<String>,
+    pub kind: Option<String>,
+    pub stats: Option<LinkStats>,
+}
+
+impl LinkInfo {
+    pub fn is_wireguard(&self) -> bool {
+        self.kind.as_deref() == Some(WG_GENL_NAME)
+    }
+
+    pub fn parse<E>(payload: &[u8]) -> Result<Self, E>
+    where
+        E: From<DeError> + From<ParseAttributeError>,
+    {
+        let infomsg = Ifinfomsg::from_bytes_with_input(&mut Cursor::new(payload), payload.len())?;
+
+        let mut link_info = LinkInfo {
+            ifindex: infomsg.ifi_index as u32,
+            ifname: None,
+            kind: None,
+            stats: None,
+        };
+
+        for attr in infomsg.rtattrs.iter() {
+            match attr.rta_type {
+                Ifla::Linkinfo => {
+                    for info_kind in attr
+                        .get_attr_handle()?
+                        .iter()
+                        .filter(|attr: &&Rtattr<IflaInfo, _>| attr.rta_type == IflaInfo::Kind)
+                    {
+                        link_info.kind = Some(info_kind.get_payload_as_with_len::<String>()?);
+                    }
+                }
+                Ifla::Ifname => {
+                    link_info.ifname = Some(attr.get_payload_as_with_len::<String>()?);
+                }
+                Ifla::Stats64 => {
+                    link_info.stats = Some(parse_link_stats64(attr.rta_payload.as_ref())?);
+                }
+                _ => {}
+            }
+        }
+
+        Ok(link_info)
+    }
+}
@@ -1,4 +1,6 @@
 use crate::linux::consts::WG_GENL_NAME;
+use crate::linux::link::{self, NetNs};
+use crate::linux::DeviceInterface;
 use neli::{
     consts::{
         nl::{NlmF, NlmFFlags},
@@ -7,36 +9,48 @@ use neli::{
     err::NlError,
     nl::{NlPayload, Nlmsghdr},
     rtnl::{Ifinfomsg, Rtattr},
-    types::RtBuffer,
+    types::{Buffer, RtBuffer},
 };
 
-pub enum WireGuardDeviceLinkOperation {
-    Add,
-    Delete,
+fn wireguard_link_info() -> Result<Rtattr<Ifla, Buffer>, NlError> {
+    let mut genl_name = RtBuffer::new();
+    genl_name.push(Rtattr::new(None, IflaInfo::Kind, WG_GENL_NAME.as_bytes())?);
+    Ok(Rtattr::new(None, Ifla::Linkinfo, genl_name)?)
 }
 
-pub fn link_message(
-    ifname: &str,
-    link_operation: WireGuardDeviceLinkOperation,
-) -> Result<Nlmsghdr<Rtm, Ifinfomsg>, NlError> {
+/// Creates the RTM_NEWLINK message for a new WireGuard device interface. The
+/// message always carries NLM_F_EXCL, so an existing interface of the same
+/// name is never modified.
+pub fn new_link_message(device: &link::Device) -> Result<Nlmsghdr<Rtm, Ifinfomsg>, NlError> {
     let infomsg = {
         let ifi_family = RtAddrFamily::Unspecified;
         // Arphrd::Netrom corresponds to 0. Not sure why 0 is necessary here but this is what the
         // embedded C library does.
         let ifi_type = Arphrd::Netrom;
-        let ifi_index = 0;
-        let ifi_flags = IffFlags::empty();
+        // A non-zero ifindex in a RTM_NEWLINK request asks the kernel to use that index.
+        let ifi_index = device.ifindex.unwrap_or(0) as libc::c_int;
+        let ifi_flags = if device.up {
+            IffFlags::new(&[Iff::Up])
+        } else {
+            IffFlags::empty()
+        };
         let ifi_change = IffFlags::new(&[Iff::Up]);
         let rtattrs = {
             let mut buffer = RtBuffer::new();
-            buffer.push(Rtattr::new(None, Ifla::Ifname, ifname.as_bytes())?);
+            buffer.push(Rtattr::new(None, Ifla::Ifname, device.ifname.as_bytes())?);
 
-            let mut genl_name = RtBuffer::new();
-            genl_name.push(Rtattr::new(None, IflaInfo::Kind, WG_GENL_NAME.as_bytes())?);
+            buffer.push(wireguard_link_info()?);
 
-            let link = Rtattr::new(None, Ifla::Linkinfo, genl_name)?;
+            if let Some(mtu) = device.mtu {
+                buffer.push(Rtattr::new(None, Ifla::Mtu, mtu)?);
+            }
+
+            match device.netns {
+                Some(NetNs::Fd(fd)) => buffer.push(Rtattr::new(None, Ifla::NetNsFd, fd as u32)?),
+                Some(NetNs::Pid(pid)) => buffer.push(Rtattr::new(None, Ifla::NetNsPid, pid)?),
+                None => {}
+            }
 
-            buffer.push(link);
             buffer
         };
         Ifinfomsg::new(
@@ -44,18 +58,42 @@ pub fn link_message(
         )
     };
 
-    let nlmsg = {
-        let len = None;
-        let nl_type = match link_operation {
-            WireGuardDeviceLinkOperation::Add => Rtm::Newlink,
-            WireGuardDeviceLinkOperation::Delete => Rtm::Dellink,
-        };
-        let flags = match link_operation {
-            WireGuardDeviceLinkOperation::Add => {
-                NlmFFlags::new(&[NlmF::Request, NlmF::Ack, NlmF::Create, NlmF::Excl])
+    let len = None;
+    let nl_type = Rtm::Newlink;
+    let flags = NlmFFlags::new(&[NlmF::Request, NlmF::Ack, NlmF::Create, NlmF::Excl]);
+    let seq = None;
+    let pid = None;
+    let payload = NlPayload::Payload(infomsg);
+    Ok(Nlmsghdr::new(len, nl_type, flags, seq, pid, payload))
+}
+
+/// Creates a RTM_DELLINK message. The kernel doesn't check the link kind of
+/// the target, so any interface matching `interface` is deleted.
+pub fn del_link_message(interface: &DeviceInterface) -> Result<Nlmsghdr<Rtm, Ifinfomsg>, NlError> {
+    let infomsg = {
+        let ifi_family = RtAddrFamily::Unspecified;
+        // Arphrd::Netrom corresponds to 0. Not sure why 0 is necessary here but this is what the
+        // embedded C library does.
+        let ifi_type = Arphrd::Netrom;
+        let ifi_flags = IffFlags::empty();
+        let ifi_change = IffFlags::new(&[Iff::Up]);
+        let mut rtattrs = RtBuffer::new();
+        let ifi_index = match interface {
+            DeviceInterface::Index(index) => *index as libc::c_int,
+            DeviceInterface::Name(name) => {
+                rtattrs.push(Rtattr::new(None, Ifla::Ifname, name.as_bytes())?);
+                0
             }
-            WireGuardDeviceLinkOperation::Delete => NlmFFlags::new(&[NlmF::Request, NlmF::Ack]),
         };
+        Ifinfomsg::new(
+            ifi_family, ifi_type, ifi_index, ifi_flags, ifi_change, rtattrs,
+        )
+    };
+
+    let nlmsg = {
+        let len = None;
+        let nl_type = Rtm::Dellink;
+        let flags = NlmFFlags::new(&[NlmF::Request, NlmF::Ack]);
         let seq = None;
         let pid = None;
         let payload = NlPayload::Payload(infomsg);
@@ -64,3 +102,40 @@ pub fn link_message(
 
     Ok(nlmsg)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::linux::link::NetNs;
+
+    #[test]
+    fn new_link_message_includes_requested_options() -> Result<(), NlError> {
+        let device = link::Device::from_ifname("wgtest0")
+            .mtu(1280)
+            .ifindex(42)
+            .netns(NetNs::Pid(1))
+            .up(true);
+        let nlmsg = new_link_message(&device)?;
+        let infomsg = nlmsg.get_payload()?;
+
+        assert_eq!(infomsg.ifi_index, 42);
+        assert!(infomsg.ifi_flags.contains(&Iff::Up));
+
+        let attrs = infomsg.rtattrs.get_attr_handle();
+        assert_eq!(attrs.get_attr_payload_as::<u32>(Ifla::Mtu)?, 1280);
+        assert_eq!(attrs.get_attr_payload_as::<u32>(Ifla::NetNsPid)?, 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn del_link_message_by_index_has_no_name() -> Result<(), NlError> {
+        let nlmsg = del_link_message(&DeviceInterface::from_index(7))?;
+        let infomsg = nlmsg.get_payload()?;
+
+        assert_eq!(infomsg.ifi_index, 7);
+        assert!(infomsg.rtattrs.is_empty());
+
+        Ok(())
+    }
+}
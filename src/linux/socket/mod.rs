@@ -1,14 +1,41 @@
+mod transport;
+pub use transport::{NetlinkTransport, Transport};
+
 mod route_socket;
-pub use route_socket::RouteSocket;
+pub use route_socket::{DeviceNamesRequest, RouteSocket};
 
 mod wg_socket;
-pub use wg_socket::WgSocket;
+pub use wg_socket::{DeviceRequest, DeviceResults, WgSocket};
+
+mod peer_iter;
+pub use peer_iter::PeerIter;
+
+#[cfg(feature = "tokio")]
+mod async_nl_socket;
+
+#[cfg(feature = "tokio")]
+mod async_route_socket;
+#[cfg(feature = "tokio")]
+pub use async_route_socket::AsyncRouteSocket;
+
+#[cfg(feature = "tokio")]
+mod async_wg_socket;
+#[cfg(feature = "tokio")]
+pub use async_wg_socket::AsyncWgSocket;
 
 pub(crate) mod parse;
 
+pub(crate) mod get_device_utils;
+
+pub(crate) mod genl_family_utils;
+
+pub(crate) mod ext_ack;
+
 pub(crate) type NlWgMsgType = u16;
 
 pub(crate) mod link_message;
-pub(crate) use link_message::{link_message, WireGuardDeviceLinkOperation};
+pub(crate) use link_message::{del_link_message, new_link_message};
+
+pub(crate) mod link_info_utils;
 
 pub(crate) mod list_device_names_utils;
@@ -1,89 +1,145 @@
 use crate::err::{ParseAttributeError, ParseDeviceError, ParseIpAddrError, ParseSockAddrError};
 use crate::get::{AllowedIp, AllowedIpBuilder, Device, DeviceBuilder, Peer, PeerBuilder};
-use crate::linux::attr::{
-    NlaNested, WgAllowedIpAttribute, WgDeviceAttribute, WgPeerAttribute, NLA_TYPE_MASK,
-};
+use crate::linux::attr::{WgAllowedIpAttribute, WgDeviceAttribute, WgPeerAttribute, NLA_TYPE_MASK};
+use crate::linux::stats::LinkStats;
 use libc::{in6_addr, in_addr, AF_INET, AF_INET6};
-use neli::{
-    attr,
-    genl::Nlattr,
-    types::{Buffer, GenlBuffer},
-};
+use neli::err::DeError;
 use std::convert::TryFrom;
 use std::mem::size_of;
 use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
 use std::time::Duration;
 
-type AttrHandle<'a, T> = attr::AttrHandle<'a, GenlBuffer<T, Buffer>, Nlattr<T, Buffer>>;
+const GENL_HEADER_SIZE: usize = 4;
+const NLA_HEADER_SIZE: usize = 4;
 
-impl TryFrom<AttrHandle<'_, WgDeviceAttribute>> for Device {
-    type Error = ParseDeviceError;
+// The parsers below read attributes straight out of the buffer a message was
+// received into. Payloads are only copied once they're converted into the
+// fields of a device, and peers are built without cloning their allowed IPs.
 
-    fn try_from(handle: AttrHandle<WgDeviceAttribute>) -> Result<Self, Self::Error> {
-        let mut device_builder = DeviceBuilder::default();
+/// Iterates over the (type, payload) pairs of a buffer of netlink attributes
+/// without copying the payloads.
+fn nla_iter(mut buf: &[u8]) -> impl Iterator<Item = Result<(u16, &[u8]), ParseDeviceError>> {
+    std::iter::from_fn(move || {
+        // Anything shorter than an attribute header is padding.
+        if buf.len() < NLA_HEADER_SIZE {
+            return None;
+        }
+        let nla_len = usize::from(u16::from_ne_bytes([buf[0], buf[1]]));
+        let nla_type = u16::from_ne_bytes([buf[2], buf[3]]);
+        let payload = match buf.get(NLA_HEADER_SIZE..nla_len) {
+            Some(payload) => payload,
+            None => {
+                buf = &[];
+                return Some(Err(DeError::UnexpectedEOB.into()));
+            }
+        };
+        buf = buf.get((nla_len + 3) & !3..).unwrap_or_default();
+        Some(Ok((nla_type & NLA_TYPE_MASK, payload)))
+    })
+}
 
-        for attr in handle.iter() {
-            match attr.nla_type.nla_type & NLA_TYPE_MASK {
-                WgDeviceAttribute::Unspec => {
-                    // The embeddable-wg-library example ignores unspec, so we'll do the same.
-                }
-                WgDeviceAttribute::Ifindex => {
-                    device_builder.ifindex(parse_nla_u32(attr.nla_payload.as_ref())?);
-                }
-                WgDeviceAttribute::Ifname => {
-                    device_builder.ifname(parse_nla_nul_string(attr.nla_payload.as_ref())?);
-                }
-                WgDeviceAttribute::PrivateKey => {
-                    device_builder.private_key(Some(parse_device_key(attr.nla_payload.as_ref())?));
-                }
-                WgDeviceAttribute::PublicKey => {
-                    device_builder.public_key(Some(parse_device_key(attr.nla_payload.as_ref())?));
-                }
-                WgDeviceAttribute::ListenPort => {
-                    device_builder.listen_port(parse_nla_u16(attr.nla_payload.as_ref())?);
-                }
-                WgDeviceAttribute::Fwmark => {
-                    device_builder.fwmark(parse_nla_u32(attr.nla_payload.as_ref())?);
-                }
-                WgDeviceAttribute::Peers => {
-                    device_builder.peers(parse_peers(attr.get_attr_handle::<NlaNested>()?)?);
-                }
-                WgDeviceAttribute::Flags => {
-                    // This attribute is for set_device. Ignore it for get_device.
-                }
-                WgDeviceAttribute::UnrecognizedConst(i) => {
-                    return Err(ParseDeviceError::UnknownDeviceAttributeError { id: i })
+/// Strips the generic netlink header off the payload of a netlink message.
+fn genl_attrs(payload: &[u8]) -> Result<&[u8], ParseDeviceError> {
+    Ok(payload
+        .get(GENL_HEADER_SIZE..)
+        .ok_or(DeError::UnexpectedEOB)?)
+}
+
+/// Whether a message is the first of a WG_CMD_GET_DEVICE dump, which is the
+/// only one that carries the attributes of the device itself.
+pub fn is_first_device_message(payload: &[u8]) -> Result<bool, ParseDeviceError> {
+    for attr in nla_iter(genl_attrs(payload)?) {
+        if WgDeviceAttribute::from(attr?.0) == WgDeviceAttribute::Ifindex {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+/// Parses the first message of a WG_CMD_GET_DEVICE dump. `payload` starts
+/// with the generic netlink header.
+pub fn parse_device(payload: &[u8]) -> Result<Device, ParseDeviceError> {
+    let mut device_builder = DeviceBuilder::default();
+    let mut peers = vec![];
+
+    for attr in nla_iter(genl_attrs(payload)?) {
+        let (nla_type, payload) = attr?;
+        match WgDeviceAttribute::from(nla_type) {
+            WgDeviceAttribute::Unspec => {
+                // The embeddable-wg-library example ignores unspec, so we'll do the same.
+            }
+            WgDeviceAttribute::Ifindex => {
+                device_builder.ifindex(parse_nla_u32(payload)?);
+            }
+            WgDeviceAttribute::Ifname => {
+                device_builder.ifname(parse_nla_nul_string(payload)?);
+            }
+            WgDeviceAttribute::PrivateKey => {
+                device_builder.private_key(Some(parse_device_key(payload)?));
+            }
+            WgDeviceAttribute::PublicKey => {
+                device_builder.public_key(Some(parse_device_key(payload)?));
+            }
+            WgDeviceAttribute::ListenPort => {
+                device_builder.listen_port(parse_nla_u16(payload)?);
+            }
+            WgDeviceAttribute::Fwmark => {
+                device_builder.fwmark(parse_nla_u32(payload)?);
+            }
+            WgDeviceAttribute::Peers => {
+                for peer in nla_iter(payload) {
+                    peers.push(PeerAttrs::parse(peer?.1)?.build()?);
                 }
             }
+            WgDeviceAttribute::Flags => {
+                // This attribute is for set_device. Ignore it for get_device.
+            }
+            WgDeviceAttribute::UnrecognizedConst(i) => {
+                return Err(ParseDeviceError::UnknownDeviceAttributeError { id: i })
+            }
         }
-
-        Ok(device_builder.build()?)
     }
+
+    // The peers are moved in after building since the builder would clone
+    // them otherwise.
+    let mut device = device_builder.build()?;
+    device.peers = peers;
+    Ok(device)
+}
+
+/// Appends the peers of a continuation message of a WG_CMD_GET_DEVICE dump to
+/// a device.
+pub fn extend_device(device: &mut Device, payload: &[u8]) -> Result<(), ParseDeviceError> {
+    for_each_peer(payload, |peer| match device.peers.last_mut() {
+        Some(last_peer) if peer.continues(last_peer) => peer.append_allowed_ips_to(last_peer),
+        _ => {
+            device.peers.push(peer.build()?);
+            Ok(())
+        }
+    })
 }
 
-pub fn extend_device(
+/// Parses the peers of a device message without building them. The first peer
+/// of a message may only be a continuation of the last peer of the previous
+/// message, in which case it's missing everything but its public key and
+/// allowed IPs.
+pub fn parse_peer_builders(payload: &[u8]) -> Result<Vec<PeerBuilder>, ParseDeviceError> {
+    let mut peers = vec![];
+    for_each_peer(payload, |peer| {
+        peers.push(peer.into_builder()?);
+        Ok(())
+    })?;
+    Ok(peers)
+}
+
+/// Appends the peers of a continuation message to a device. A first peer with
+/// the same public key as the device's last peer only carries more of its
+/// allowed IPs.
+pub fn extend_device_with_peers(
     mut device: Device,
-    handle: AttrHandle<'_, WgDeviceAttribute>,
+    peers: Vec<PeerBuilder>,
 ) -> Result<Device, ParseDeviceError> {
-    let next_peers = {
-        let peers_attr = handle
-            .iter()
-            .find(|attr| attr.nla_type.nla_type & NLA_TYPE_MASK == WgDeviceAttribute::Peers)
-            .expect("Unable to find additional peers to coalesce.");
-        let handle = peers_attr.get_attr_handle()?;
-
-        handle
-            .iter()
-            .map(Nlattr::<NlaNested, Buffer>::get_attr_handle::<WgPeerAttribute>)
-            .map(|handle| {
-                handle
-                    .map_err(|err| err.into())
-                    .and_then(parse_peer_builder)
-            })
-            .collect::<Result<Vec<PeerBuilder>, _>>()?
-    };
-
-    for next_peer in next_peers {
+    for next_peer in peers {
         let matching_last_peer = device
             .peers
             .last_mut()
@@ -100,123 +156,158 @@ pub fn extend_device(
     Ok(device)
 }
 
-pub fn parse_peers(handle: AttrHandle<'_, NlaNested>) -> Result<Vec<Peer>, ParseDeviceError> {
-    let mut peers = vec![];
+/// Calls `f` with every peer in the WGDEVICE_A_PEERS attribute of a dump
+/// message. `payload` starts with the generic netlink header.
+pub(crate) fn for_each_peer<F>(payload: &[u8], mut f: F) -> Result<(), ParseDeviceError>
+where
+    F: FnMut(PeerAttrs<'_>) -> Result<(), ParseDeviceError>,
+{
+    let mut peers_attr = None;
+    for attr in nla_iter(genl_attrs(payload)?) {
+        let (nla_type, payload) = attr?;
+        if WgDeviceAttribute::from(nla_type) == WgDeviceAttribute::Peers {
+            peers_attr = Some(payload);
+        }
+    }
 
-    for peer in handle.iter() {
-        let handle = peer.get_attr_handle()?;
-        peers.push(Peer::try_from(handle)?);
+    for (index, peer) in nla_iter(peers_attr.unwrap_or_default()).enumerate() {
+        let mut peer = PeerAttrs::parse(peer?.1)?;
+        peer.first = index == 0;
+        f(peer)?;
     }
 
-    Ok(peers)
+    Ok(())
 }
 
-pub fn parse_peer_builder(
-    handle: AttrHandle<'_, WgPeerAttribute>,
-) -> Result<PeerBuilder, ParseDeviceError> {
-    let mut peer_builder = PeerBuilder::default();
-
-    for attr in handle.iter() {
-        match attr.nla_type.nla_type & NLA_TYPE_MASK {
-            WgPeerAttribute::Unspec => {}
-            WgPeerAttribute::Flags => {}
-            WgPeerAttribute::PublicKey => {
-                peer_builder.public_key(parse_device_key(attr.nla_payload.as_ref())?);
-            }
-            WgPeerAttribute::PresharedKey => {
-                peer_builder.preshared_key(parse_device_key(attr.nla_payload.as_ref())?);
-            }
-            WgPeerAttribute::Endpoint => {
-                peer_builder.endpoint(Some(parse_sockaddr_in(attr.nla_payload.as_ref())?));
-            }
-            WgPeerAttribute::PersistentKeepaliveInterval => {
-                peer_builder
-                    .persistent_keepalive_interval(parse_nla_u16(attr.nla_payload.as_ref())?);
-            }
-            WgPeerAttribute::LastHandshakeTime => {
-                peer_builder
-                    .last_handshake_time(parse_last_handshake_time(attr.nla_payload.as_ref())?);
-            }
-            WgPeerAttribute::RxBytes => {
-                peer_builder.rx_bytes(parse_nla_u64(attr.nla_payload.as_ref())?);
-            }
-            WgPeerAttribute::TxBytes => {
-                peer_builder.tx_bytes(parse_nla_u64(attr.nla_payload.as_ref())?);
-            }
-            WgPeerAttribute::AllowedIps => {
-                let handle = attr.get_attr_handle()?;
-                peer_builder.allowed_ips(parse_allowedips(handle)?);
-            }
-            WgPeerAttribute::ProtocolVersion => {
-                peer_builder.protocol_version(parse_nla_u32(attr.nla_payload.as_ref())?);
-            }
-            WgPeerAttribute::UnrecognizedConst(i) => {
-                return Err(ParseDeviceError::UnknownPeerAttributeError { id: i })
+/// The attributes of a peer. The allowed IPs are left in the receive buffer
+/// until it's known whether they belong to a new peer or continue one.
+pub(crate) struct PeerAttrs<'a> {
+    builder: PeerBuilder,
+    allowed_ips: Option<&'a [u8]>,
+    /// Whether this is the first peer of its message.
+    first: bool,
+}
+
+impl<'a> PeerAttrs<'a> {
+    fn parse(buf: &'a [u8]) -> Result<Self, ParseDeviceError> {
+        let mut builder = PeerBuilder::default();
+        let mut allowed_ips = None;
+
+        for attr in nla_iter(buf) {
+            let (nla_type, payload) = attr?;
+            match WgPeerAttribute::from(nla_type) {
+                WgPeerAttribute::Unspec => {}
+                WgPeerAttribute::Flags => {}
+                WgPeerAttribute::PublicKey => {
+                    builder.public_key(parse_device_key(payload)?);
+                }
+                WgPeerAttribute::PresharedKey => {
+                    builder.preshared_key(parse_device_key(payload)?);
+                }
+                WgPeerAttribute::Endpoint => {
+                    builder.endpoint(Some(parse_sockaddr_in(payload)?));
+                }
+                WgPeerAttribute::PersistentKeepaliveInterval => {
+                    builder.persistent_keepalive_interval(parse_nla_u16(payload)?);
+                }
+                WgPeerAttribute::LastHandshakeTime => {
+                    builder.last_handshake_time(parse_last_handshake_time(payload)?);
+                }
+                WgPeerAttribute::RxBytes => {
+                    builder.rx_bytes(parse_nla_u64(payload)?);
+                }
+                WgPeerAttribute::TxBytes => {
+                    builder.tx_bytes(parse_nla_u64(payload)?);
+                }
+                WgPeerAttribute::AllowedIps => {
+                    allowed_ips = Some(payload);
+                }
+                WgPeerAttribute::ProtocolVersion => {
+                    builder.protocol_version(parse_nla_u32(payload)?);
+                }
+                WgPeerAttribute::UnrecognizedConst(i) => {
+                    return Err(ParseDeviceError::UnknownPeerAttributeError { id: i })
+                }
             }
         }
-    }
 
-    Ok(peer_builder)
-}
+        Ok(Self {
+            builder,
+            allowed_ips,
+            first: false,
+        })
+    }
 
-impl TryFrom<AttrHandle<'_, WgPeerAttribute>> for Peer {
-    type Error = ParseDeviceError;
-    fn try_from(handle: AttrHandle<'_, WgPeerAttribute>) -> Result<Self, Self::Error> {
-        let peer_builder = parse_peer_builder(handle)?;
-        Ok(peer_builder.build()?)
+    /// Whether this peer continues `previous`, the last peer of the previous
+    /// message, instead of being a new peer.
+    pub fn continues(&self, previous: &Peer) -> bool {
+        self.first && self.builder.public_key == Some(previous.public_key)
     }
-}
 
-pub fn parse_allowedips(
-    handle: AttrHandle<'_, NlaNested>,
-) -> Result<Vec<AllowedIp>, ParseDeviceError> {
-    let mut allowed_ips = vec![];
+    pub fn build(self) -> Result<Peer, ParseDeviceError> {
+        let mut peer = self.builder.build()?;
+        if let Some(allowed_ips) = self.allowed_ips {
+            parse_allowed_ips(allowed_ips, &mut peer.allowed_ips)?;
+        }
+        Ok(peer)
+    }
 
-    for allowed_ip in handle.iter() {
-        allowed_ips.push(AllowedIp::try_from(
-            allowed_ip.get_attr_handle::<WgAllowedIpAttribute>()?,
-        )?);
+    pub fn into_builder(mut self) -> Result<PeerBuilder, ParseDeviceError> {
+        if let Some(allowed_ips) = self.allowed_ips {
+            let mut parsed = vec![];
+            parse_allowed_ips(allowed_ips, &mut parsed)?;
+            self.builder.allowed_ips(parsed);
+        }
+        Ok(self.builder)
     }
 
-    Ok(allowed_ips)
+    pub fn append_allowed_ips_to(self, peer: &mut Peer) -> Result<(), ParseDeviceError> {
+        match self.allowed_ips {
+            Some(allowed_ips) => parse_allowed_ips(allowed_ips, &mut peer.allowed_ips),
+            None => Ok(()),
+        }
+    }
 }
 
-impl TryFrom<AttrHandle<'_, WgAllowedIpAttribute>> for AllowedIp {
-    type Error = ParseDeviceError;
+fn parse_allowed_ips(buf: &[u8], allowed_ips: &mut Vec<AllowedIp>) -> Result<(), ParseDeviceError> {
+    for allowed_ip in nla_iter(buf) {
+        allowed_ips.push(parse_allowed_ip(allowed_ip?.1)?);
+    }
+    Ok(())
+}
 
-    fn try_from(handle: AttrHandle<'_, WgAllowedIpAttribute>) -> Result<Self, Self::Error> {
-        let mut allowed_ip_builder = AllowedIpBuilder::default();
+fn parse_allowed_ip(buf: &[u8]) -> Result<AllowedIp, ParseDeviceError> {
+    let mut allowed_ip_builder = AllowedIpBuilder::default();
 
-        for attr in handle.iter() {
-            let payload = attr.nla_payload.as_ref();
-            match attr.nla_type.nla_type {
-                WgAllowedIpAttribute::Unspec => {}
-                WgAllowedIpAttribute::Family => {
-                    allowed_ip_builder.family(parse_nla_u16(payload)?);
-                }
-                WgAllowedIpAttribute::IpAddr => {
-                    let addr = match payload.len() {
-                        len if len == size_of::<in_addr>() => IpAddr::V4(parse_in_addr(payload)?),
-                        len if len == size_of::<in6_addr>() => IpAddr::V6(parse_in6_addr(payload)?),
-                        len => {
-                            return Err(ParseIpAddrError::InvalidIpAddrLengthError { found: len })
-                                .map_err(ParseAttributeError::from)
-                                .map_err(ParseDeviceError::from)
-                        }
-                    };
-                    allowed_ip_builder.ipaddr(addr);
-                }
-                WgAllowedIpAttribute::CidrMask => {
-                    allowed_ip_builder.cidr_mask(parse_nla_u8(payload)?);
-                }
-                WgAllowedIpAttribute::UnrecognizedConst(i) => {
-                    return Err(ParseDeviceError::UnknownAllowedIpAttributeError { id: i })
-                }
+    for attr in nla_iter(buf) {
+        let (nla_type, payload) = attr?;
+        match WgAllowedIpAttribute::from(nla_type) {
+            WgAllowedIpAttribute::Unspec => {}
+            WgAllowedIpAttribute::Family => {
+                allowed_ip_builder.family(parse_nla_u16(payload)?);
+            }
+            WgAllowedIpAttribute::IpAddr => {
+                let addr = match payload.len() {
+                    len if len == size_of::<in_addr>() => IpAddr::V4(parse_in_addr(payload)?),
+                    len if len == size_of::<in6_addr>() => IpAddr::V6(parse_in6_addr(payload)?),
+                    len => {
+                        return Err(ParseDeviceError::from(ParseAttributeError::from(
+                            ParseIpAddrError::InvalidIpAddrLengthError { found: len },
+                        )))
+                    }
+                };
+                allowed_ip_builder.ipaddr(addr);
+            }
+            WgAllowedIpAttribute::CidrMask => {
+                allowed_ip_builder.cidr_mask(parse_nla_u8(payload)?);
+            }
+            WgAllowedIpAttribute::UnrecognizedConst(i) => {
+                return Err(ParseDeviceError::UnknownAllowedIpAttributeError { id: i })
             }
         }
-
-        Ok(allowed_ip_builder.build()?)
     }
+
+    Ok(allowed_ip_builder.build()?)
 }
 
 macro_rules! create_parse_nla_int {
@@ -283,15 +374,28 @@ pub fn parse_device_key(buf: &[u8]) -> Result<[u8; 32], ParseAttributeError> {
 }
 
 pub fn parse_sockaddr_in(buf: &[u8]) -> Result<SocketAddr, ParseAttributeError> {
-    let family = parse_nla_u16(&buf[0..2])?;
+    // The family decides how long the rest of the address is.
+    let too_short = |expected: usize| ParseAttributeError::StaticLengthError {
+        expected,
+        found: buf.len(),
+    };
+    let family = parse_nla_u16(buf.get(0..2).ok_or_else(|| too_short(2))?)?;
+    let family = libc::c_int::from(family);
+    let expected = match family {
+        AF_INET => std::mem::size_of::<libc::sockaddr_in>(),
+        AF_INET6 => std::mem::size_of::<libc::sockaddr_in6>(),
+        id => return Err(ParseSockAddrError::UnrecognizedAddressFamilyError { id }.into()),
+    };
+    if buf.len() < expected {
+        return Err(too_short(expected));
+    }
 
     // The port bytes are always in network byte order (or big endian) according to man 7 ip.
     let port = parse_nla_u16_be(&buf[2..4])?;
 
-    let addr = match libc::c_int::from(family) {
+    let addr = match family {
         AF_INET => IpAddr::V4(parse_in_addr(&buf[4..8])?),
-        AF_INET6 => IpAddr::V6(parse_in6_addr(&buf[8..24])?),
-        id => return Err(ParseSockAddrError::UnrecognizedAddressFamilyError { id }.into()),
+        _ => IpAddr::V6(parse_in6_addr(&buf[8..24])?),
     };
 
     Ok(SocketAddr::new(addr, port))
@@ -349,12 +453,53 @@ pub fn parse_in6_addr(buf: &[u8]) -> Result<Ipv6Addr, ParseAttributeError> {
     ))
 }
 
+pub fn parse_link_stats64(buf: &[u8]) -> Result<LinkStats, ParseAttributeError> {
+    // Kernels before 4.6 don't report rx_nohandler, so only the first 23
+    // counters of struct rtnl_link_stats64 are required. Newer kernels may
+    // append counters this library doesn't know about yet.
+    const MIN_LEN: usize = 23 * size_of::<u64>();
+    Some(buf.len()).filter(|&len| len >= MIN_LEN).ok_or({
+        ParseAttributeError::StaticLengthError {
+            expected: MIN_LEN,
+            found: buf.len(),
+        }
+    })?;
+
+    let mut counters = buf.chunks_exact(size_of::<u64>()).map(parse_nla_u64);
+    let mut next = || counters.next().transpose().map(Option::unwrap_or_default);
+
+    Ok(LinkStats {
+        rx_packets: next()?,
+        tx_packets: next()?,
+        rx_bytes: next()?,
+        tx_bytes: next()?,
+        rx_errors: next()?,
+        tx_errors: next()?,
+        rx_dropped: next()?,
+        tx_dropped: next()?,
+        multicast: next()?,
+        collisions: next()?,
+        rx_length_errors: next()?,
+        rx_over_errors: next()?,
+        rx_crc_errors: next()?,
+        rx_frame_errors: next()?,
+        rx_fifo_errors: next()?,
+        rx_missed_errors: next()?,
+        tx_aborted_errors: next()?,
+        tx_carrier_errors: next()?,
+        tx_fifo_errors: next()?,
+        tx_heartbeat_errors: next()?,
+        tx_window_errors: next()?,
+        rx_compressed: next()?,
+        tx_compressed: next()?,
+        rx_nohandler: next()?,
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::linux::cmd::WgCmd;
     use anyhow::Error;
-    use neli::{err::DeError, genl::Genlmsghdr};
 
     // This device comes from the configuration example in "man wg", but with
     // the third peer removed since it specifies an domain endpoint only valid
@@ -424,13 +569,6 @@ mod tests {
         })
     }
 
-    fn create_test_genlmsghdr(
-        payload: &[u8],
-    ) -> Result<Genlmsghdr<WgCmd, WgDeviceAttribute>, DeError> {
-        use neli::FromBytesWithInput;
-        Genlmsghdr::from_bytes_with_input(&mut std::io::Cursor::new(payload), payload.len())
-    }
-
     #[test]
     fn parse_device_example_from_man_page() -> Result<(), Error> {
         let payload = vec![
@@ -475,11 +613,17 @@ mod tests {
             0x1c, 0x00, 0x00, 0x80, 0x05, 0x00, 0x03, 0x00, 0x10, 0x00, 0x00, 0x00, 0x06, 0x00,
             0x01, 0x00, 0x02, 0x00, 0x00, 0x00, 0x08, 0x00, 0x02, 0x00, 0xc0, 0xa8, 0x00, 0x00,
         ];
-        let genlmsghdr = create_test_genlmsghdr(&payload)?;
-        let device = Device::try_from(genlmsghdr.get_attr_handle())?;
+        let device = parse_device(&payload)?;
 
         assert_eq!(device, get_device_from_man()?);
 
+        // Attributes that run past the end of the message are rejected
+        // instead of being read out of bounds.
+        assert!(matches!(
+            parse_device(&payload[..payload.len() - 3]),
+            Err(ParseDeviceError::NlDeError(DeError::UnexpectedEOB))
+        ));
+
         Ok(())
     }
 
@@ -513,8 +657,7 @@ mod tests {
             6, 0, 1, 0, 2, 0, 0, 0, 8, 0, 2, 0, 192, 168, 0, 0, 20, 0, 0, 0, 3, 0, 2, 0, 0, 0, 0,
             0, 250, 117, 199, 159, 0, 0, 0, 0,
         ];
-        let genlmsghdr = create_test_genlmsghdr(&payload)?;
-        let device = Device::try_from(genlmsghdr.get_attr_handle())?;
+        let device = parse_device(&payload)?;
 
         assert_eq!(device, get_device_from_man()?);
 
@@ -766,11 +909,8 @@ mod tests {
             0, 0, 0, 0, 0, 0, 0, 0, 150,
         ];
 
-        let genlmsghdr = create_test_genlmsghdr(&first_payload)?;
-        let device = Device::try_from(genlmsghdr.get_attr_handle())?;
-
-        let genlmsghdr = create_test_genlmsghdr(&second_payload)?;
-        let device = extend_device(device, genlmsghdr.get_attr_handle())?;
+        let mut device = parse_device(&first_payload)?;
+        extend_device(&mut device, &second_payload)?;
 
         assert_eq!(
             device,
@@ -810,4 +950,34 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn parse_link_stats64_from_counters() -> Result<(), Error> {
+        let counters: Vec<u8> = (1..=24u64).flat_map(u64::to_ne_bytes).collect();
+        let stats = parse_link_stats64(&counters)?;
+        assert_eq!(stats.rx_packets, 1);
+        assert_eq!(stats.tx_bytes, 4);
+        assert_eq!(stats.tx_dropped, 8);
+        assert_eq!(stats.rx_missed_errors, 16);
+        assert_eq!(stats.rx_nohandler, 24);
+
+        // Pre-4.6 kernels omit rx_nohandler.
+        let stats = parse_link_stats64(&counters[..23 * 8])?;
+        assert_eq!(stats.tx_compressed, 23);
+        assert_eq!(stats.rx_nohandler, 0);
+
+        // Counters added by future kernels are ignored.
+        let counters: Vec<u8> = (1..=26u64).flat_map(u64::to_ne_bytes).collect();
+        assert_eq!(parse_link_stats64(&counters)?.rx_nohandler, 24);
+
+        assert!(matches!(
+            parse_link_stats64(&counters[..22 * 8]),
+            Err(ParseAttributeError::StaticLengthError {
+                expected: 184,
+                found: 176
+            })
+        ));
+
+        Ok(())
+    }
 }
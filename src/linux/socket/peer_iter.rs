@@ -0,0 +1,141 @@
+use super::ext_ack::decode_nl_error;
+use super::parse::{for_each_peer, parse_device};
+use super::transport::{NetlinkTransport, NlConnection, Transport};
+use crate::get;
+use crate::linux::err::GetDeviceError;
+use neli::consts::nl::Nlmsg;
+use std::collections::VecDeque;
+
+/// Streams the peers of a device as the kernel sends them. Created by
+/// [`WgSocket::get_device_peers`](crate::WgSocket::get_device_peers).
+///
+/// Only the peers of the netlink message that's currently being processed are
+/// held in memory. A peer whose allowed IPs continue in the next message is
+/// held back until that message arrives, so every yielded peer is complete.
+///
+/// Dropping the iterator early reads and discards the rest of the dump so the
+/// socket can be used for the next request.
+pub struct PeerIter<'a, T: Transport = NetlinkTransport> {
+    sock: &'a mut NlConnection<T>,
+    /// The sequence number of the request the dump answers.
+    seq: u32,
+    device: get::Device,
+    ready: VecDeque<get::Peer>,
+    /// The last peer seen so far, which may still be continued.
+    held: Option<get::Peer>,
+    done: bool,
+}
+
+impl<'a, T: Transport> PeerIter<'a, T> {
+    /// Expects the WG_CMD_GET_DEVICE request numbered `seq` to have been
+    /// sent on `sock` already. Reads the first message of the dump to learn
+    /// the device attributes.
+    pub(crate) fn new(sock: &'a mut NlConnection<T>, seq: u32) -> Result<Self, GetDeviceError> {
+        let capped_acks = sock.capped_acks();
+        let response = sock
+            .recv_reply(seq)
+            .map_err(|err| decode_nl_error::<GetDeviceError>(err, capped_acks))?
+            .filter(|response| response.nl_type != Nlmsg::Done.into())
+            .ok_or(GetDeviceError::AccessError)?;
+
+        let done = response.is_last();
+        let mut device = match parse_device(response.payload) {
+            Ok(device) => device,
+            Err(err) => {
+                if !done {
+                    drain(sock, seq);
+                }
+                return Err(err.into());
+            }
+        };
+        let mut ready: VecDeque<get::Peer> = std::mem::take(&mut device.peers).into();
+        let held = ready.pop_back();
+
+        Ok(Self {
+            sock,
+            seq,
+            device,
+            ready,
+            held,
+            done,
+        })
+    }
+
+    /// The device attributes. Its `peers` are always empty since they're
+    /// yielded by the iterator instead.
+    pub fn device(&self) -> &get::Device {
+        &self.device
+    }
+
+    fn recv_next_message(&mut self) -> Result<(), GetDeviceError> {
+        let capped_acks = self.sock.capped_acks();
+        let response = self
+            .sock
+            .recv_reply(self.seq)
+            .map_err(|err| decode_nl_error::<GetDeviceError>(err, capped_acks))?;
+
+        let response = match response {
+            Some(response) if response.nl_type != Nlmsg::Done.into() => response,
+            _ => {
+                self.done = true;
+                return Ok(());
+            }
+        };
+
+        let (held, ready) = (&mut self.held, &mut self.ready);
+        for_each_peer(response.payload, |peer| {
+            match held.as_mut() {
+                Some(held) if peer.continues(held) => peer.append_allowed_ips_to(held)?,
+                _ => {
+                    if let Some(previous) = held.replace(peer.build()?) {
+                        ready.push_back(previous);
+                    }
+                }
+            }
+            Ok(())
+        })?;
+        self.done = response.is_last();
+
+        Ok(())
+    }
+}
+
+impl<T: Transport> Iterator for PeerIter<'_, T> {
+    type Item = Result<get::Peer, GetDeviceError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(peer) = self.ready.pop_front() {
+                return Some(Ok(peer));
+            }
+            if self.done {
+                return self.held.take().map(Ok);
+            }
+            if let Err(err) = self.recv_next_message() {
+                // The rest of the dump can't be trusted after an error.
+                self.done = true;
+                self.held = None;
+                return Some(Err(err));
+            }
+        }
+    }
+}
+
+impl<T: Transport> Drop for PeerIter<'_, T> {
+    fn drop(&mut self) {
+        if !self.done {
+            drain(self.sock, self.seq);
+        }
+    }
+}
+
+/// Reads and discards the rest of the dump answering the request numbered
+/// `seq`. The kernel runs only one dump per socket at a time, so the next
+/// dump would fail with `EBUSY` if this one was left unfinished.
+fn drain<T: Transport>(sock: &mut NlConnection<T>, seq: u32) {
+    while let Ok(Some(response)) = sock.recv_reply(seq) {
+        if response.is_last() {
+            break;
+        }
+    }
+}
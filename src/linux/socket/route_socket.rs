@@ -1,73 +1,268 @@
+use super::ext_ack::decode_nl_error;
+use super::link_info_utils::{get_link_msg, LinkInfo};
 use super::list_device_names_utils;
-use super::{link_message, WireGuardDeviceLinkOperation};
-use crate::err::{ConnectError, LinkDeviceError, ListDevicesError};
-use list_device_names_utils::PotentialWireGuardDeviceName;
+use super::transport::{NetlinkTransport, NlConnection, Transport};
+use super::{del_link_message, new_link_message};
+use crate::err::{
+    ConnectError, GetLinkStatsError, KernelError, LinkDeviceError, ListDevicesError,
+    ParseAttributeError,
+};
+use crate::linux::link;
+use crate::linux::stats::LinkStats;
+use crate::linux::{is_valid_ifname, DeviceInterface};
 use neli::{
-    consts::{nl::Nlmsg, socket::NlFamily},
-    rtnl::Ifinfomsg,
-    socket::NlSocketHandle,
+    consts::{nl::Nlmsg, rtnl::Rtm, socket::NlFamily},
+    err::{DeError, NlError, SerError},
+    nl::Nlmsghdr,
+    ToBytes,
 };
-use std::convert::TryFrom;
+use std::fmt::Debug;
+use std::io;
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::time::Duration;
+
+pub struct RouteSocket<T = NetlinkTransport> {
+    sock: NlConnection<T>,
+}
 
-pub struct RouteSocket {
-    sock: NlSocketHandle,
+/// A request for the names of the WireGuard devices whose reply is still
+/// being received. Created by [`RouteSocket::send_list_device_names`] and
+/// completed by [`RouteSocket::poll_list_device_names`].
+#[derive(Debug)]
+pub struct DeviceNamesRequest {
+    seq: u32,
+    names: Vec<String>,
 }
 
 impl RouteSocket {
     pub fn connect() -> Result<Self, ConnectError> {
-        // Autoselect a PID
-        let pid = None;
-        let groups = &[];
-        let sock = NlSocketHandle::connect(NlFamily::Route, pid, groups)?;
+        Ok(Self::from_transport(NetlinkTransport::connect(
+            NlFamily::Route,
+        )?))
+    }
 
-        Ok(Self { sock })
+    /// See [`NetlinkTransport::read_timeout`].
+    pub fn read_timeout(&self) -> io::Result<Option<Duration>> {
+        self.sock.transport().read_timeout()
     }
 
-    pub fn add_device(&mut self, ifname: &str) -> Result<(), LinkDeviceError> {
-        let operation = WireGuardDeviceLinkOperation::Add;
-        self.sock.send(link_message(ifname, operation)?)?;
-        self.sock.recv()?;
+    /// See [`WgSocket::set_read_timeout`](crate::WgSocket::set_read_timeout).
+    pub fn set_read_timeout(&mut self, timeout: Option<Duration>) -> io::Result<()> {
+        self.sock.transport_mut().set_read_timeout(timeout)
+    }
+
+    /// Switches the socket between blocking and non-blocking mode. Use
+    /// [`send_list_device_names`](Self::send_list_device_names) and
+    /// [`poll_list_device_names`](Self::poll_list_device_names) to list
+    /// devices from a poll or epoll loop. See
+    /// [non-blocking mode](crate::err#non-blocking-mode).
+    pub fn set_nonblocking(&mut self, nonblocking: bool) -> io::Result<()> {
+        self.sock.transport_mut().set_nonblocking(nonblocking)
+    }
+}
+
+impl<T: Transport + AsRawFd> AsRawFd for RouteSocket<T> {
+    fn as_raw_fd(&self) -> RawFd {
+        self.sock.transport().as_raw_fd()
+    }
+}
+
+impl<T: Transport> RouteSocket<T> {
+    /// Creates a socket that exchanges `NETLINK_ROUTE` messages over
+    /// `transport` instead of a socket connected to the kernel.
+    pub fn from_transport(transport: T) -> Self {
+        Self {
+            sock: NlConnection::new(transport),
+        }
+    }
 
+    pub fn add_device(&mut self, ifname: &str) -> Result<(), LinkDeviceError> {
+        self.create_device(&link::Device::from_ifname(ifname))?;
         Ok(())
     }
 
+    /// Creates a WireGuard device interface and returns its ifindex.
+    ///
+    /// The ifindex of a device created in another network namespace can't be
+    /// looked up from this socket. Creating a device with
+    /// [`link::Device::netns`] therefore requires an explicit
+    /// [`link::Device::ifindex`] and can't be combined with
+    /// [`link::Device::ensure_exists`].
+    pub fn create_device(&mut self, device: &link::Device) -> Result<u32, LinkDeviceError> {
+        if !is_valid_ifname(&device.ifname) {
+            return Err(LinkDeviceError::InvalidInterfaceName);
+        }
+
+        if device.netns.is_some() && (device.ifindex.is_none() || device.ensure_exists) {
+            return Err(LinkDeviceError::UnsupportedNetNsOptions);
+        }
+
+        let interface = DeviceInterface::from_name(device.ifname.as_ref());
+
+        if device.ensure_exists {
+            match self.get_link_info::<LinkDeviceError>(&interface) {
+                Ok(link_info) if link_info.is_wireguard() => return Ok(link_info.ifindex),
+                Ok(link_info) => return Err(LinkDeviceError::NotWireGuard(link_info.ifindex)),
+                Err(LinkDeviceError::KernelError(err)) if err.raw_os_error() == libc::ENODEV => {}
+                Err(err) => return Err(err),
+            }
+        }
+
+        self.send_and_ack(new_link_message(device)?)?;
+
+        match device.ifindex {
+            Some(ifindex) => Ok(ifindex),
+            None => Ok(self.get_link_info::<LinkDeviceError>(&interface)?.ifindex),
+        }
+    }
+
+    /// Deletes a WireGuard device interface by name. Interfaces of any other
+    /// kind are left alone and reported as
+    /// [`LinkDeviceError::NotWireGuard`].
     pub fn del_device(&mut self, ifname: &str) -> Result<(), LinkDeviceError> {
-        let operation = WireGuardDeviceLinkOperation::Delete;
-        self.sock.send(link_message(ifname, operation)?)?;
-        self.sock.recv()?;
+        self.del_wireguard_device(DeviceInterface::from_name(ifname))
+    }
 
-        Ok(())
+    /// Same as [`del_device`](Self::del_device), but by ifindex. Unlike names,
+    /// an ifindex can't end up pointing at a different interface after a
+    /// rename.
+    pub fn del_device_by_index(&mut self, ifindex: u32) -> Result<(), LinkDeviceError> {
+        self.del_wireguard_device(DeviceInterface::from_index(ifindex))
+    }
+
+    /// Deletes an interface without checking that it's a WireGuard device.
+    pub fn del_device_unchecked(
+        &mut self,
+        interface: DeviceInterface,
+    ) -> Result<(), LinkDeviceError> {
+        if !interface.is_valid() {
+            return Err(LinkDeviceError::InvalidInterfaceName);
+        }
+
+        self.send_and_ack(del_link_message(&interface)?)
+    }
+
+    fn del_wireguard_device(&mut self, interface: DeviceInterface) -> Result<(), LinkDeviceError> {
+        if !interface.is_valid() {
+            return Err(LinkDeviceError::InvalidInterfaceName);
+        }
+
+        let link_info = self.get_link_info::<LinkDeviceError>(&interface)?;
+        if !link_info.is_wireguard() {
+            return Err(LinkDeviceError::NotWireGuard(link_info.ifindex));
+        }
+
+        // Delete by the index that was just checked so a rename in between
+        // can't redirect the request to another interface.
+        self.del_device_unchecked(DeviceInterface::from_index(link_info.ifindex))
+    }
+
+    fn send_and_ack<P>(&mut self, mut message: Nlmsghdr<Rtm, P>) -> Result<(), LinkDeviceError>
+    where
+        P: ToBytes + Debug,
+    {
+        let seq = self.sock.next_seq();
+        message.nl_seq = seq;
+        self.sock.send(message)?;
+
+        let capped_acks = self.sock.capped_acks();
+        self.sock
+            .recv_ack(seq)
+            .map_err(|err| decode_nl_error::<LinkDeviceError>(err, capped_acks))
     }
 
     /// Retrieves all interface names that have the string "wireguard" as an
     /// [IFLA_INFO_KIND](libc::IFLA_INFO_KIND) value.
     pub fn list_device_names(&mut self) -> Result<Vec<String>, ListDevicesError> {
-        self.sock
-            .send(list_device_names_utils::get_list_device_names_msg())?;
+        let mut request = self.send_list_device_names()?;
+        self.poll_list_device_names(&mut request)
+    }
+
+    /// Sends the request [`list_device_names`](Self::list_device_names)
+    /// makes without waiting for the reply. Receive it with
+    /// [`poll_list_device_names`](Self::poll_list_device_names).
+    pub fn send_list_device_names(&mut self) -> Result<DeviceNamesRequest, ListDevicesError> {
+        let mut message = list_device_names_utils::get_list_device_names_msg();
+        let seq = self.sock.next_seq();
+        message.nl_seq = seq;
+        self.sock.send(message)?;
 
-        let mut iter = self.sock.iter::<Nlmsg, Ifinfomsg>(false);
+        Ok(DeviceNamesRequest { seq, names: vec![] })
+    }
 
-        let mut result_names = vec![];
+    /// Receives as much of the reply to `request` as is queued on the socket.
+    /// In non-blocking mode, this fails with [`ListDevicesError::WouldBlock`]
+    /// until the reply is complete. Poll again once the socket is readable.
+    ///
+    /// Any other result completes the request.
+    pub fn poll_list_device_names(
+        &mut self,
+        request: &mut DeviceNamesRequest,
+    ) -> Result<Vec<String>, ListDevicesError> {
+        let capped_acks = self.sock.capped_acks();
 
-        while let Some(Ok(response)) = iter.next() {
-            match response.nl_type {
-                Nlmsg::Error => return Err(ListDevicesError::Unknown),
-                Nlmsg::Done => break,
-                _ => (),
+        while let Some(response) = self
+            .sock
+            .recv_reply(request.seq)
+            .map_err(|err| decode_nl_error::<ListDevicesError>(err, capped_acks))?
+        {
+            if response.nl_type == Nlmsg::Done.into() {
+                break;
             }
 
-            let PotentialWireGuardDeviceName {
-                is_wireguard,
-                ifname,
-            } = PotentialWireGuardDeviceName::try_from(response)?;
+            let link_info = LinkInfo::parse::<ListDevicesError>(response.payload)?;
 
-            if is_wireguard {
-                if let Some(ifname) = ifname {
-                    result_names.push(ifname);
+            if link_info.is_wireguard() {
+                if let Some(ifname) = link_info.ifname {
+                    request.names.push(ifname);
                 }
             }
+            if response.is_last() {
+                break;
+            }
+        }
+
+        Ok(std::mem::take(&mut request.names))
+    }
+
+    /// Retrieves the interface statistics (IFLA_STATS64) of a WireGuard
+    /// device. These include packet, error, and drop counts that aren't
+    /// available through [`WgSocket::get_device`](crate::WgSocket::get_device).
+    pub fn get_link_stats(
+        &mut self,
+        interface: DeviceInterface,
+    ) -> Result<LinkStats, GetLinkStatsError> {
+        if !interface.is_valid() {
+            return Err(GetLinkStatsError::InvalidInterfaceName);
+        }
+
+        let link_info = self.get_link_info::<GetLinkStatsError>(&interface)?;
+
+        if !link_info.is_wireguard() {
+            return Err(GetLinkStatsError::NotWireGuard(link_info.ifindex));
         }
 
-        Ok(result_names)
+        link_info
+            .stats
+            .ok_or(GetLinkStatsError::MissingStats(link_info.ifindex))
+    }
+
+    fn get_link_info<E>(&mut self, interface: &DeviceInterface) -> Result<LinkInfo, E>
+    where
+        E: From<NlError> + From<KernelError> + From<SerError> + From<DeError>,
+        E: From<ParseAttributeError>,
+    {
+        let mut message = get_link_msg(interface)?;
+        let seq = self.sock.next_seq();
+        message.nl_seq = seq;
+        self.sock.send(message)?;
+
+        let capped_acks = self.sock.capped_acks();
+        let response = self
+            .sock
+            .recv_reply(seq)
+            .map_err(|err| decode_nl_error::<E>(err, capped_acks))?
+            .ok_or_else(|| NlError::msg("No response received for link request"))?;
+        LinkInfo::parse::<E>(response.payload)
     }
 }
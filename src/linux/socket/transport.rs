@@ -0,0 +1,703 @@
+use super::ext_ack::enable_ext_ack;
+use crate::linux::set::NETLINK_MSG_LIMIT;
+use neli::{
+    consts::{
+        nl::{NlType, Nlmsg},
+        socket::NlFamily,
+        MAX_NL_LENGTH,
+    },
+    err::NlError,
+    nl::{NlPayload, Nlmsghdr},
+    socket::NlSocket,
+    types::Buffer,
+    FromBytes, ToBytes,
+};
+use std::convert::{TryFrom, TryInto};
+use std::fmt::Debug;
+use std::io::{self, Cursor};
+use std::mem::size_of;
+use std::ops::Range;
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::time::Duration;
+
+/// The channel [`WgSocket`](crate::WgSocket) and
+/// [`RouteSocket`](crate::RouteSocket) exchange netlink messages over.
+///
+/// [`NetlinkTransport`] talks to the kernel. Other implementations can answer
+/// requests themselves, which allows testing code that uses the sockets
+/// without root privileges or the WireGuard kernel module.
+pub trait Transport {
+    /// Sends a datagram containing one netlink message.
+    fn send(&mut self, buf: &[u8]) -> io::Result<()>;
+
+    /// Receives a datagram into `buf` and returns its length. A datagram may
+    /// contain several netlink messages.
+    fn recv(&mut self, buf: &mut [u8]) -> io::Result<usize>;
+
+    /// Whether `NLMSG_ERROR` replies leave out the payload of the request
+    /// they answer.
+    fn capped_acks(&self) -> bool {
+        false
+    }
+
+    /// The size of the largest message the other end accepts. Set requests
+    /// that don't fit are split into several messages.
+    fn max_message_size(&self) -> usize {
+        NETLINK_MSG_LIMIT
+    }
+}
+
+impl<T: Transport + ?Sized> Transport for &mut T {
+    fn send(&mut self, buf: &[u8]) -> io::Result<()> {
+        (**self).send(buf)
+    }
+
+    fn recv(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        (**self).recv(buf)
+    }
+
+    fn capped_acks(&self) -> bool {
+        (**self).capped_acks()
+    }
+
+    fn max_message_size(&self) -> usize {
+        (**self).max_message_size()
+    }
+}
+
+const NL_HEADER_SIZE: usize = 16;
+const NLM_F_MULTI: u16 = libc::NLM_F_MULTI as u16;
+
+/// A netlink socket connected to the kernel. This is the default transport.
+pub struct NetlinkTransport {
+    sock: NlSocket,
+    capped_acks: bool,
+    max_message_size: usize,
+    nonblocking: bool,
+}
+
+impl NetlinkTransport {
+    pub fn connect(family: NlFamily) -> io::Result<Self> {
+        // Autoselect a PID
+        let pid = None;
+        let groups = &[];
+        let sock = NlSocket::connect(family, pid, groups)?;
+        let capped_acks = enable_ext_ack(&sock);
+        let max_message_size = max_send_size(&sock);
+
+        Ok(Self {
+            sock,
+            capped_acks,
+            max_message_size,
+            nonblocking: false,
+        })
+    }
+
+    /// How long receiving a reply may block before failing. `None`, the
+    /// default, blocks until the kernel answers.
+    pub fn read_timeout(&self) -> io::Result<Option<Duration>> {
+        let mut timeout = libc::timeval {
+            tv_sec: 0,
+            tv_usec: 0,
+        };
+        let mut len = size_of::<libc::timeval>() as libc::socklen_t;
+        let result = unsafe {
+            libc::getsockopt(
+                self.sock.as_raw_fd(),
+                libc::SOL_SOCKET,
+                libc::SO_RCVTIMEO,
+                &mut timeout as *mut libc::timeval as *mut libc::c_void,
+                &mut len,
+            )
+        };
+        if result != 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        let timeout = Duration::new(timeout.tv_sec as u64, timeout.tv_usec as u32 * 1000);
+        Ok(Some(timeout).filter(|timeout| !timeout.is_zero()))
+    }
+
+    /// Sets the `SO_RCVTIMEO` option of the socket. Like
+    /// [`std::net::UdpSocket::set_read_timeout`], a zero duration is rejected
+    /// since the kernel would take it to mean no timeout.
+    pub fn set_read_timeout(&mut self, timeout: Option<Duration>) -> io::Result<()> {
+        let timeout = match timeout {
+            Some(timeout) if timeout.is_zero() => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "Cannot set a zero duration timeout",
+                ))
+            }
+            Some(timeout) => {
+                // Durations below a microsecond would round down to no
+                // timeout at all.
+                let timeout = timeout.max(Duration::from_micros(1));
+                libc::timeval {
+                    tv_sec: libc::time_t::try_from(timeout.as_secs()).unwrap_or(libc::time_t::MAX),
+                    tv_usec: timeout.subsec_micros() as libc::suseconds_t,
+                }
+            }
+            None => libc::timeval {
+                tv_sec: 0,
+                tv_usec: 0,
+            },
+        };
+        let result = unsafe {
+            libc::setsockopt(
+                self.sock.as_raw_fd(),
+                libc::SOL_SOCKET,
+                libc::SO_RCVTIMEO,
+                &timeout as *const libc::timeval as *const libc::c_void,
+                size_of::<libc::timeval>() as libc::socklen_t,
+            )
+        };
+        if result != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+
+    /// Switches the socket between blocking and non-blocking mode. In
+    /// non-blocking mode, receiving fails with [`io::ErrorKind::WouldBlock`]
+    /// right away if no reply is queued.
+    pub fn set_nonblocking(&mut self, nonblocking: bool) -> io::Result<()> {
+        if nonblocking {
+            self.sock.nonblock()?;
+        } else {
+            self.sock.block()?;
+        }
+        self.nonblocking = nonblocking;
+        Ok(())
+    }
+}
+
+impl Transport for NetlinkTransport {
+    fn send(&mut self, buf: &[u8]) -> io::Result<()> {
+        self.sock.send(buf, 0)?;
+        Ok(())
+    }
+
+    fn recv(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self.sock.recv(buf, 0) {
+            // The kernel reports an expired read timeout as EAGAIN as well,
+            // which only means the call would block in non-blocking mode.
+            Err(err) if err.kind() == io::ErrorKind::WouldBlock && !self.nonblocking => {
+                Err(io::ErrorKind::TimedOut.into())
+            }
+            result => result,
+        }
+    }
+
+    fn capped_acks(&self) -> bool {
+        self.capped_acks
+    }
+
+    fn max_message_size(&self) -> usize {
+        self.max_message_size
+    }
+}
+
+/// The size of the largest message the kernel accepts on a netlink socket.
+/// `netlink_sendmsg` fails with EMSGSIZE for messages that don't fit into the
+/// send buffer minus 32 bytes.
+pub(crate) fn max_send_size(sock: &impl AsRawFd) -> usize {
+    let mut sndbuf: libc::c_int = 0;
+    let mut len = size_of::<libc::c_int>() as libc::socklen_t;
+    let result = unsafe {
+        libc::getsockopt(
+            sock.as_raw_fd(),
+            libc::SOL_SOCKET,
+            libc::SO_SNDBUF,
+            &mut sndbuf as *mut libc::c_int as *mut libc::c_void,
+            &mut len,
+        )
+    };
+
+    match usize::try_from(sndbuf) {
+        Ok(sndbuf) if result == 0 => sndbuf.saturating_sub(32),
+        _ => NETLINK_MSG_LIMIT,
+    }
+}
+
+impl AsRawFd for NetlinkTransport {
+    fn as_raw_fd(&self) -> RawFd {
+        self.sock.as_raw_fd()
+    }
+}
+
+/// A netlink message whose payload points into the receive buffer of an
+/// [`NlConnection`].
+pub(crate) struct RawNlmsg<'a> {
+    pub nl_type: u16,
+    pub nl_flags: u16,
+    pub payload: &'a [u8],
+}
+
+impl RawNlmsg<'_> {
+    /// Whether this message ends its reply: a `NLMSG_DONE`, an error or ACK,
+    /// or a message that's not part of a multipart reply.
+    pub fn is_last(&self) -> bool {
+        self.nl_type == Nlmsg::Done.into()
+            || self.nl_type == Nlmsg::Error.into()
+            || self.nl_flags & NLM_F_MULTI == 0
+    }
+}
+
+/// Serializes and parses the netlink messages exchanged over a transport. This
+/// follows `NlSocketHandle::{send, recv}` so the sockets can use either.
+pub(crate) struct NlConnection<T> {
+    transport: T,
+    buffer: Vec<u8>,
+    position: usize,
+    end: usize,
+    seq: u32,
+}
+
+impl<T: Transport> NlConnection<T> {
+    pub fn new(transport: T) -> Self {
+        Self {
+            transport,
+            buffer: vec![0; MAX_NL_LENGTH],
+            position: 0,
+            end: 0,
+            seq: 0,
+        }
+    }
+
+    /// Returns a sequence number for a request whose reply has to be told
+    /// apart from leftovers of earlier requests.
+    pub fn next_seq(&mut self) -> u32 {
+        self.seq = self.seq.wrapping_add(1);
+        self.seq
+    }
+
+    pub fn transport(&self) -> &T {
+        &self.transport
+    }
+
+    pub fn transport_mut(&mut self) -> &mut T {
+        &mut self.transport
+    }
+
+    pub fn capped_acks(&self) -> bool {
+        self.transport.capped_acks()
+    }
+
+    pub fn max_message_size(&self) -> usize {
+        self.transport.max_message_size()
+    }
+
+    pub fn send<M, P>(&mut self, msg: Nlmsghdr<M, P>) -> Result<(), NlError>
+    where
+        M: NlType + Debug,
+        P: ToBytes + Debug,
+    {
+        let mut buffer = Cursor::new(Vec::new());
+        msg.to_bytes(&mut buffer)?;
+        self.transport.send(buffer.get_ref())?;
+        Ok(())
+    }
+
+    /// Returns the next message. Error messages with a non-zero errno are
+    /// returned as `NlError::Nlmsgerr`. Returns `None` if the transport has
+    /// nothing more to read.
+    pub fn recv(&mut self) -> Result<Option<Nlmsghdr<u16, Buffer>>, NlError> {
+        let message = match self.next_message()? {
+            Some(message) => message,
+            None => return Ok(None),
+        };
+
+        let message = Nlmsghdr::<u16, Buffer>::from_bytes(&mut Cursor::new(&self.buffer[message]))?;
+        if let NlPayload::Err(err) = message.nl_payload {
+            return Err(NlError::Nlmsgerr(err));
+        }
+
+        Ok(Some(message))
+    }
+
+    /// Like [`recv`](Self::recv), but the message borrows its payload from
+    /// the receive buffer instead of copying it out. Only messages answering
+    /// the request numbered `seq` are returned. Replies to other requests,
+    /// such as the rest of a dump that was abandoned after an error, are
+    /// skipped.
+    pub fn recv_reply(&mut self, seq: u32) -> Result<Option<RawNlmsg<'_>>, NlError> {
+        let message = loop {
+            let message = match self.next_message()? {
+                Some(message) => message,
+                None => return Ok(None),
+            };
+            let buf = &self.buffer[message.clone()];
+            if u32::from_ne_bytes([buf[8], buf[9], buf[10], buf[11]]) == seq {
+                break message;
+            }
+        };
+        let buf = &self.buffer[message];
+
+        let header = RawNlmsg {
+            nl_type: u16::from_ne_bytes([buf[4], buf[5]]),
+            nl_flags: u16::from_ne_bytes([buf[6], buf[7]]),
+            payload: buf.get(NL_HEADER_SIZE..).unwrap_or_default(),
+        };
+
+        // Errors are rare enough to go through neli, which keeps them in the
+        // same shape recv returns them in.
+        if header.nl_type == Nlmsg::Error.into() {
+            let message = Nlmsghdr::<u16, Buffer>::from_bytes(&mut Cursor::new(buf))?;
+            if let NlPayload::Err(err) = message.nl_payload {
+                return Err(NlError::Nlmsgerr(err));
+            }
+        }
+
+        Ok(Some(header))
+    }
+
+    /// Waits for the ACK of the request numbered `seq`. Replies to other
+    /// requests, such as the rest of a dump that was abandoned after an
+    /// error, are skipped. A non-zero errno is returned as
+    /// `NlError::Nlmsgerr`.
+    pub fn recv_ack(&mut self, seq: u32) -> Result<(), NlError> {
+        loop {
+            let message = match self.next_message()? {
+                Some(message) => message,
+                None => return Err(NlError::msg(format!("No ACK received for request {}", seq))),
+            };
+            let buf = &self.buffer[message];
+
+            let nl_type = u16::from_ne_bytes([buf[4], buf[5]]);
+            let nl_seq = u32::from_ne_bytes([buf[8], buf[9], buf[10], buf[11]]);
+            if nl_type != Nlmsg::Error.into() || nl_seq != seq {
+                continue;
+            }
+
+            let message = Nlmsghdr::<u16, Buffer>::from_bytes(&mut Cursor::new(buf))?;
+            return match message.nl_payload {
+                NlPayload::Err(err) => Err(NlError::Nlmsgerr(err)),
+                _ => Ok(()),
+            };
+        }
+    }
+
+    /// Finds the next message in the receive buffer, reading the next
+    /// datagram from the transport once the buffer is exhausted.
+    fn next_message(&mut self) -> Result<Option<Range<usize>>, NlError> {
+        if self.position == self.end {
+            let read = self.transport.recv(&mut self.buffer)?;
+            if read == 0 {
+                return Ok(None);
+            }
+            self.position = 0;
+            self.end = read;
+        }
+
+        let nl_len = self.buffer[self.position..self.end]
+            .get(0..4)
+            .and_then(|len| len.try_into().ok())
+            .map(|len| u32::from_ne_bytes(len) as usize)
+            .filter(|&len| len >= NL_HEADER_SIZE && self.position + len <= self.end);
+        let nl_len = match nl_len {
+            Some(nl_len) => nl_len,
+            None => {
+                // Skip the rest of the datagram since the next message can't
+                // be found anymore.
+                self.position = self.end;
+                return Err(NlError::msg("Incomplete packet received from socket"));
+            }
+        };
+
+        let message = self.position..self.position + nl_len;
+        self.position = (self.position + ((nl_len + 3) & !3)).min(self.end);
+        Ok(Some(message))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::err::{GetDeviceError, ListDevicesError, SetDeviceError};
+    use crate::{set, DeviceInterface, RouteSocket, WgSocket};
+    use neli::{
+        consts::{
+            genl::{CtrlAttr, CtrlCmd},
+            nl::{GenlId, NlmF, NlmFFlags},
+            rtnl::{Arphrd, IffFlags, Ifla, IflaInfo, RtAddrFamily, Rtm},
+        },
+        genl::{Genlmsghdr, Nlattr},
+        rtnl::{Ifinfomsg, Rtattr},
+        types::{GenlBuffer, RtBuffer},
+    };
+    use std::collections::VecDeque;
+
+    const FAMILY_ID: u16 = 0x1d;
+
+    /// Replays canned datagrams and records everything that's sent.
+    #[derive(Default)]
+    struct ScriptedTransport {
+        sent: Vec<Vec<u8>>,
+        replies: VecDeque<Vec<u8>>,
+    }
+
+    impl Transport for ScriptedTransport {
+        fn send(&mut self, buf: &[u8]) -> io::Result<()> {
+            self.sent.push(buf.to_vec());
+            Ok(())
+        }
+
+        fn recv(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let reply = self.replies.pop_front().unwrap_or_default();
+            buf[..reply.len()].copy_from_slice(&reply);
+            Ok(reply.len())
+        }
+    }
+
+    fn family_reply() -> Vec<u8> {
+        let mut attrs = GenlBuffer::new();
+        attrs.push(Nlattr::new(false, false, CtrlAttr::FamilyId, FAMILY_ID).unwrap());
+        let message = Nlmsghdr::new(
+            None,
+            GenlId::Ctrl,
+            NlmFFlags::empty(),
+            None,
+            None,
+            NlPayload::Payload(Genlmsghdr::new(CtrlCmd::Newfamily, 2, attrs)),
+        );
+        let mut buffer = Cursor::new(Vec::new());
+        message.to_bytes(&mut buffer).unwrap();
+        buffer.into_inner()
+    }
+
+    fn error_reply(seq: u32, errno: i32) -> Vec<u8> {
+        let mut reply = vec![];
+        reply.extend(&36u32.to_ne_bytes());
+        reply.extend(&u16::from(Nlmsg::Error).to_ne_bytes());
+        reply.extend(&[0u8; 2]);
+        reply.extend(&seq.to_ne_bytes());
+        reply.extend(&[0u8; 4]);
+        reply.extend(&(-errno).to_ne_bytes());
+        reply.extend(&16u32.to_ne_bytes());
+        reply.extend(&FAMILY_ID.to_ne_bytes());
+        reply.extend(&[0u8; 10]);
+        reply
+    }
+
+    #[test]
+    fn wg_socket_resolves_family_over_transport() -> anyhow::Result<()> {
+        let mut transport = ScriptedTransport::default();
+        transport.replies.push_back(family_reply());
+        transport.replies.push_back(error_reply(1, 0));
+
+        let mut wg = WgSocket::from_transport(&mut transport)?;
+        wg.set_device(set::Device::from_ifname("wgtest0").listen_port(1234))?;
+        drop(wg);
+
+        assert_eq!(transport.sent.len(), 2);
+        let nl_type = u16::from_ne_bytes(transport.sent[1][4..6].try_into()?);
+        assert_eq!(nl_type, FAMILY_ID);
+
+        Ok(())
+    }
+
+    #[test]
+    fn wg_socket_decodes_error_replies() -> anyhow::Result<()> {
+        let mut transport = ScriptedTransport::default();
+        transport.replies.push_back(family_reply());
+        transport.replies.push_back(error_reply(1, libc::ENODEV));
+
+        let mut wg = WgSocket::from_transport(&mut transport)?;
+        match wg.set_device(set::Device::from_ifname("wgtest0")) {
+            Err(SetDeviceError::KernelError(err)) => assert_eq!(err.raw_os_error(), libc::ENODEV),
+            result => panic!("Unexpected result {:?}", result),
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn wg_socket_skips_replies_to_other_requests() -> anyhow::Result<()> {
+        let mut transport = ScriptedTransport::default();
+        transport.replies.push_back(family_reply());
+        transport.replies.push_back(error_reply(0, libc::EINVAL));
+        transport.replies.push_back(error_reply(1, 0));
+        transport.replies.push_back(error_reply(2, libc::ENODEV));
+
+        let mut wg = WgSocket::from_transport(&mut transport)?;
+        wg.set_device(set::Device::from_ifname("wgtest0"))?;
+        match wg.set_device(set::Device::from_ifname("wgtest0")) {
+            Err(SetDeviceError::KernelError(err)) => assert_eq!(err.raw_os_error(), libc::ENODEV),
+            result => panic!("Unexpected result {:?}", result),
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn get_device_skips_replies_to_other_requests() -> anyhow::Result<()> {
+        let mut transport = ScriptedTransport::default();
+        transport.replies.push_back(family_reply());
+        let mut replies = error_reply(0, libc::EINVAL);
+        replies.extend(error_reply(1, libc::ENODEV));
+        transport.replies.push_back(replies);
+
+        let mut wg = WgSocket::from_transport(&mut transport)?;
+        match wg.get_device(DeviceInterface::from_name("wgtest0")) {
+            Err(GetDeviceError::KernelError(err)) => assert_eq!(err.raw_os_error(), libc::ENODEV),
+            result => panic!("Unexpected result {:?}", result),
+        }
+        drop(wg);
+
+        let seq = u32::from_ne_bytes(transport.sent[1][8..12].try_into()?);
+        assert_eq!(seq, 1);
+
+        Ok(())
+    }
+
+    /// The reply to the first dump request of a route socket.
+    fn wireguard_link_dump() -> Vec<u8> {
+        let mut link_info = Rtattr::new(None, Ifla::Linkinfo, Buffer::from(Vec::new())).unwrap();
+        link_info
+            .add_nested_attribute(&Rtattr::new(None, IflaInfo::Kind, "wireguard").unwrap())
+            .unwrap();
+        let mut rtattrs = RtBuffer::new();
+        rtattrs.push(Rtattr::new(None, Ifla::Ifname, "wgtest0").unwrap());
+        rtattrs.push(link_info);
+        let infomsg = Ifinfomsg::new(
+            RtAddrFamily::Unspecified,
+            Arphrd::None,
+            3,
+            IffFlags::empty(),
+            IffFlags::empty(),
+            rtattrs,
+        );
+        let message = Nlmsghdr::new(
+            None,
+            Rtm::Newlink,
+            NlmFFlags::new(&[NlmF::Multi]),
+            Some(1),
+            None,
+            NlPayload::Payload(infomsg),
+        );
+        let done = Nlmsghdr::new(
+            None,
+            Nlmsg::Done,
+            NlmFFlags::new(&[NlmF::Multi]),
+            Some(1),
+            None,
+            NlPayload::Payload(0i32),
+        );
+
+        let mut buffer = Cursor::new(Vec::new());
+        message.to_bytes(&mut buffer).unwrap();
+        done.to_bytes(&mut buffer).unwrap();
+        buffer.into_inner()
+    }
+
+    #[test]
+    fn set_device_skips_replies_left_over_from_get_all_devices() -> anyhow::Result<()> {
+        let mut route_transport = ScriptedTransport::default();
+        route_transport.replies.push_back(wireguard_link_dump());
+        let mut route = RouteSocket::from_transport(&mut route_transport);
+
+        let mut transport = ScriptedTransport::default();
+        transport.replies.push_back(family_reply());
+        // The socket closes before the dump is answered, which ends
+        // get_all_devices early.
+        transport.replies.push_back(vec![]);
+        transport.replies.push_back(error_reply(1, libc::EBUSY));
+        transport.replies.push_back(error_reply(2, 0));
+
+        let mut wg = WgSocket::from_transport(&mut transport)?;
+        assert!(wg.get_all_devices(&mut route).is_err());
+        wg.set_device(set::Device::from_ifname("wgtest0").listen_port(1234))?;
+        drop(wg);
+
+        let seqs: Vec<u32> = transport.sent[1..]
+            .iter()
+            .map(|message| u32::from_ne_bytes(message[8..12].try_into().unwrap()))
+            .collect();
+        assert_eq!(seqs, vec![1, 2]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn wg_socket_requires_an_ack() -> anyhow::Result<()> {
+        let mut transport = ScriptedTransport::default();
+        transport.replies.push_back(family_reply());
+
+        let mut wg = WgSocket::from_transport(&mut transport)?;
+        assert!(matches!(
+            wg.set_device(set::Device::from_ifname("wgtest0")),
+            Err(SetDeviceError::NlError(_))
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn read_timeout_round_trips() -> anyhow::Result<()> {
+        let mut transport = NetlinkTransport::connect(NlFamily::Route)?;
+        assert_eq!(transport.read_timeout()?, None);
+
+        // The kernel rounds timeouts to its clock ticks, which whole seconds
+        // are a multiple of.
+        transport.set_read_timeout(Some(Duration::from_secs(2)))?;
+        assert_eq!(transport.read_timeout()?, Some(Duration::from_secs(2)));
+
+        transport.set_read_timeout(None)?;
+        assert_eq!(transport.read_timeout()?, None);
+
+        assert!(transport.set_read_timeout(Some(Duration::ZERO)).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn nonblocking_sockets_fail_with_would_block() -> anyhow::Result<()> {
+        let mut transport = NetlinkTransport::connect(NlFamily::Route)?;
+        transport.set_nonblocking(true)?;
+        let mut sock = NlConnection::new(transport);
+        let err = GetDeviceError::from(sock.recv().unwrap_err());
+        assert!(matches!(err, GetDeviceError::WouldBlock), "{:?}", err);
+
+        Ok(())
+    }
+
+    #[test]
+    fn list_device_names_can_be_polled() -> anyhow::Result<()> {
+        let mut route = RouteSocket::connect()?;
+        route.set_nonblocking(true)?;
+
+        let mut request = route.send_list_device_names()?;
+        let names = loop {
+            match route.poll_list_device_names(&mut request) {
+                Err(ListDevicesError::WouldBlock) => {
+                    let mut fd = libc::pollfd {
+                        fd: route.as_raw_fd(),
+                        events: libc::POLLIN,
+                        revents: 0,
+                    };
+                    assert_eq!(unsafe { libc::poll(&mut fd, 1, 1000) }, 1);
+                }
+                result => break result?,
+            }
+        };
+
+        assert_eq!(names, RouteSocket::connect()?.list_device_names()?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn unanswered_requests_time_out() -> anyhow::Result<()> {
+        let mut transport = NetlinkTransport::connect(NlFamily::Route)?;
+        transport.set_read_timeout(Some(Duration::from_millis(10)))?;
+        let mut sock = NlConnection::new(transport);
+        let err = GetDeviceError::from(sock.recv().unwrap_err());
+        assert!(matches!(err, GetDeviceError::Timeout), "{:?}", err);
+
+        let err = SetDeviceError::from(sock.recv_ack(1).unwrap_err());
+        assert!(matches!(err, SetDeviceError::Timeout), "{:?}", err);
+
+        Ok(())
+    }
+}
@@ -1,111 +1,241 @@
 use crate::get;
-use crate::linux::attr::WgDeviceAttribute;
-use crate::linux::cmd::WgCmd;
-use crate::linux::consts::NLA_NETWORK_ORDER;
-use crate::linux::consts::{WG_GENL_NAME, WG_GENL_VERSION};
-use crate::linux::err::{ConnectError, GetDeviceError, SetDeviceError};
+use crate::linux::consts::WG_GENL_NAME;
+use crate::linux::err::{
+    ConnectError, GetAllDevicesError, GetDeviceError, GetDeviceReportError, SetDeviceError,
+    SetDeviceFragmentError,
+};
 use crate::linux::set;
-use crate::linux::set::create_set_device_messages;
-use crate::linux::socket::parse::*;
-use crate::linux::socket::NlWgMsgType;
+use crate::linux::set::{clamp_message_size, create_set_device_messages, SetDeviceMessage};
+use crate::linux::socket::ext_ack::{decode_nl_error, parse_nlmsgerr};
+use crate::linux::socket::genl_family_utils::{
+    family_not_found, get_family_msg, parse_family_id, ResolveFamilyError,
+};
+use crate::linux::socket::get_device_utils::{extend_device_with_payload, get_device_msg};
+use crate::linux::socket::transport::{NetlinkTransport, NlConnection, Transport};
+use crate::linux::socket::{NlWgMsgType, PeerIter, RouteSocket};
+use crate::linux::stats::DeviceReport;
 use crate::linux::DeviceInterface;
-use libc::IFNAMSIZ;
 use neli::{
-    consts::{
-        nl::{NlmF, NlmFFlags, Nlmsg},
-        socket::NlFamily,
-    },
-    genl::{Genlmsghdr, Nlattr},
-    nl::{NlPayload, Nlmsghdr},
-    socket::NlSocketHandle,
-    types::GenlBuffer,
+    consts::{nl::Nlmsg, socket::NlFamily},
+    err::NlError,
+    nl::NlPayload,
 };
-use std::convert::TryFrom;
+use std::io;
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::time::Duration;
+
+/// The devices read by [`WgSocket::get_all_devices`], each next to its name.
+pub type DeviceResults = Vec<(String, Result<get::Device, GetDeviceError>)>;
+
+/// A device request whose reply is still being received. Created by
+/// [`WgSocket::send_get_device`] and completed by
+/// [`WgSocket::poll_get_device`].
+#[derive(Debug)]
+pub struct DeviceRequest {
+    seq: u32,
+    /// The device as far as it has been received. Parse errors are kept until
+    /// the rest of the dump has been read.
+    device: Result<Option<get::Device>, GetDeviceError>,
+}
 
-pub struct WgSocket {
-    sock: NlSocketHandle,
+pub struct WgSocket<T = NetlinkTransport> {
+    sock: NlConnection<T>,
     family_id: NlWgMsgType,
+    max_message_size: usize,
 }
 
 impl WgSocket {
     pub fn connect() -> Result<Self, ConnectError> {
-        let family_id = {
-            NlSocketHandle::new(NlFamily::Generic)?
-                .resolve_genl_family(WG_GENL_NAME)
-                .map_err(ConnectError::ResolveFamilyError)?
-        };
+        Self::from_transport(NetlinkTransport::connect(NlFamily::Generic)?)
+    }
+
+    /// See [`NetlinkTransport::read_timeout`].
+    pub fn read_timeout(&self) -> io::Result<Option<Duration>> {
+        self.sock.transport().read_timeout()
+    }
+
+    /// Limits how long a request waits for the kernel's reply. Requests that
+    /// run out of time fail with a `Timeout` error, such as
+    /// [`GetDeviceError::Timeout`]. See [timeouts](crate::err#timeouts).
+    pub fn set_read_timeout(&mut self, timeout: Option<Duration>) -> io::Result<()> {
+        self.sock.transport_mut().set_read_timeout(timeout)
+    }
+
+    /// Switches the socket between blocking and non-blocking mode. Use
+    /// [`send_get_device`](Self::send_get_device) and
+    /// [`poll_get_device`](Self::poll_get_device) to read devices from a poll
+    /// or epoll loop. See [non-blocking mode](crate::err#non-blocking-mode).
+    pub fn set_nonblocking(&mut self, nonblocking: bool) -> io::Result<()> {
+        self.sock.transport_mut().set_nonblocking(nonblocking)
+    }
+}
+
+impl<T: Transport + AsRawFd> AsRawFd for WgSocket<T> {
+    fn as_raw_fd(&self) -> RawFd {
+        self.sock.transport().as_raw_fd()
+    }
+}
 
-        // Autoselect a PID
-        let pid = None;
-        let groups = &[];
-        let wgsock = NlSocketHandle::connect(NlFamily::Generic, pid, groups)?;
+impl<T: Transport> WgSocket<T> {
+    /// Creates a socket that exchanges `NETLINK_GENERIC` messages over
+    /// `transport` instead of a socket connected to the kernel. The id of the
+    /// WireGuard family is resolved over the transport as well.
+    pub fn from_transport(transport: T) -> Result<Self, ConnectError> {
+        let mut sock = NlConnection::new(transport);
+        let family_id = resolve_genl_family(&mut sock, WG_GENL_NAME)
+            .map_err(ConnectError::ResolveFamilyError)?;
+        let max_message_size = clamp_message_size(sock.max_message_size());
 
         Ok(Self {
-            sock: wgsock,
+            sock,
             family_id,
+            max_message_size,
         })
     }
 
+    /// The size of the largest message [`set_device`](Self::set_device)
+    /// sends. Defaults to what the transport accepts, which for a socket
+    /// connected to the kernel is derived from its send buffer size.
+    pub fn max_message_size(&self) -> usize {
+        self.max_message_size
+    }
+
+    /// Changes the size of the largest message
+    /// [`set_device`](Self::set_device) sends. Devices that don't fit are
+    /// split across several messages. Sizes are clamped to between 512 bytes,
+    /// which still fits a peer with all of its attributes, and 64 KiB.
+    pub fn set_max_message_size(&mut self, max_message_size: usize) {
+        self.max_message_size = clamp_message_size(max_message_size);
+    }
+
     pub fn get_device(
         &mut self,
         interface: DeviceInterface,
     ) -> Result<get::Device, GetDeviceError> {
-        let attr = match interface {
-            DeviceInterface::Name(name) => {
-                Some(name.len())
-                    .filter(|&len| 0 < len && len < IFNAMSIZ)
-                    .ok_or(GetDeviceError::InvalidInterfaceName)?;
-                Nlattr::new(
-                    false,
-                    NLA_NETWORK_ORDER,
-                    WgDeviceAttribute::Ifname,
-                    name.as_ref(),
-                )?
+        let mut request = self.send_get_device(interface)?;
+        self.poll_get_device(&mut request)
+    }
+
+    /// Sends the request [`get_device`](Self::get_device) makes without
+    /// waiting for the reply. Receive it with
+    /// [`poll_get_device`](Self::poll_get_device).
+    ///
+    /// The kernel runs only one dump per socket at a time, so a device
+    /// request has to be completed before the next one is sent.
+    pub fn send_get_device(
+        &mut self,
+        interface: DeviceInterface,
+    ) -> Result<DeviceRequest, GetDeviceError> {
+        let seq = self.send_get_device_msg(interface)?;
+        Ok(DeviceRequest {
+            seq,
+            device: Ok(None),
+        })
+    }
+
+    /// Receives as much of the reply to `request` as is queued on the socket.
+    /// In non-blocking mode, this fails with [`GetDeviceError::WouldBlock`]
+    /// until the reply is complete. Poll again once the socket is readable.
+    ///
+    /// Any other result completes the request.
+    pub fn poll_get_device(
+        &mut self,
+        request: &mut DeviceRequest,
+    ) -> Result<get::Device, GetDeviceError> {
+        let capped_acks = self.sock.capped_acks();
+
+        // The messages of the dump are parsed right in the receive buffer.
+        loop {
+            let response = match self
+                .sock
+                .recv_reply(request.seq)
+                .map_err(|err| decode_nl_error::<GetDeviceError>(err, capped_acks))?
+            {
+                Some(response) => response,
+                None => {
+                    return Err(NlError::msg(
+                        "The netlink socket closed before the device was received",
+                    )
+                    .into())
+                }
+            };
+            if response.nl_type == Nlmsg::Done.into() {
+                break;
             }
-            DeviceInterface::Index(index) => {
-                Nlattr::new(false, NLA_NETWORK_ORDER, WgDeviceAttribute::Ifindex, index)?
+
+            // A message that fails to parse doesn't end the read early, since
+            // the next dump on the socket would fail with EBUSY if this one
+            // was left unfinished.
+            let device = std::mem::replace(&mut request.device, Ok(None));
+            request.device = device
+                .and_then(|device| extend_device_with_payload(device, response.payload).map(Some));
+            if response.is_last() {
+                break;
             }
-        };
-        let genlhdr = {
-            let cmd = WgCmd::GetDevice;
-            let version = WG_GENL_VERSION;
-            let mut attrs = GenlBuffer::new();
-
-            attrs.push(attr);
-            Genlmsghdr::new(cmd, version, attrs)
-        };
-        let nlhdr = {
-            let size = None;
-            let nl_type = self.family_id;
-            let flags = NlmFFlags::new(&[NlmF::Request, NlmF::Ack, NlmF::Dump]);
-            let seq = None;
-            let pid = None;
-            let payload = NlPayload::Payload(genlhdr);
-            Nlmsghdr::new(size, nl_type, flags, seq, pid, payload)
-        };
+        }
 
-        self.sock.send(nlhdr)?;
+        std::mem::replace(&mut request.device, Ok(None))?.ok_or(GetDeviceError::AccessError)
+    }
 
-        let mut iter = self
-            .sock
-            .iter::<Nlmsg, Genlmsghdr<WgCmd, WgDeviceAttribute>>(false);
+    /// Like [`get_device`](Self::get_device), but yields peers one at a time
+    /// as they're received instead of collecting all of them first. This
+    /// keeps memory usage flat for devices with a very large number of peers.
+    pub fn get_device_peers(
+        &mut self,
+        interface: DeviceInterface,
+    ) -> Result<PeerIter<'_, T>, GetDeviceError> {
+        let seq = self.send_get_device_msg(interface)?;
+        PeerIter::new(&mut self.sock, seq)
+    }
 
-        let mut device = None;
-        while let Some(Ok(response)) = iter.next() {
-            match response.nl_type {
-                Nlmsg::Error => return Err(GetDeviceError::AccessError),
-                Nlmsg::Done => break,
-                _ => (),
-            };
+    /// Retrieves every WireGuard device. Device names are listed through
+    /// `route`, after which the devices are read one after another on this
+    /// socket. The kernel runs only one dump per socket at a time, so each
+    /// device's dump is read to its end before the next one is requested.
+    ///
+    /// Errors that only concern a single device are returned next to its name
+    /// without failing the whole call. Devices that are deleted between being
+    /// listed and being read are left out.
+    pub fn get_all_devices<R: Transport>(
+        &mut self,
+        route: &mut RouteSocket<R>,
+    ) -> Result<DeviceResults, GetAllDevicesError> {
+        let names = route.list_device_names()?;
 
-            let handle = response.get_payload()?.get_attr_handle();
-            device = Some(match device {
-                Some(device) => extend_device(device, handle)?,
-                None => get::Device::try_from(handle)?,
-            });
+        let mut devices = Vec::with_capacity(names.len());
+        for name in names {
+            match self.get_device(DeviceInterface::from_name(&name)) {
+                // The device was deleted after it was listed.
+                Err(GetDeviceError::KernelError(err)) if err.raw_os_error() == libc::ENODEV => {}
+                Err(err) if fails_all_devices(&err) => return Err(err.into()),
+                result => devices.push((name, result)),
+            }
         }
 
-        device.ok_or(GetDeviceError::AccessError)
+        Ok(devices)
+    }
+
+    /// Sends a WG_CMD_GET_DEVICE request and returns its sequence number.
+    fn send_get_device_msg(&mut self, interface: DeviceInterface) -> Result<u32, GetDeviceError> {
+        let mut nlhdr = get_device_msg(interface, self.family_id)?;
+        let seq = self.sock.next_seq();
+        nlhdr.nl_seq = seq;
+        self.sock.send(nlhdr)?;
+        Ok(seq)
+    }
+
+    /// Retrieves a device along with the interface statistics of its network
+    /// interface. The statistics are looked up by the ifindex of the returned
+    /// device, so both halves of the report describe the same interface even
+    /// if it's renamed in between.
+    pub fn get_device_report<R: Transport>(
+        &mut self,
+        route: &mut RouteSocket<R>,
+        interface: DeviceInterface,
+    ) -> Result<DeviceReport, GetDeviceReportError> {
+        let device = self.get_device(interface)?;
+        let stats = route.get_link_stats(DeviceInterface::from_index(device.ifindex))?;
+        Ok(DeviceReport { device, stats })
     }
 
     /// This assumes that the device interface has already been created. Otherwise an error will
@@ -120,12 +250,78 @@ impl WgSocket {
     /// ```sh
     ///  sudo ip -4 route add 127.3.1.1/32 dev wgtest0
     /// ```
+    ///
+    /// Devices too large for a single message are sent in several, each of
+    /// which is acknowledged before the next one is sent. If the kernel
+    /// rejects one of them, [`SetDeviceError::FragmentRejected`] says which
+    /// message it was and which peers it carried.
     pub fn set_device(&mut self, device: set::Device) -> Result<(), SetDeviceError> {
-        for nl_message in create_set_device_messages(device, self.family_id)? {
+        let capped_acks = self.sock.capped_acks();
+        let fragments = create_set_device_messages(device, self.family_id, self.max_message_size)?;
+        let count = fragments.len();
+        for (index, fragment) in fragments.into_iter().enumerate() {
+            let SetDeviceMessage {
+                message: mut nl_message,
+                public_keys,
+            } = fragment;
+            let seq = self.sock.next_seq();
+            nl_message.nl_seq = seq;
+
             self.sock.send(nl_message)?;
-            self.sock.recv()?;
+            self.sock
+                .recv_ack(seq)
+                .map_err(|err| decode_nl_error::<SetDeviceError>(err, capped_acks))
+                .map_err(|err| reject_fragment(err, index, count, public_keys))?;
         }
 
         Ok(())
     }
 }
+
+/// Whether an error reading a single device in get_all_devices comes from the
+/// socket itself, which affects every device after it as well.
+pub(crate) fn fails_all_devices(err: &GetDeviceError) -> bool {
+    matches!(
+        err,
+        GetDeviceError::NlError(_) | GetDeviceError::Timeout | GetDeviceError::WouldBlock
+    )
+}
+
+/// Adds the position and peers of a rejected message to the kernel's error,
+/// unless the request fit into a single message.
+pub(crate) fn reject_fragment(
+    err: SetDeviceError,
+    index: usize,
+    count: usize,
+    public_keys: Vec<[u8; 32]>,
+) -> SetDeviceError {
+    match err {
+        SetDeviceError::KernelError(err) if count > 1 => {
+            SetDeviceFragmentError::new(index, count, public_keys, err).into()
+        }
+        err => err,
+    }
+}
+
+/// The equivalent of `NlSocketHandle::resolve_genl_family` for any transport.
+fn resolve_genl_family<T: Transport>(
+    sock: &mut NlConnection<T>,
+    family_name: &str,
+) -> Result<NlWgMsgType, ResolveFamilyError> {
+    sock.send(get_family_msg(family_name)?)
+        .map_err(|err| NlError::new(err.to_string()))?;
+
+    match sock.recv() {
+        Ok(Some(message)) => match message.nl_payload {
+            NlPayload::Payload(payload) => {
+                parse_family_id(&payload)?.ok_or_else(|| family_not_found(family_name))
+            }
+            _ => Err(family_not_found(family_name)),
+        },
+        Ok(None) => Err(family_not_found(family_name)),
+        Err(NlError::Nlmsgerr(err)) => Err(NlError::new(
+            parse_nlmsgerr(&err, sock.capped_acks()).to_string(),
+        )),
+        Err(err) => Err(NlError::new(err.to_string())),
+    }
+}
@@ -0,0 +1,66 @@
+use crate::get;
+
+/// Interface-level counters reported by the kernel through `IFLA_STATS64`.
+///
+/// The layout follows `struct rtnl_link_stats64` from `linux/if_link.h`.
+/// Older kernels report fewer fields than newer ones. Any counter the kernel
+/// didn't report is left at 0.
+///
+/// https://github.com/torvalds/linux/blob/v5.15/include/uapi/linux/if_link.h#L215
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct LinkStats {
+    pub rx_packets: u64,
+    pub tx_packets: u64,
+    pub rx_bytes: u64,
+    pub tx_bytes: u64,
+    pub rx_errors: u64,
+    pub tx_errors: u64,
+    pub rx_dropped: u64,
+    pub tx_dropped: u64,
+    pub multicast: u64,
+    pub collisions: u64,
+
+    // Detailed rx_errors
+    pub rx_length_errors: u64,
+    pub rx_over_errors: u64,
+    pub rx_crc_errors: u64,
+    pub rx_frame_errors: u64,
+    pub rx_fifo_errors: u64,
+    pub rx_missed_errors: u64,
+
+    // Detailed tx_errors
+    pub tx_aborted_errors: u64,
+    pub tx_carrier_errors: u64,
+    pub tx_fifo_errors: u64,
+    pub tx_heartbeat_errors: u64,
+    pub tx_window_errors: u64,
+
+    // For cslip etc
+    pub rx_compressed: u64,
+    pub tx_compressed: u64,
+
+    pub rx_nohandler: u64,
+}
+
+/// A WireGuard device combined with the statistics of its network interface.
+///
+/// The per-peer counters in [`get::Peer`] only count bytes that made it
+/// through the tunnel. Packets dropped because of MTU problems or decryption
+/// failures only show up in the interface statistics.
+#[derive(Debug, PartialEq, Eq)]
+pub struct DeviceReport {
+    pub device: get::Device,
+    pub stats: LinkStats,
+}
+
+impl DeviceReport {
+    /// Sum of [`get::Peer::rx_bytes`] across all peers of the device.
+    pub fn peer_rx_bytes(&self) -> u64 {
+        self.device.peers.iter().map(|peer| peer.rx_bytes).sum()
+    }
+
+    /// Sum of [`get::Peer::tx_bytes`] across all peers of the device.
+    pub fn peer_tx_bytes(&self) -> u64 {
+        self.device.peers.iter().map(|peer| peer.tx_bytes).sum()
+    }
+}
@@ -0,0 +1,245 @@
+use super::{check_set_end_of_response, check_set_errno_line, GET_CMD, SET_CMD};
+use crate::get;
+use crate::xplatform::error::GetDeviceError;
+use crate::xplatform::error::SetDeviceError;
+use crate::xplatform::parser::{
+    finish, finish_lenient, initial_state, is_end_of_response_lenient, process_line,
+    process_line_lenient, Extensions, LenientGetResponse,
+};
+use crate::xplatform::set;
+use std::path::Path;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader, BufWriter};
+use tokio::net::UnixStream;
+
+/// The async version of [`Client`](super::Client), built on tokio's
+/// [`UnixStream`]. Each request opens a new connection, so one task can poll
+/// several devices concurrently.
+pub struct AsyncClient<P: AsRef<Path>> {
+    path: P,
+}
+
+impl<P: AsRef<Path>> AsyncClient<P> {
+    /// A path to the unix socket file. Ex: `/var/run/wireguard/utun0.sock`
+    pub fn create(path: P) -> Self {
+        Self { path }
+    }
+
+    pub async fn get(&self) -> Result<get::Device, GetDeviceError> {
+        let mut stream = UnixStream::connect(&self.path).await?;
+
+        stream.write_all(GET_CMD.as_bytes()).await?;
+
+        let mut response_lines = BufReader::new(stream).lines();
+
+        // Feed lines to the parser as they arrive. WireGuard xplatform
+        // implementations signify the end of a response with an empty line, so
+        // stop reading there instead of waiting for the socket to close.
+        let mut state = initial_state();
+        while let Some(line) = response_lines.next_line().await.transpose() {
+            let is_end_of_response = matches!(line.as_deref(), Ok(""));
+            state = process_line(state, line)?;
+            if is_end_of_response {
+                break;
+            }
+        }
+
+        Ok(finish(state)?)
+    }
+
+    /// See [`Client::get_lenient`](super::Client::get_lenient).
+    pub async fn get_lenient(&self) -> Result<LenientGetResponse, GetDeviceError> {
+        let mut stream = UnixStream::connect(&self.path).await?;
+
+        stream.write_all(GET_CMD.as_bytes()).await?;
+
+        let mut response_lines = BufReader::new(stream).lines();
+
+        let mut state = initial_state();
+        let mut extensions = Extensions::default();
+        while let Some(line) = response_lines.next_line().await.transpose() {
+            let is_end_of_response = is_end_of_response_lenient(&line);
+            (state, extensions) = process_line_lenient(state, extensions, line)?;
+            if is_end_of_response {
+                break;
+            }
+        }
+
+        Ok(finish_lenient(state, extensions)?)
+    }
+
+    pub async fn set(&self, set_request: set::Device) -> Result<(), SetDeviceError> {
+        self.set_peers(set_request, std::iter::empty()).await
+    }
+
+    /// Like [`set`](Self::set), but also sends the peers produced by `peers`
+    /// after the ones in `set_request`. The request is written through a
+    /// buffer as it's serialized, so it never has to fit in memory at once.
+    pub async fn set_peers<I>(
+        &self,
+        set_request: set::Device,
+        peers: I,
+    ) -> Result<(), SetDeviceError>
+    where
+        I: IntoIterator<Item = set::Peer>,
+    {
+        let stream = UnixStream::connect(&self.path).await?;
+
+        // Allowed IPs are written one at a time like the rest of the request,
+        // so a peer with many of them is never serialized in one piece.
+        let mut writer = BufWriter::new(stream);
+        writer.write_all(SET_CMD.as_bytes()).await?;
+        let interface_keys = set_request.interface_keys().to_string();
+        writer.write_all(interface_keys.as_bytes()).await?;
+        for peer in set_request.peers.into_iter().chain(peers) {
+            let peer_keys = peer.peer_keys().to_string();
+            writer.write_all(peer_keys.as_bytes()).await?;
+            for allowed_ip in &peer.allowed_ips {
+                writer.write_all(allowed_ip.to_string().as_bytes()).await?;
+            }
+        }
+        writer.write_all(b"\n").await?;
+        writer.flush().await?;
+
+        let mut response_lines = BufReader::new(writer.into_inner()).lines();
+
+        let errno_line = response_lines
+            .next_line()
+            .await?
+            .ok_or(SetDeviceError::EmptyResponse)?;
+        check_set_errno_line(errno_line)?;
+
+        let empty_line = response_lines
+            .next_line()
+            .await?
+            .ok_or(SetDeviceError::EmptyResponse)?;
+        check_set_end_of_response(empty_line)?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::AsyncClient;
+    use crate::xplatform::error::{ServerError, SetDeviceError};
+    use crate::xplatform::set;
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+    use tokio::net::UnixListener;
+
+    /// Answers a single request with `response` and then keeps the connection
+    /// open, like a real implementation would.
+    async fn serve_once(listener: UnixListener, response: &'static str) -> Vec<String> {
+        let (stream, _) = listener.accept().await.unwrap();
+        let (reader, mut writer) = stream.into_split();
+        let mut lines = BufReader::new(reader).lines();
+
+        let mut request = vec![];
+        while let Some(line) = lines.next_line().await.unwrap() {
+            let is_end_of_request = line.is_empty();
+            request.push(line);
+            if is_end_of_request {
+                break;
+            }
+        }
+
+        writer.write_all(response.as_bytes()).await.unwrap();
+        let _ = lines.next_line().await;
+        request
+    }
+
+    #[tokio::test]
+    async fn get_stops_reading_at_end_of_response() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("wgtest0.sock");
+        let listener = UnixListener::bind(&path)?;
+        let server = tokio::spawn(serve_once(listener, "listen_port=51820\nerrno=0\n\n"));
+
+        let device = AsyncClient::create(&path).get().await?;
+        assert_eq!(device.listen_port, 51820);
+        assert!(device.peers.is_empty());
+
+        server.abort();
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn get_lenient_collects_unknown_keys() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("wgtest0.sock");
+        let listener = UnixListener::bind(&path)?;
+        let response = "listen_port=51820\njc=4\nerrno=0\n\n";
+        let server = tokio::spawn(serve_once(listener, response));
+
+        let response = AsyncClient::create(&path).get_lenient().await?;
+        assert_eq!(response.device.listen_port, 51820);
+        assert_eq!(response.extensions.interface["jc"], vec!["4"]);
+
+        server.abort();
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn set_reports_server_errno() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("wgtest0.sock");
+        let listener = UnixListener::bind(&path)?;
+        // wireguard-go negates errno values.
+        let server = tokio::spawn(serve_once(listener, "errno=-22\n\n"));
+
+        let request = set::Device {
+            listen_port: Some(51820),
+            ..Default::default()
+        };
+        let err = AsyncClient::create(&path).set(request).await.unwrap_err();
+        match err {
+            SetDeviceError::ServerError(err) => {
+                assert_eq!(err, ServerError::InvalidRequest);
+                assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+            }
+            err => panic!("Unexpected error: {}", err),
+        }
+
+        server.abort();
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn set_peers_writes_peers_after_the_request() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("wgtest0.sock");
+        let listener = UnixListener::bind(&path)?;
+        let server = tokio::spawn(serve_once(listener, "errno=0\n\n"));
+
+        let request = set::Device {
+            fwmark: Some(0),
+            peers: vec![set::Peer::from_public_key([1u8; 32])],
+            ..Default::default()
+        };
+        let allowed_ips: Vec<_> = (0..2)
+            .map(|byte| set::AllowedIp {
+                ipaddr: [10, 0, 0, byte].into(),
+                cidr_mask: 32,
+            })
+            .collect();
+        let peers = (2..4).map(|byte| set::Peer::from_public_key([byte; 32]));
+        let peers = peers.map(|peer| peer.allowed_ips(allowed_ips.clone()));
+        AsyncClient::create(&path).set_peers(request, peers).await?;
+
+        let public_key = |byte: u8| format!("public_key={}", hex::encode([byte; 32]));
+        let expected = vec![
+            "set=1".to_string(),
+            "fwmark=0".to_string(),
+            public_key(1),
+            public_key(2),
+            "allowed_ip=10.0.0.0/32".to_string(),
+            "allowed_ip=10.0.0.1/32".to_string(),
+            public_key(3),
+            "allowed_ip=10.0.0.0/32".to_string(),
+            "allowed_ip=10.0.0.1/32".to_string(),
+            "".to_string(),
+        ];
+        assert_eq!(server.await?, expected);
+
+        Ok(())
+    }
+}
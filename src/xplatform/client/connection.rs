@@ -0,0 +1,219 @@
+use super::unix::{get_device, set_device};
+use crate::get;
+use crate::xplatform::error::GetDeviceError;
+use crate::xplatform::error::SetDeviceError;
+use crate::xplatform::parser::{parse, parse_lenient, LenientGetResponse};
+use crate::xplatform::set;
+use std::io::{self, BufReader};
+use std::os::unix::io::AsRawFd;
+use std::os::unix::net::UnixStream;
+use std::path::Path;
+
+/// Runs any number of operations on one connection to the socket, instead of
+/// opening a new connection for each of them like [`Client`](super::Client)
+/// does. This saves a round trip to the implementation per operation when
+/// polling a device frequently.
+///
+/// The socket is connected to on the first operation. If the implementation
+/// has closed the connection since the previous operation, for example
+/// because it restarted, a new connection is opened before the operation is
+/// sent. An operation that fails for any reason closes the connection, since
+/// the rest of its response may still be waiting to be read. The next
+/// operation then reconnects.
+///
+/// Operations are never retried. A connection that's closed while an
+/// operation is in flight results in an error for that operation.
+#[derive(Debug)]
+pub struct Connection<P: AsRef<Path>> {
+    path: P,
+    stream: Option<BufReader<UnixStream>>,
+}
+
+impl<P: AsRef<Path>> Connection<P> {
+    /// A path to the unix socket file. Ex: `/var/run/wireguard/utun0.sock`
+    pub fn new(path: P) -> Self {
+        Self { path, stream: None }
+    }
+
+    pub fn path(&self) -> &Path {
+        self.path.as_ref()
+    }
+
+    /// Whether a connection is currently open. It may have been closed by the
+    /// implementation since the last operation.
+    pub fn is_connected(&self) -> bool {
+        self.stream.is_some()
+    }
+
+    /// Closes the current connection, if any. The next operation opens a new
+    /// one.
+    pub fn disconnect(&mut self) {
+        self.stream = None;
+    }
+
+    pub fn get(&mut self) -> Result<get::Device, GetDeviceError> {
+        let result = get_device(self.stream()?, parse);
+        self.close_on_error(result)
+    }
+
+    /// See [`Client::get_lenient`](super::Client::get_lenient).
+    pub fn get_lenient(&mut self) -> Result<LenientGetResponse, GetDeviceError> {
+        let result = get_device(self.stream()?, parse_lenient);
+        self.close_on_error(result)
+    }
+
+    pub fn set(&mut self, set_request: set::Device) -> Result<(), SetDeviceError> {
+        self.set_peers(set_request, std::iter::empty())
+    }
+
+    /// See [`Client::set_peers`](super::Client::set_peers).
+    pub fn set_peers<I>(&mut self, set_request: set::Device, peers: I) -> Result<(), SetDeviceError>
+    where
+        I: IntoIterator<Item = set::Peer>,
+    {
+        let result = set_device(self.stream()?, set_request, peers);
+        self.close_on_error(result)
+    }
+
+    /// The open connection, after replacing it if the implementation closed
+    /// it.
+    fn stream(&mut self) -> io::Result<&mut BufReader<UnixStream>> {
+        if let Some(stream) = &self.stream {
+            if !is_open(stream.get_ref()) {
+                self.stream = None;
+            }
+        }
+
+        match &mut self.stream {
+            Some(stream) => Ok(stream),
+            stream @ None => {
+                let new_stream = UnixStream::connect(&self.path)?;
+                Ok(stream.insert(BufReader::new(new_stream)))
+            }
+        }
+    }
+
+    fn close_on_error<T, E>(&mut self, result: Result<T, E>) -> Result<T, E> {
+        if result.is_err() {
+            self.stream = None;
+        }
+        result
+    }
+}
+
+/// Checks whether a connection between operations can still be used. The
+/// implementation shouldn't send anything unprompted, so pending data means
+/// the previous response wasn't fully read and the connection is unusable.
+fn is_open(stream: &UnixStream) -> bool {
+    let mut buf = [0u8; 1];
+    // Peek without blocking. Closed connections read as end of file.
+    let received = unsafe {
+        libc::recv(
+            stream.as_raw_fd(),
+            buf.as_mut_ptr() as *mut libc::c_void,
+            buf.len(),
+            libc::MSG_PEEK | libc::MSG_DONTWAIT,
+        )
+    };
+
+    received == -1 && io::Error::last_os_error().kind() == io::ErrorKind::WouldBlock
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Connection;
+    use crate::xplatform::fake::FakeDevice;
+    use crate::xplatform::server::Server;
+    use crate::xplatform::set;
+    use std::io::{BufRead, BufReader, Write};
+    use std::os::unix::net::UnixListener;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{mpsc, Arc};
+
+    #[test]
+    fn operations_share_a_connection() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("wgtest0.sock");
+        let server = Server::bind(&path, FakeDevice::new())?;
+        let accepted = Arc::new(AtomicUsize::new(0));
+        let server_accepted = accepted.clone();
+        std::thread::spawn(move || loop {
+            let (stream, _) = server.listener().accept().unwrap();
+            server_accepted.fetch_add(1, Ordering::SeqCst);
+            server.handle_connection(stream).unwrap();
+        });
+
+        let mut connection = Connection::new(&path);
+        assert!(!connection.is_connected());
+        assert_eq!(connection.get()?.listen_port, 0);
+        connection.set(set::Device {
+            listen_port: Some(51821),
+            ..Default::default()
+        })?;
+        assert_eq!(connection.get()?.listen_port, 51821);
+        assert!(connection.is_connected());
+        assert_eq!(accepted.load(Ordering::SeqCst), 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn get_lenient_reads_responses_with_unknown_keys() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("wgtest0.sock");
+        let listener = UnixListener::bind(&path)?;
+
+        // Answers every get on a single connection with an unknown key.
+        std::thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(&stream);
+            let mut line = String::new();
+            while reader.read_line(&mut line).unwrap() > 0 {
+                if line == "\n" {
+                    write!(&stream, "listen_port=51820\njc=4\nerrno=0\n\n").unwrap();
+                }
+                line.clear();
+            }
+        });
+
+        let mut connection = Connection::new(&path);
+        for _ in 0..2 {
+            let response = connection.get_lenient()?;
+            assert_eq!(response.device.listen_port, 51820);
+            assert_eq!(response.extensions.interface["jc"], vec!["4"]);
+        }
+        assert!(connection.is_connected());
+
+        Ok(())
+    }
+
+    #[test]
+    fn reconnects_after_the_server_closes_the_connection() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("wgtest0.sock");
+        let listener = UnixListener::bind(&path)?;
+        let (closed_tx, closed_rx) = mpsc::channel();
+
+        // Answers a single get on each connection and then closes it.
+        std::thread::spawn(move || {
+            for listen_port in 1..=2 {
+                let (stream, _) = listener.accept().unwrap();
+                let mut reader = BufReader::new(&stream);
+                let mut request = String::new();
+                while request != "get=1\n\n" {
+                    reader.read_line(&mut request).unwrap();
+                }
+                write!(&stream, "listen_port={}\nerrno=0\n\n", listen_port).unwrap();
+                drop(stream);
+                let _ = closed_tx.send(());
+            }
+        });
+
+        let mut connection = Connection::new(&path);
+        assert_eq!(connection.get()?.listen_port, 1);
+        closed_rx.recv()?;
+        assert_eq!(connection.get()?.listen_port, 2);
+
+        Ok(())
+    }
+}
@@ -1,5 +1,59 @@
 #[cfg(unix)]
 pub mod unix;
 
+#[cfg(unix)]
+mod connection;
+
+#[cfg(unix)]
+pub use connection::Connection;
 #[cfg(unix)]
 pub use unix::Client;
+
+#[cfg(all(unix, feature = "tokio"))]
+pub mod async_unix;
+
+#[cfg(all(unix, feature = "tokio"))]
+pub use async_unix::AsyncClient;
+
+#[cfg(unix)]
+use crate::xplatform::error::{ServerError, SetDeviceError};
+
+#[cfg(unix)]
+const GET_CMD: &str = "get=1\n\n";
+#[cfg(unix)]
+const SET_CMD: &str = "set=1\n";
+
+/// Checks the first line of a set response. For protocol_version=1 this is
+/// expected to be a single "errno=N" line.
+#[cfg(unix)]
+fn check_set_errno_line(errno_line: String) -> Result<(), SetDeviceError> {
+    let (raw_key, raw_value) = {
+        let mut tokens = errno_line.trim().splitn(2, '=');
+        let raw_key = tokens.next().unwrap();
+        let raw_value = match tokens.next() {
+            Some(val) => val,
+            None => return Err(SetDeviceError::InvalidResponse(errno_line)),
+        };
+
+        (raw_key, raw_value)
+    };
+
+    match (raw_key, raw_value) {
+        ("errno", "0") => Ok(()),
+        ("errno", val) => match val.parse() {
+            Ok(errno) if errno != 0 => Err(ServerError::from_errno(errno).into()),
+            _ => Err(SetDeviceError::InvalidResponse(errno_line)),
+        },
+        (_, _) => Err(SetDeviceError::InvalidResponse(errno_line)),
+    }
+}
+
+/// Checks the empty line that follows the errno line of a set response.
+#[cfg(unix)]
+fn check_set_end_of_response(empty_line: String) -> Result<(), SetDeviceError> {
+    if !empty_line.is_empty() {
+        return Err(SetDeviceError::InvalidEndOfResponse(empty_line));
+    }
+
+    Ok(())
+}
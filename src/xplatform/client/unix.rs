@@ -1,16 +1,18 @@
+use super::{check_set_end_of_response, check_set_errno_line, GET_CMD, SET_CMD};
 use crate::get;
 use crate::xplatform::error::GetDeviceError;
 use crate::xplatform::error::SetDeviceError;
-use crate::xplatform::parser::parse;
+use crate::xplatform::parser::{parse, parse_lenient, LenientGetResponse, ParseGetResponseError};
 use crate::xplatform::set;
 use std::io::BufRead;
+use std::io::BufReader;
+use std::io::BufWriter;
+use std::io::Lines;
 use std::io::Write;
 use std::os::unix::net::UnixStream;
 use std::path::Path;
 
-const GET_CMD: &str = "get=1\n\n";
-const SET_CMD: &str = "set=1\n";
-
+#[derive(Debug)]
 pub struct Client<P: AsRef<Path>> {
     path: P,
 }
@@ -22,57 +24,135 @@ impl<P: AsRef<Path>> Client<P> {
     }
 
     pub fn get(&self) -> Result<get::Device, GetDeviceError> {
-        let mut stream = UnixStream::connect(&self.path)?;
+        let stream = UnixStream::connect(&self.path)?;
+        get_device(&mut BufReader::new(stream), parse)
+    }
 
-        stream.write_all(GET_CMD.as_bytes())?;
+    /// Like [`get`](Self::get), but parses the response with
+    /// [`parse_lenient`], so keys from newer implementations are returned as
+    /// extensions instead of failing the operation.
+    pub fn get_lenient(&self) -> Result<LenientGetResponse, GetDeviceError> {
+        let stream = UnixStream::connect(&self.path)?;
+        get_device(&mut BufReader::new(stream), parse_lenient)
+    }
 
-        let reader = std::io::BufReader::new(stream);
-        let response_lines = reader.lines();
+    pub fn set(&self, set_request: set::Device) -> Result<(), SetDeviceError> {
+        self.set_peers(set_request, std::iter::empty())
+    }
 
-        Ok(parse(response_lines)?)
+    /// Like [`set`](Self::set), but also sends the peers produced by `peers`
+    /// after the ones in `set_request`. The request is written to the socket
+    /// as it's serialized and each peer is dropped once it's been written, so
+    /// a request with many peers doesn't have to be held in memory at once.
+    pub fn set_peers<I>(&self, set_request: set::Device, peers: I) -> Result<(), SetDeviceError>
+    where
+        I: IntoIterator<Item = set::Peer>,
+    {
+        let stream = UnixStream::connect(&self.path)?;
+        set_device(&mut BufReader::new(stream), set_request, peers)
     }
 
-    pub fn set(&self, set_request: set::Device) -> Result<(), SetDeviceError> {
-        let mut stream = UnixStream::connect(&self.path)?;
-
-        stream.write_all(SET_CMD.as_bytes())?;
-        // TODO: This likely buffers the entire set_request in memory before
-        // sending it across the socket. We can probably do better.
-        stream.write_fmt(format_args!("{}", set_request))?;
-        stream.write_all(b"\n")?;
-
-        let reader = std::io::BufReader::new(stream);
-        let mut response_lines = reader.lines();
-
-        // The response for protocol_version=1 is expected to be a single
-        // "errno=N" line followed by an empty line.
-        let errno_line = response_lines
-            .next()
-            .ok_or(SetDeviceError::EmptyResponse)??;
-
-        let (raw_key, raw_value) = {
-            let mut tokens = errno_line.trim().splitn(2, '=');
-            let raw_key = tokens.next().unwrap();
-            let raw_value = match tokens.next() {
-                Some(val) => val,
-                None => return Err(SetDeviceError::InvalidResponse(errno_line)),
-            };
-
-            (raw_key, raw_value)
-        };
+    /// A [`Connection`](super::Connection) that runs operations on a single
+    /// stream instead of connecting for each of them.
+    pub fn connection(&self) -> super::Connection<&Path> {
+        super::Connection::new(self.path.as_ref())
+    }
+}
+
+/// Runs a get operation on `stream` and reads the response with `parse`.
+/// Reading stops at the end of the response, so further operations can follow
+/// on the same stream.
+pub(super) fn get_device<'a, T, F>(
+    stream: &'a mut BufReader<UnixStream>,
+    parse: F,
+) -> Result<T, GetDeviceError>
+where
+    F: FnOnce(Lines<&'a mut BufReader<UnixStream>>) -> Result<T, ParseGetResponseError>,
+{
+    stream.get_mut().write_all(GET_CMD.as_bytes())?;
+
+    Ok(parse(stream.lines())?)
+}
 
-        match (raw_key, raw_value) {
-            ("errno", "0") => {}
-            ("errno", val) => return Err(SetDeviceError::ServerError(val.to_string())),
-            (_, _) => return Err(SetDeviceError::InvalidResponse(errno_line)),
-        }
-
-        let empty_line = response_lines
-            .next()
-            .ok_or(SetDeviceError::EmptyResponse)??;
-        if !empty_line.is_empty() {
-            return Err(SetDeviceError::InvalidEndOfResponse(empty_line));
-        }
+/// Runs a set operation on `stream`. Reading stops at the end of the
+/// response, so further operations can follow on the same stream.
+pub(super) fn set_device<I>(
+    stream: &mut BufReader<UnixStream>,
+    set_request: set::Device,
+    peers: I,
+) -> Result<(), SetDeviceError>
+where
+    I: IntoIterator<Item = set::Peer>,
+{
+    let mut writer = BufWriter::new(stream.get_ref());
+    writer.write_all(SET_CMD.as_bytes())?;
+    write!(writer, "{}", set_request.interface_keys())?;
+    for peer in set_request.peers.into_iter().chain(peers) {
+        write!(writer, "{}", peer)?;
+    }
+    writer.write_all(b"\n")?;
+    writer.flush()?;
+    drop(writer);
+
+    let mut response_lines = stream.lines();
+
+    let errno_line = response_lines
+        .next()
+        .ok_or(SetDeviceError::EmptyResponse)??;
+    check_set_errno_line(errno_line)?;
+
+    let empty_line = response_lines
+        .next()
+        .ok_or(SetDeviceError::EmptyResponse)??;
+    check_set_end_of_response(empty_line)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Client;
+    use crate::xplatform::fake::FakeDevice;
+    use crate::xplatform::server::Server;
+    use crate::xplatform::set;
+    use std::net::Ipv6Addr;
+
+    fn peer(index: u8) -> set::Peer {
+        let allowed_ips = (0..100)
+            .map(|host| set::AllowedIp {
+                ipaddr: Ipv6Addr::new(0xfd00, u16::from(index), 0, 0, 0, 0, 0, host).into(),
+                cidr_mask: 128,
+            })
+            .collect();
+        set::Peer::from_public_key([index; 32]).allowed_ips(allowed_ips)
+    }
+
+    #[test]
+    fn set_peers_appends_streamed_peers() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("wgtest0.sock");
+        let server = Server::bind(&path, FakeDevice::new())?;
+        let server = std::thread::spawn(move || {
+            let (stream, _) = server.listener().accept().unwrap();
+            server.handle_connection(stream).unwrap();
+            server.handler().requests()[0].clone()
+        });
+
+        let request = set::Device {
+            replace_peers: Some(true),
+            peers: vec![peer(0)],
+            ..Default::default()
+        };
+        Client::create(&path).set_peers(request, (1..=200).map(peer))?;
+
+        let received = server.join().unwrap();
+        assert_eq!(received.replace_peers, Some(true));
+        assert_eq!(received.peers.len(), 201);
+        assert!(received
+            .peers
+            .iter()
+            .enumerate()
+            .all(|(index, received)| *received == peer(index as u8)));
 
         Ok(())
     }
@@ -0,0 +1,209 @@
+use crate::xplatform::error::DiscoverError;
+use crate::xplatform::Client;
+use std::collections::BTreeMap;
+use std::io;
+use std::os::unix::fs::FileTypeExt;
+use std::os::unix::net::UnixStream;
+use std::path::{Path, PathBuf};
+
+/// Where userspace implementations such as wireguard-go create their sockets.
+pub const DEFAULT_SOCKET_DIR: &str = "/var/run/wireguard";
+
+/// Finds the userspace WireGuard interfaces on this machine by scanning a
+/// directory for `<ifname>.sock` files, the same way `wg show interfaces`
+/// does. This is the userspace equivalent of
+/// `RouteSocket::list_device_names`.
+///
+/// ```no_run
+/// use wireguard_uapi::xplatform::Discovery;
+///
+/// let discovered = Discovery::new().remove_stale(true).discover()?;
+/// for (ifname, client) in &discovered.clients {
+///     println!("{}: {:?}", ifname, client.get()?);
+/// }
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+#[derive(Clone, Debug)]
+pub struct Discovery {
+    socket_dir: PathBuf,
+    remove_stale: bool,
+}
+
+/// The result of [`Discovery::discover`].
+#[derive(Debug, Default)]
+pub struct Discovered {
+    /// A client for each live socket, keyed by interface name.
+    pub clients: BTreeMap<String, Client<PathBuf>>,
+
+    /// Sockets that nothing is listening on anymore, usually left behind by an
+    /// implementation that didn't shut down cleanly. They have already been
+    /// removed if [`Discovery::remove_stale`] was set.
+    pub stale: Vec<PathBuf>,
+
+    /// Sockets that couldn't be connected to, or stale sockets that couldn't
+    /// be removed, along with the error. Usually this is a lack of
+    /// permissions.
+    pub inaccessible: Vec<(PathBuf, io::Error)>,
+}
+
+impl Default for Discovery {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Discovery {
+    pub fn new() -> Self {
+        Self {
+            socket_dir: PathBuf::from(DEFAULT_SOCKET_DIR),
+            remove_stale: false,
+        }
+    }
+
+    /// The directory to scan. Defaults to [`DEFAULT_SOCKET_DIR`].
+    pub fn socket_dir<P: Into<PathBuf>>(mut self, socket_dir: P) -> Self {
+        self.socket_dir = socket_dir.into();
+        self
+    }
+
+    /// Whether stale sockets should be deleted. Defaults to `false`, in which
+    /// case they're only reported.
+    pub fn remove_stale(mut self, remove_stale: bool) -> Self {
+        self.remove_stale = remove_stale;
+        self
+    }
+
+    /// Scans the socket directory. A missing directory means there are no
+    /// userspace interfaces, so it results in nothing being discovered.
+    ///
+    /// Every socket is connected to once to check that it's live. Files that
+    /// aren't sockets are skipped. A socket that can't be checked doesn't stop
+    /// the scan, it's reported in [`Discovered::inaccessible`] instead.
+    pub fn discover(&self) -> Result<Discovered, DiscoverError> {
+        let read_dir_error = |err| DiscoverError::ReadDir(self.socket_dir.clone(), err);
+
+        let entries = match std::fs::read_dir(&self.socket_dir) {
+            Ok(entries) => entries,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Discovered::default()),
+            Err(err) => return Err(read_dir_error(err)),
+        };
+
+        let mut discovered = Discovered::default();
+        for entry in entries {
+            let entry = entry.map_err(read_dir_error)?;
+            let path = entry.path();
+
+            let ifname = match socket_ifname(&path) {
+                Some(ifname) => ifname.to_string(),
+                None => continue,
+            };
+            // The file may have been removed since the directory was read.
+            match entry.file_type() {
+                Ok(file_type) if file_type.is_socket() => {}
+                _ => continue,
+            }
+
+            match UnixStream::connect(&path) {
+                Ok(_) => {
+                    discovered.clients.insert(ifname, Client::create(path));
+                }
+                Err(err) if err.kind() == io::ErrorKind::ConnectionRefused => {
+                    if self.remove_stale {
+                        match std::fs::remove_file(&path) {
+                            Ok(()) => {}
+                            Err(err) if err.kind() == io::ErrorKind::NotFound => {}
+                            Err(err) => {
+                                discovered.inaccessible.push((path, err));
+                                continue;
+                            }
+                        }
+                    }
+                    discovered.stale.push(path);
+                }
+                Err(err) if err.kind() == io::ErrorKind::NotFound => {}
+                Err(err) => discovered.inaccessible.push((path, err)),
+            }
+        }
+
+        discovered.stale.sort();
+        discovered
+            .inaccessible
+            .sort_by(|(left, _), (right, _)| left.cmp(right));
+        Ok(discovered)
+    }
+}
+
+/// The interface name of a `<ifname>.sock` path.
+fn socket_ifname(path: &Path) -> Option<&str> {
+    if path.extension()? != "sock" {
+        return None;
+    }
+    path.file_stem()?.to_str()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::net::{UnixDatagram, UnixListener};
+
+    #[test]
+    fn discover_live_and_stale_sockets() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let _wg0 = UnixListener::bind(dir.path().join("wg0.sock"))?;
+        let _utun3 = UnixListener::bind(dir.path().join("utun3.sock"))?;
+        // Unix sockets stay on disk after their listener is closed.
+        drop(UnixListener::bind(dir.path().join("wg1.sock"))?);
+        let _other = UnixListener::bind(dir.path().join("other.socket"))?;
+        std::fs::write(dir.path().join("wg2.sock"), "")?;
+
+        let discovered = Discovery::new().socket_dir(dir.path()).discover()?;
+        assert_eq!(
+            discovered.clients.keys().collect::<Vec<_>>(),
+            vec!["utun3", "wg0"]
+        );
+        assert_eq!(discovered.stale, vec![dir.path().join("wg1.sock")]);
+        assert!(dir.path().join("wg1.sock").exists());
+
+        let discovered = Discovery::new()
+            .socket_dir(dir.path())
+            .remove_stale(true)
+            .discover()?;
+        assert_eq!(discovered.clients.len(), 2);
+        assert_eq!(discovered.stale, vec![dir.path().join("wg1.sock")]);
+        assert!(!dir.path().join("wg1.sock").exists());
+
+        Ok(())
+    }
+
+    #[test]
+    fn discover_reports_inaccessible_sockets() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let _wg0 = UnixListener::bind(dir.path().join("wg0.sock"))?;
+        // Stream connections to a datagram socket fail with EPROTOTYPE, which
+        // doesn't depend on permissions that root would bypass.
+        let _wg1 = UnixDatagram::bind(dir.path().join("wg1.sock"))?;
+
+        let discovered = Discovery::new().socket_dir(dir.path()).discover()?;
+        assert_eq!(discovered.clients.keys().collect::<Vec<_>>(), vec!["wg0"]);
+        assert!(discovered.stale.is_empty());
+        assert_eq!(discovered.inaccessible.len(), 1);
+        let (path, err) = &discovered.inaccessible[0];
+        assert_eq!(path, &dir.path().join("wg1.sock"));
+        assert_eq!(err.raw_os_error(), Some(libc::EPROTOTYPE));
+
+        Ok(())
+    }
+
+    #[test]
+    fn discover_without_socket_dir() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let discovered = Discovery::new()
+            .socket_dir(dir.path().join("wireguard"))
+            .discover()?;
+        assert!(discovered.clients.is_empty());
+        assert!(discovered.stale.is_empty());
+        assert!(discovered.inaccessible.is_empty());
+
+        Ok(())
+    }
+}
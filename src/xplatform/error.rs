@@ -1,4 +1,62 @@
 pub use super::parser::ParseGetResponseError;
+use std::io;
+use std::path::PathBuf;
+
+/// A non-zero `errno` an implementation replied with. Values that callers
+/// commonly need to tell apart have their own variants.
+///
+/// Some implementations, such as wireguard-go, send negated errno values.
+/// These are treated the same as positive ones.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, thiserror::Error)]
+pub enum ServerError {
+    /// A key or value of the request was invalid (`EINVAL`).
+    #[error("Server rejected the request as invalid (EINVAL)")]
+    InvalidRequest,
+    /// The request didn't follow the protocol (`EPROTO`).
+    #[error("Server rejected the request as a protocol violation (EPROTO)")]
+    Protocol,
+    /// The device doesn't exist (`ENOENT`).
+    #[error("Server could not find the device (ENOENT)")]
+    NotFound,
+    /// The requested listen port is taken (`EADDRINUSE`).
+    #[error("Server could not bind the listen port since it is in use (EADDRINUSE)")]
+    AddrInUse,
+    #[error("Received non-zero error number in response: `{0}`")]
+    Other(i32),
+}
+
+impl ServerError {
+    pub fn from_errno(errno: i32) -> Self {
+        match errno.wrapping_abs() {
+            libc::EINVAL => Self::InvalidRequest,
+            libc::EPROTO => Self::Protocol,
+            libc::ENOENT => Self::NotFound,
+            libc::EADDRINUSE => Self::AddrInUse,
+            errno => Self::Other(errno),
+        }
+    }
+
+    /// The positive errno value.
+    pub fn errno(&self) -> i32 {
+        match self {
+            Self::InvalidRequest => libc::EINVAL,
+            Self::Protocol => libc::EPROTO,
+            Self::NotFound => libc::ENOENT,
+            Self::AddrInUse => libc::EADDRINUSE,
+            Self::Other(errno) => *errno,
+        }
+    }
+
+    pub fn kind(&self) -> io::ErrorKind {
+        io::Error::from_raw_os_error(self.errno()).kind()
+    }
+}
+
+impl From<ServerError> for io::Error {
+    fn from(error: ServerError) -> Self {
+        io::Error::from_raw_os_error(error.errno())
+    }
+}
 
 #[derive(Debug, thiserror::Error)]
 pub enum GetDeviceError {
@@ -12,8 +70,8 @@ pub enum GetDeviceError {
 pub enum SetDeviceError {
     #[error(transparent)]
     Io(#[from] std::io::Error),
-    #[error("Received non-zero error number in response: `{0}`")]
-    ServerError(String),
+    #[error(transparent)]
+    ServerError(#[from] ServerError),
     #[error("Received empty response")]
     EmptyResponse,
     #[error("Failed to parse response: `{0}`")]
@@ -21,3 +79,9 @@ pub enum SetDeviceError {
     #[error("Invalid end of response. Expected empty line but saw: `{0}`")]
     InvalidEndOfResponse(String),
 }
+
+#[derive(Debug, thiserror::Error)]
+pub enum DiscoverError {
+    #[error("Failed to read socket directory `{}`: `{1}`", .0.display())]
+    ReadDir(PathBuf, #[source] std::io::Error),
+}
@@ -0,0 +1,205 @@
+//! An in-memory stand-in for a userspace WireGuard implementation.
+//!
+//! [`FakeDevice`] is a [`Handler`] that applies set operations the way
+//! wireguard-go's `IpcSetOperation` does, so clients can be tested against a
+//! [`Server`](crate::xplatform::server::Server) without running wireguard-go:
+//!
+//! ```
+//! use wireguard_uapi::xplatform::{fake::FakeDevice, server::Server, set, Client};
+//!
+//! let dir = tempfile::tempdir()?;
+//! let path = dir.path().join("wgtest0.sock");
+//! let server = Server::bind(&path, FakeDevice::new())?;
+//! std::thread::spawn(move || server.serve());
+//!
+//! let client = Client::create(&path);
+//! client.set(set::Device {
+//!     listen_port: Some(51820),
+//!     ..Default::default()
+//! })?;
+//! assert_eq!(client.get()?.listen_port, 51820);
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+use crate::get;
+use crate::xplatform::server::Handler;
+use crate::xplatform::set;
+use std::collections::HashSet;
+use std::io;
+use std::net::IpAddr;
+use std::time::Duration;
+
+/// A single device kept in memory. Keys of a set operation are applied in
+/// order, so a failing key leaves the ones before it applied.
+#[derive(Clone, Debug)]
+pub struct FakeDevice {
+    /// `None` once the device has been removed.
+    device: Option<get::Device>,
+    /// Ports some other program is listening on.
+    ports_in_use: HashSet<u16>,
+    requests: Vec<set::Device>,
+}
+
+impl Default for FakeDevice {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FakeDevice {
+    /// A device without keys or peers.
+    pub fn new() -> Self {
+        Self::from_device(get::Device {
+            ifindex: 0,
+            ifname: "".to_string(),
+            private_key: None,
+            public_key: None,
+            listen_port: 0,
+            fwmark: 0,
+            peers: vec![],
+        })
+    }
+
+    pub fn from_device(device: get::Device) -> Self {
+        Self {
+            device: Some(device),
+            ports_in_use: HashSet::new(),
+            requests: vec![],
+        }
+    }
+
+    /// The current state of the device, or `None` if it has been removed.
+    pub fn device(&self) -> Option<&get::Device> {
+        self.device.as_ref()
+    }
+
+    /// Every set operation received so far, including ones that failed.
+    pub fn requests(&self) -> &[set::Device] {
+        &self.requests
+    }
+
+    /// Makes setting `port` as the listen port fail with `EADDRINUSE`, as if
+    /// another program was listening on it.
+    pub fn occupy_port(&mut self, port: u16) {
+        self.ports_in_use.insert(port);
+    }
+
+    /// Removes the device. Operations fail with `ENOENT` afterwards.
+    pub fn remove(&mut self) {
+        self.device = None;
+    }
+}
+
+fn no_device() -> io::Error {
+    io::Error::from_raw_os_error(libc::ENOENT)
+}
+
+impl Handler for FakeDevice {
+    fn get(&mut self) -> io::Result<get::Device> {
+        self.device.clone().ok_or_else(no_device)
+    }
+
+    fn set(&mut self, request: set::Device) -> io::Result<()> {
+        self.requests.push(request.clone());
+        let device = self.device.as_mut().ok_or_else(no_device)?;
+
+        if let Some(private_key) = request.private_key {
+            device.private_key = Some(private_key).filter(|key| *key != [0u8; 32]);
+        }
+        if let Some(listen_port) = request.listen_port {
+            if self.ports_in_use.contains(&listen_port) {
+                return Err(io::Error::from_raw_os_error(libc::EADDRINUSE));
+            }
+            device.listen_port = listen_port;
+        }
+        if let Some(fwmark) = request.fwmark {
+            device.fwmark = fwmark;
+        }
+        if request.replace_peers == Some(true) {
+            device.peers.clear();
+        }
+
+        for peer in request.peers {
+            apply_peer(device, peer);
+        }
+
+        Ok(())
+    }
+}
+
+fn apply_peer(device: &mut get::Device, request: set::Peer) {
+    let existing = device
+        .peers
+        .iter()
+        .position(|peer| peer.public_key == request.public_key);
+
+    if request.remove == Some(true) {
+        if let Some(index) = existing {
+            device.peers.remove(index);
+        }
+        return;
+    }
+
+    let index = match existing {
+        Some(index) => index,
+        None if request.update_only == Some(true) => return,
+        None => {
+            device.peers.push(get::Peer {
+                public_key: request.public_key,
+                preshared_key: [0u8; 32],
+                endpoint: None,
+                persistent_keepalive_interval: 0,
+                last_handshake_time: Duration::new(0, 0),
+                rx_bytes: 0,
+                tx_bytes: 0,
+                allowed_ips: vec![],
+                protocol_version: 1,
+            });
+            device.peers.len() - 1
+        }
+    };
+
+    // An allowed IP belongs to at most one peer, so adding it to this one
+    // takes it away from any other.
+    let allowed_ips: Vec<get::AllowedIp> = request
+        .allowed_ips
+        .iter()
+        .map(|allowed_ip| get::AllowedIp {
+            family: match allowed_ip.ipaddr {
+                IpAddr::V4(_) => libc::AF_INET as u16,
+                IpAddr::V6(_) => libc::AF_INET6 as u16,
+            },
+            ipaddr: allowed_ip.ipaddr,
+            cidr_mask: allowed_ip.cidr_mask,
+        })
+        .collect();
+    let moved: HashSet<_> = allowed_ips
+        .iter()
+        .map(|allowed_ip| (allowed_ip.ipaddr, allowed_ip.cidr_mask))
+        .collect();
+    for (other_index, other) in device.peers.iter_mut().enumerate() {
+        if other_index != index {
+            other
+                .allowed_ips
+                .retain(|existing| !moved.contains(&(existing.ipaddr, existing.cidr_mask)));
+        }
+    }
+
+    let peer = &mut device.peers[index];
+    if let Some(preshared_key) = request.preshared_key {
+        peer.preshared_key = preshared_key;
+    }
+    if let Some(endpoint) = request.endpoint {
+        peer.endpoint = Some(endpoint);
+    }
+    if let Some(interval) = request.persistent_keepalive_interval {
+        peer.persistent_keepalive_interval = interval;
+    }
+    if request.replace_allowed_ips == Some(true) {
+        peer.allowed_ips.clear();
+    }
+    for allowed_ip in allowed_ips {
+        if !peer.allowed_ips.contains(&allowed_ip) {
+            peer.allowed_ips.push(allowed_ip);
+        }
+    }
+}
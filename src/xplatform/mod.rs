@@ -9,6 +9,15 @@
 //! [`get`][crate::get] module for the cross-platform client is shared with the
 //! Linux-specific client since the typings are compatible.
 //!
+//! The [`server`][crate::xplatform::server] module implements the other end
+//! of the protocol, for programs that embed a userspace WireGuard
+//! implementation. [`GetResponse`] renders any [`get::Device`][crate::get::Device]
+//! as a get response, including ones read from the Linux kernel. With the
+//! `fake` feature, [`fake::FakeDevice`] answers them like wireguard-go does.
+//!
+//! Existing userspace interfaces can be found with [`Discovery`], which scans
+//! the directory their sockets live in.
+//!
 //! This module does not provide any way to create and destroy WireGuard
 //! interfaces. That functionality is not part of the cross-platform protocol
 //! specification. In general you will have to shell out to the command line. See
@@ -19,10 +28,25 @@
 //! [xplatform-interface]: https://www.wireguard.com/xplatform/#interface
 
 mod client;
+#[cfg(unix)]
+mod discover;
 pub mod error;
+#[cfg(all(unix, any(test, feature = "fake")))]
+pub mod fake;
 pub mod parser;
-mod protocol;
+pub(crate) mod protocol;
+mod response;
+#[cfg(unix)]
+pub mod server;
 pub mod set;
 
+pub use response::GetResponse;
+
+#[cfg(unix)]
+pub use client::{Client, Connection};
+
 #[cfg(unix)]
-pub use client::Client;
+pub use discover::{Discovered, Discovery, DEFAULT_SOCKET_DIR};
+
+#[cfg(all(unix, feature = "tokio"))]
+pub use client::AsyncClient;
@@ -0,0 +1,185 @@
+use super::parse::{finish, initial_state, process_line, ParseGetResponseError};
+use super::state::ParseState;
+use crate::get;
+use crate::xplatform::protocol::GetKey;
+use std::collections::BTreeMap;
+use std::str::FromStr;
+use take_until::TakeUntilExt;
+
+/// Values of keys the parser doesn't know about, in the order they appeared.
+pub type ExtensionMap = BTreeMap<String, Vec<String>>;
+
+/// Unknown keys encountered by [`parse_lenient`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Extensions {
+    /// Keys that appeared before the first `public_key`.
+    pub interface: ExtensionMap,
+
+    /// Keys that appeared in each peer block. This has one entry for each of
+    /// [`get::Device::peers`], in the same order.
+    pub peers: Vec<ExtensionMap>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LenientGetResponse {
+    pub device: get::Device,
+    pub extensions: Extensions,
+}
+
+/// A forward-compatible version of [`parse`](super::parse). Keys that the
+/// parser doesn't know about are collected into [`Extensions`] instead of
+/// failing with [`ParseGetResponseError::UnknownKey`], so responses from newer
+/// implementations can still be read.
+///
+/// Whitespace around lines, keys and values is ignored, and a line with only
+/// whitespace ends the response. The `errno` value is read as a number, so
+/// variations like `errno= 0` or `errno=00` are accepted as success.
+///
+/// Everything else is checked the same way [`parse`](super::parse) does it.
+/// Use that instead to test that an implementation follows the protocol
+/// exactly.
+pub fn parse_lenient(
+    lines: impl Iterator<Item = Result<String, std::io::Error>>,
+) -> Result<LenientGetResponse, ParseGetResponseError> {
+    let (parse_state, extensions) = lines.take_until(is_end_of_response_lenient).try_fold(
+        (initial_state(), Extensions::default()),
+        |(state, extensions), line| process_line_lenient(state, extensions, line),
+    )?;
+
+    finish_lenient(parse_state, extensions)
+}
+
+/// Whether `line` ends a response for [`parse_lenient`].
+pub(crate) fn is_end_of_response_lenient(line: &std::io::Result<String>) -> bool {
+    matches!(line.as_ref().map(|line| line.trim()), Ok(""))
+}
+
+pub(crate) fn finish_lenient(
+    state: ParseState,
+    mut extensions: Extensions,
+) -> Result<LenientGetResponse, ParseGetResponseError> {
+    let device = finish(state)?;
+    extensions
+        .peers
+        .resize_with(device.peers.len(), Default::default);
+    Ok(LenientGetResponse { device, extensions })
+}
+
+pub(crate) fn process_line_lenient(
+    state: ParseState,
+    mut extensions: Extensions,
+    line: std::io::Result<String>,
+) -> Result<(ParseState, Extensions), ParseGetResponseError> {
+    let line = line.map_err(ParseGetResponseError::ReadLineIoError)?;
+    let line = line.trim();
+
+    if line.is_empty() {
+        return Ok((process_line(state, Ok(String::new()))?, extensions));
+    }
+
+    let (raw_key, raw_val) = match line.split_once('=') {
+        Some((raw_key, raw_val)) => (raw_key.trim(), raw_val.trim()),
+        None => return Ok((process_line(state, Ok(line.to_string()))?, extensions)),
+    };
+
+    let raw_val = match GetKey::from_str(raw_key) {
+        Ok(GetKey::Errno) => match raw_val.parse::<i64>() {
+            Ok(0) => "0",
+            _ => raw_val,
+        },
+        Ok(_) => raw_val,
+        Err(_) => {
+            let map = match &state {
+                ParseState::PeerLevelKeys(peer_state) => {
+                    // The peer currently being read comes after the ones
+                    // that are already complete.
+                    let index = peer_state.peers.len();
+                    extensions.peers.resize_with(index + 1, Default::default);
+                    &mut extensions.peers[index]
+                }
+                _ => &mut extensions.interface,
+            };
+            map.entry(raw_key.to_string())
+                .or_default()
+                .push(raw_val.to_string());
+            return Ok((state, extensions));
+        }
+    };
+
+    let line = format!("{}={}", raw_key, raw_val);
+    Ok((process_line(state, Ok(line))?, extensions))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_lenient, ExtensionMap};
+    use crate::xplatform::error::ServerError;
+    use crate::xplatform::parser::{parse, ParseGetResponseError};
+
+    fn lines(response: &str) -> impl Iterator<Item = std::io::Result<String>> + '_ {
+        response.lines().map(String::from).map(Ok)
+    }
+
+    #[test]
+    fn parse_lenient_collects_unknown_keys() -> anyhow::Result<()> {
+        let response = "\
+            private_key=18aa10c05a531f5c537a18426b376387fc2cbd701ae1b9b4271e327aaade9d4f\n\
+            listen_port=56137\n\
+            jc=4\n\
+            public_key=913ea0e20e28c12b5c5f5a858b93a05e686dc3ce524e16f3143bbb1023679751\n\
+            protocol_version=1\n\
+            allowed_ip=10.24.24.3/32\n\
+            public_key=58402e695ba1772b1cc9309755f043251ea77fdcf10fbe63989ceb7e19321376\n\
+            endpoint_name=vpn.example.com\n\
+            endpoint_name=vpn2.example.com\n\
+            allowed_ip=10.24.24.4/32\n\
+            public_key=662e14fd594556f522604703340351258903b64f35553763f19426ab2a515c58\n\
+            errno=0\n\
+            \n";
+
+        assert!(matches!(
+            parse(lines(response)),
+            Err(ParseGetResponseError::UnknownKey(key)) if key == "jc"
+        ));
+
+        let parsed = parse_lenient(lines(response))?;
+        assert_eq!(parsed.device.listen_port, 56137);
+        assert_eq!(parsed.device.peers.len(), 3);
+        assert_eq!(parsed.device.peers[1].allowed_ips.len(), 1);
+
+        let expected_interface: ExtensionMap = vec![("jc".to_string(), vec!["4".to_string()])]
+            .into_iter()
+            .collect();
+        assert_eq!(parsed.extensions.interface, expected_interface);
+        assert_eq!(parsed.extensions.peers.len(), 3);
+        assert!(parsed.extensions.peers[0].is_empty());
+        assert_eq!(
+            parsed.extensions.peers[1]["endpoint_name"],
+            vec!["vpn.example.com", "vpn2.example.com"]
+        );
+        assert!(parsed.extensions.peers[2].is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn parse_lenient_tolerates_whitespace_and_errno_variations() -> anyhow::Result<()> {
+        let response = "  listen_port = 56137\r\n\
+            public_key=913ea0e20e28c12b5c5f5a858b93a05e686dc3ce524e16f3143bbb1023679751 \n\
+            errno = 00\n\
+            \t\n";
+
+        assert!(parse(lines(response)).is_err());
+
+        let parsed = parse_lenient(lines(response))?;
+        assert_eq!(parsed.device.listen_port, 56137);
+        assert_eq!(parsed.device.peers.len(), 1);
+
+        assert!(matches!(
+            parse_lenient(lines("errno= 19\n\n")),
+            Err(ParseGetResponseError::ServerError(ServerError::Other(19)))
+        ));
+
+        Ok(())
+    }
+}
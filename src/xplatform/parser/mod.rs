@@ -1,5 +1,13 @@
+mod lenient;
 mod parse;
+mod parse_set;
 mod state;
 
+#[cfg(feature = "tokio")]
+pub(crate) use lenient::{finish_lenient, is_end_of_response_lenient, process_line_lenient};
+pub use lenient::{parse_lenient, ExtensionMap, Extensions, LenientGetResponse};
 pub use parse::parse;
 pub use parse::ParseGetResponseError;
+#[cfg(feature = "tokio")]
+pub(crate) use parse::{finish, initial_state, process_line};
+pub use parse_set::{parse_set, ParseSetRequestError};
@@ -1,6 +1,7 @@
 use super::state::{ParsePeerState, ParseState};
 use crate::get;
 use crate::get::{DeviceBuilderError, ParseAllowedIpError, PeerBuilderError};
+use crate::xplatform::error::ServerError;
 use crate::xplatform::protocol::{GetKey, ParseKeyError};
 use std::net::AddrParseError;
 use std::num::ParseIntError;
@@ -13,8 +14,10 @@ pub enum ParseGetResponseError {
     #[error("Failed to read line from socket: `{0}`")]
     ReadLineIoError(#[source] std::io::Error),
 
-    #[error("Received non-zero error number in response: `{0}`")]
-    ServerError(String),
+    #[error(transparent)]
+    ServerError(#[from] ServerError),
+    #[error("Invalid errno: `{0}`")]
+    InvalidErrno(String),
     #[error("Received incomplete response")]
     IncompleteResponse,
     #[error("Received empty response")]
@@ -81,13 +84,6 @@ impl From<ParseKeyError> for ParseGetResponseError {
 pub fn parse(
     lines: impl Iterator<Item = Result<String, std::io::Error>>,
 ) -> Result<get::Device, ParseGetResponseError> {
-    let initial_state = {
-        let mut device_builder = get::DeviceBuilder::default();
-        device_builder.ifindex(0);
-        device_builder.ifname("".to_string());
-        device_builder.fwmark(0);
-        ParseState::Initial(device_builder)
-    };
     let parse_state = lines
         // WireGuard xplatform implementations signify the end of a response
         // with an empty newline. Stop reading beyond this point to avoid
@@ -96,8 +92,24 @@ pub fn parse(
         // The rust standard library may include take_until in the future.
         // https://github.com/rust-lang/rust/issues/62208
         .take_until(|result| matches!(result.as_ref().map(String::as_str), Ok("")))
-        .try_fold(initial_state, process_line)?;
+        .try_fold(initial_state(), process_line)?;
+
+    finish(parse_state)
+}
+
+/// The state before any line of a response has been read. Along with
+/// [`process_line`] and [`finish`], this allows driving the parser one line at
+/// a time when lines aren't available as an [`Iterator`].
+pub(crate) fn initial_state() -> ParseState {
+    let mut device_builder = get::DeviceBuilder::default();
+    device_builder.ifindex(0);
+    device_builder.ifname("".to_string());
+    device_builder.fwmark(0);
+    ParseState::Initial(device_builder)
+}
 
+/// Converts the state after the last line of a response into a device.
+pub(crate) fn finish(parse_state: ParseState) -> Result<get::Device, ParseGetResponseError> {
     match parse_state {
         ParseState::Initial(_) => Err(ParseGetResponseError::EmptyResponse),
         ParseState::InterfaceLevelKeys(_) | ParseState::PeerLevelKeys(_) => {
@@ -107,7 +119,7 @@ pub fn parse(
     }
 }
 
-fn process_line(
+pub(crate) fn process_line(
     state: ParseState,
     line: std::io::Result<String>,
 ) -> Result<ParseState, ParseGetResponseError> {
@@ -158,10 +170,10 @@ fn process_line(
                 device_builder.listen_port(listen_port);
                 Ok(ParseState::InterfaceLevelKeys(device_builder))
             }
-            GetKey::Errno => match raw_val {
-                "0" => Ok(ParseState::Initial(device_builder)),
-                _ => Err(ParseErr::ServerError(raw_val.to_string())),
-            },
+            GetKey::Errno => {
+                check_errno(raw_val)?;
+                Ok(ParseState::Initial(device_builder))
+            }
             _ => Err(ParseErr::InvalidStartOfResponse(key)),
         },
 
@@ -212,10 +224,10 @@ fn process_line(
             | GetKey::LastHandshakeTimeNsec
             | GetKey::ProtocolVersion => Err(ParseErr::PeerLevelKeyBeforePublicKey(key)),
 
-            GetKey::Errno => match raw_val {
-                "0" => Ok(ParseState::InterfaceLevelKeys(device_builder)),
-                _ => Err(ParseErr::ServerError(raw_val.to_string())),
-            },
+            GetKey::Errno => {
+                check_errno(raw_val)?;
+                Ok(ParseState::InterfaceLevelKeys(device_builder))
+            }
         },
 
         ParseState::PeerLevelKeys(mut state) => match key {
@@ -305,16 +317,28 @@ fn process_line(
                 state.peer_builder.protocol_version(protocol_version);
                 Ok(ParseState::PeerLevelKeys(state))
             }
-            GetKey::Errno => match raw_val {
-                "0" => Ok(ParseState::PeerLevelKeys(state)),
-                _ => Err(ParseErr::ServerError(raw_val.to_string())),
-            },
+            GetKey::Errno => {
+                check_errno(raw_val)?;
+                Ok(ParseState::PeerLevelKeys(state))
+            }
         },
 
         ParseState::Finish(_) => Err(ParseErr::DataAfterEndOfResponse(key)),
     }
 }
 
+/// Succeeds for `errno=0`. Any other number is reported as a [`ServerError`].
+fn check_errno(raw_val: &str) -> Result<(), ParseGetResponseError> {
+    if raw_val == "0" {
+        return Ok(());
+    }
+
+    match raw_val.parse() {
+        Ok(errno) if errno != 0 => Err(ServerError::from_errno(errno).into()),
+        _ => Err(ParseGetResponseError::InvalidErrno(raw_val.to_string())),
+    }
+}
+
 // TODO: Get this from a shared util
 pub fn parse_device_key(buf: &[u8]) -> Option<[u8; 32]> {
     if buf.len() != 32 {
@@ -328,8 +352,9 @@ pub fn parse_device_key(buf: &[u8]) -> Option<[u8; 32]> {
 
 #[cfg(test)]
 mod tests {
-    use super::{parse, parse_device_key};
+    use super::{parse, parse_device_key, ParseGetResponseError};
     use crate::get;
+    use crate::xplatform::error::ServerError;
     use std::time::Duration;
 
     #[test]
@@ -384,6 +409,24 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn parse_server_error() {
+        let parse_str = |response: &str| parse(response.lines().map(String::from).map(Ok));
+
+        assert!(matches!(
+            parse_str("errno=2\n\n"),
+            Err(ParseGetResponseError::ServerError(ServerError::NotFound))
+        ));
+        assert!(matches!(
+            parse_str(&format!("listen_port=51820\nerrno=-{}\n\n", libc::EPROTO)),
+            Err(ParseGetResponseError::ServerError(ServerError::Protocol))
+        ));
+        assert!(matches!(
+            parse_str("errno=-\n\n"),
+            Err(ParseGetResponseError::InvalidErrno(_))
+        ));
+    }
+
     #[test]
     fn parse_device_with_no_peers() -> anyhow::Result<()> {
         let response = "\
@@ -0,0 +1,349 @@
+use super::parse::parse_device_key;
+use crate::get::{self, ParseAllowedIpError};
+use crate::xplatform::protocol::{ParseKeyError, SetKey};
+use crate::xplatform::set;
+use std::collections::HashSet;
+use std::net::AddrParseError;
+use std::num::ParseIntError;
+use std::str::FromStr;
+use take_until::TakeUntilExt;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum ParseSetRequestError {
+    #[error("Failed to read line from socket: `{0}`")]
+    ReadLineIoError(#[source] std::io::Error),
+
+    #[error("Set requests require an empty line. None found.")]
+    MissingEndOfRequestNewline,
+
+    #[error("Encountered unknown key `{0}`")]
+    UnknownKey(String),
+    #[error("Missing value for key `{0}`")]
+    MissingValueForKey(SetKey),
+
+    #[error("Invalid private_key")]
+    InvalidPrivateKey,
+    #[error("Invalid public_key: `{0}`")]
+    InvalidPublicKey(String),
+    #[error("Invalid preshared_key")]
+    InvalidPresharedKey,
+    #[error("{0}")]
+    InvalidListenPort(#[source] ParseIntError),
+    #[error("{0}")]
+    InvalidFwmark(#[source] ParseIntError),
+    #[error("{0}")]
+    InvalidEndpoint(#[source] AddrParseError),
+    #[error("{0}")]
+    InvalidPersistentKeepaliveInterval(#[source] ParseIntError),
+    #[error(transparent)]
+    InvalidAllowedIp(#[from] ParseAllowedIpError),
+    #[error("Expected `{0}=true`. Observed value: `{1}`")]
+    InvalidFlag(SetKey, String),
+    #[error("Unsupported protocol_version: `{0}`")]
+    UnsupportedProtocolVersion(String),
+
+    // Invalid parser state transition errors
+    #[error("Observed peer-level key `{0}` before public_key was specified")]
+    PeerLevelKeyBeforePublicKey(SetKey),
+    #[error("Observed interface-level key `{0}` after a peer-level key")]
+    InterfaceLevelKeyAfterPeerLevelKey(SetKey),
+    #[error("public_key `{0}` was specified more than once")]
+    DuplicatePublicKey(String),
+}
+
+impl From<ParseKeyError> for ParseSetRequestError {
+    fn from(err: ParseKeyError) -> Self {
+        Self::UnknownKey(err.unknown_key)
+    }
+}
+
+/// Parses the body of a `set=1` request, the inverse of
+/// [`set::Device`]'s `Display` implementation. Lines are read up to and
+/// including the empty line that ends the request.
+///
+/// The rules of the [protocol] are enforced: interface-level keys come before
+/// the first `public_key`, peer-level keys come after one, and a public key
+/// may not repeat within a request.
+///
+/// [protocol]: https://www.wireguard.com/xplatform/#configuration-protocol
+pub fn parse_set(
+    lines: impl Iterator<Item = Result<String, std::io::Error>>,
+) -> Result<set::Device, ParseSetRequestError> {
+    let parse_state = lines
+        .take_until(|result| matches!(result.as_ref().map(String::as_str), Ok("")))
+        .try_fold(ParseSetState::default(), process_line)?;
+
+    if parse_state.finished {
+        Ok(parse_state.device)
+    } else {
+        Err(ParseSetRequestError::MissingEndOfRequestNewline)
+    }
+}
+
+/// Unlike get responses, every key of a set request is optional, so the
+/// request is built up directly instead of through builders.
+#[derive(Default)]
+struct ParseSetState {
+    device: set::Device,
+    /// Public keys of `device.peers`, to detect repeats.
+    public_keys: HashSet<[u8; 32]>,
+    /// Whether the empty line ending the request was observed.
+    finished: bool,
+}
+
+fn process_line(
+    mut state: ParseSetState,
+    line: std::io::Result<String>,
+) -> Result<ParseSetState, ParseSetRequestError> {
+    type ParseErr = ParseSetRequestError;
+
+    let line = line.map_err(ParseErr::ReadLineIoError)?;
+
+    // An empty line signifies the end of a "set" request.
+    if line.is_empty() {
+        state.finished = true;
+        return Ok(state);
+    }
+
+    let (key, raw_val) = {
+        let mut tokens = line.trim().splitn(2, '=');
+
+        // The first token should always exist.
+        let raw_key = tokens.next().unwrap();
+        let key = SetKey::from_str(raw_key)?;
+
+        let raw_val = match tokens.next() {
+            Some(val) => val,
+            None => return Err(ParseErr::MissingValueForKey(key)),
+        };
+
+        (key, raw_val)
+    };
+
+    let device = &mut state.device;
+    match (key, device.peers.last_mut()) {
+        (
+            key @ (SetKey::PrivateKey | SetKey::ListenPort | SetKey::Fwmark | SetKey::ReplacePeers),
+            Some(_),
+        ) => return Err(ParseErr::InterfaceLevelKeyAfterPeerLevelKey(key)),
+
+        (SetKey::PrivateKey, None) => {
+            let private_key = parse_key(raw_val).ok_or(ParseErr::InvalidPrivateKey)?;
+            device.private_key = Some(private_key);
+        }
+        (SetKey::ListenPort, None) => {
+            let listen_port = raw_val.parse().map_err(ParseErr::InvalidListenPort)?;
+            device.listen_port = Some(listen_port);
+        }
+        (SetKey::Fwmark, None) => {
+            let fwmark = raw_val.parse().map_err(ParseErr::InvalidFwmark)?;
+            device.fwmark = Some(fwmark);
+        }
+        (SetKey::ReplacePeers, None) => {
+            device.replace_peers = Some(parse_flag(SetKey::ReplacePeers, raw_val)?);
+        }
+
+        // A public_key entry specifies the start of a new peer block.
+        (SetKey::PublicKey, _) => {
+            let public_key = parse_key(raw_val)
+                .ok_or_else(|| ParseErr::InvalidPublicKey(raw_val.to_string()))?;
+            if !state.public_keys.insert(public_key) {
+                return Err(ParseErr::DuplicatePublicKey(raw_val.to_string()));
+            }
+            device.peers.push(set::Peer::from_public_key(public_key));
+        }
+
+        (key, None) => return Err(ParseErr::PeerLevelKeyBeforePublicKey(key)),
+
+        (SetKey::Remove, Some(peer)) => {
+            peer.remove = Some(parse_flag(SetKey::Remove, raw_val)?);
+        }
+        (SetKey::UpdateOnly, Some(peer)) => {
+            peer.update_only = Some(parse_flag(SetKey::UpdateOnly, raw_val)?);
+        }
+        (SetKey::PresharedKey, Some(peer)) => {
+            let preshared_key = parse_key(raw_val).ok_or(ParseErr::InvalidPresharedKey)?;
+            peer.preshared_key = Some(preshared_key);
+        }
+        (SetKey::Endpoint, Some(peer)) => {
+            let endpoint = raw_val.parse().map_err(ParseErr::InvalidEndpoint)?;
+            peer.endpoint = Some(endpoint);
+        }
+        (SetKey::PersistentKeepaliveInterval, Some(peer)) => {
+            let interval = raw_val
+                .parse()
+                .map_err(ParseErr::InvalidPersistentKeepaliveInterval)?;
+            peer.persistent_keepalive_interval = Some(interval);
+        }
+        (SetKey::ReplaceAllowedIps, Some(peer)) => {
+            peer.replace_allowed_ips = Some(parse_flag(SetKey::ReplaceAllowedIps, raw_val)?);
+        }
+        (SetKey::AllowedIp, Some(peer)) => {
+            let allowed_ip: get::AllowedIp = raw_val.parse()?;
+            peer.allowed_ips.push(set::AllowedIp {
+                ipaddr: allowed_ip.ipaddr,
+                cidr_mask: allowed_ip.cidr_mask,
+            });
+        }
+        // Version 1 is the only version of the protocol.
+        (SetKey::ProtocolVersion, Some(peer)) => {
+            if raw_val != "1" {
+                return Err(ParseErr::UnsupportedProtocolVersion(raw_val.to_string()));
+            }
+            peer.protocol_version = Some(1);
+        }
+    }
+
+    Ok(state)
+}
+
+fn parse_key(raw_val: &str) -> Option<[u8; 32]> {
+    hex::decode(raw_val)
+        .ok()
+        .and_then(|buf| parse_device_key(&buf))
+}
+
+/// Flags only have a `true` value. Leaving them out is how they're unset.
+fn parse_flag(key: SetKey, raw_val: &str) -> Result<bool, ParseSetRequestError> {
+    match raw_val {
+        "true" => Ok(true),
+        _ => Err(ParseSetRequestError::InvalidFlag(key, raw_val.to_string())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_set, ParseSetRequestError};
+    use crate::xplatform::set;
+
+    fn parse_str(request: &str) -> Result<set::Device, ParseSetRequestError> {
+        parse_set(request.lines().map(String::from).map(Ok))
+    }
+
+    #[test]
+    fn parse_website_example() -> anyhow::Result<()> {
+        let request = "\
+            private_key=e84b5a6d2717c1003a13b431570353dbaca9146cf150c5f8575680feba52027a\n\
+            listen_port=12912\n\
+            fwmark=0\n\
+            replace_peers=true\n\
+            public_key=b85996fecc9c7f1fc6d2572a76eda11d59bcd20be8e543b15ce4bd85a8e75a33\n\
+            preshared_key=188515093e952f5f22e865cef3012e72f8b5f0b598ac0309d5dacce3b70fcf52\n\
+            replace_allowed_ips=true\n\
+            allowed_ip=192.168.4.4/32\n\
+            endpoint=[abcd:23::33%2]:51820\n\
+            public_key=58402e695ba1772b1cc9309755f043251ea77fdcf10fbe63989ceb7e19321376\n\
+            replace_allowed_ips=true\n\
+            allowed_ip=192.168.4.6/32\n\
+            persistent_keepalive_interval=111\n\
+            endpoint=182.122.22.19:3233\n\
+            public_key=662e14fd594556f522604703340351258903b64f35553763f19426ab2a515c58\n\
+            endpoint=5.152.198.39:51820\n\
+            replace_allowed_ips=true\n\
+            allowed_ip=192.168.4.10/32\n\
+            allowed_ip=192.168.4.11/32\n\
+            public_key=e818b58db5274087fcc1be5dc728cf53d3b5726b4cef6b9bab8f8f8c2452c25c\n\
+            remove=true\n\
+            \n";
+
+        let device = parse_str(request)?;
+        assert_eq!(device.listen_port, Some(12912));
+        assert_eq!(device.fwmark, Some(0));
+        assert_eq!(device.replace_peers, Some(true));
+        assert_eq!(device.peers.len(), 4);
+        assert_eq!(device.peers[1].persistent_keepalive_interval, Some(111));
+        assert_eq!(device.peers[2].allowed_ips.len(), 2);
+        assert_eq!(device.peers[3].remove, Some(true));
+
+        // The request lists peer keys in a different order than Display, so
+        // compare against a serialized and parsed copy instead of the text.
+        let serialized = format!("{}\n", device);
+        assert_eq!(parse_str(&serialized)?, device);
+
+        Ok(())
+    }
+
+    #[test]
+    fn parse_empty_request() -> anyhow::Result<()> {
+        assert_eq!(parse_str("\n")?, set::Device::default());
+        assert!(matches!(
+            parse_str(""),
+            Err(ParseSetRequestError::MissingEndOfRequestNewline)
+        ));
+        assert!(matches!(
+            parse_str("listen_port=1\n"),
+            Err(ParseSetRequestError::MissingEndOfRequestNewline)
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn parse_stops_at_end_of_request() -> anyhow::Result<()> {
+        let mut lines = "fwmark=1\n\nget=1\n\n".lines().map(String::from).map(Ok);
+        let device = parse_set(&mut lines)?;
+        assert_eq!(device.fwmark, Some(1));
+        assert_eq!(lines.next().transpose()?.as_deref(), Some("get=1"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn parse_rejects_out_of_order_keys() {
+        let public_key =
+            "public_key=b85996fecc9c7f1fc6d2572a76eda11d59bcd20be8e543b15ce4bd85a8e75a33";
+
+        assert!(matches!(
+            parse_str("allowed_ip=10.0.0.1/32\n\n"),
+            Err(ParseSetRequestError::PeerLevelKeyBeforePublicKey(_))
+        ));
+        assert!(matches!(
+            parse_str("listen_port=1\nremove=true\n\n"),
+            Err(ParseSetRequestError::PeerLevelKeyBeforePublicKey(_))
+        ));
+        assert!(matches!(
+            parse_str(&format!("{}\nlisten_port=1\n\n", public_key)),
+            Err(ParseSetRequestError::InterfaceLevelKeyAfterPeerLevelKey(_))
+        ));
+        assert!(matches!(
+            parse_str(&format!("{0}\n{0}\n\n", public_key)),
+            Err(ParseSetRequestError::DuplicatePublicKey(_))
+        ));
+    }
+
+    #[test]
+    fn parse_rejects_invalid_values() {
+        let public_key =
+            "public_key=b85996fecc9c7f1fc6d2572a76eda11d59bcd20be8e543b15ce4bd85a8e75a33";
+
+        assert!(matches!(
+            parse_str("private_key=abcd\n\n"),
+            Err(ParseSetRequestError::InvalidPrivateKey)
+        ));
+        assert!(matches!(
+            parse_str("listen_port=65536\n\n"),
+            Err(ParseSetRequestError::InvalidListenPort(_))
+        ));
+        assert!(matches!(
+            parse_str("replace_peers=false\n\n"),
+            Err(ParseSetRequestError::InvalidFlag(_, _))
+        ));
+        assert!(matches!(
+            parse_str("listen_port\n\n"),
+            Err(ParseSetRequestError::MissingValueForKey(_))
+        ));
+        assert!(matches!(
+            parse_str("rx_bytes=1\n\n"),
+            Err(ParseSetRequestError::UnknownKey(_))
+        ));
+        assert!(matches!(
+            parse_str(&format!("{}\nallowed_ip=10.0.0.1\n\n", public_key)),
+            Err(ParseSetRequestError::InvalidAllowedIp(_))
+        ));
+        assert!(matches!(
+            parse_str(&format!("{}\nprotocol_version=2\n\n", public_key)),
+            Err(ParseSetRequestError::UnsupportedProtocolVersion(_))
+        ));
+    }
+}
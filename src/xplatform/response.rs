@@ -0,0 +1,216 @@
+use crate::get;
+use crate::xplatform::protocol::GetKey;
+use std::fmt::Display;
+
+/// Renders a [`get::Device`] as the response to a `get=1` operation, the
+/// inverse of [`parse`](crate::xplatform::parser::parse). This allows devices
+/// read through other means, such as the Linux netlink API, to be served to
+/// tools that only speak the cross-platform protocol.
+///
+/// Keys are written in the order [wireguard-go] uses. `listen_port` is always
+/// written since a response has to start with either `private_key` or
+/// `listen_port`. `fwmark` and `endpoint` are left out when they're unset. The
+/// `ifindex`, `ifname` and `public_key` fields of the device aren't part of the
+/// protocol and are ignored.
+///
+/// The response ends with `errno=0` and the empty line that terminates it.
+///
+/// [wireguard-go]: https://git.zx2c4.com/wireguard-go/tree/device/uapi.go
+pub struct GetResponse<'a> {
+    device: &'a get::Device,
+}
+
+impl<'a> GetResponse<'a> {
+    pub fn new(device: &'a get::Device) -> Self {
+        Self { device }
+    }
+}
+
+impl<'a> From<&'a get::Device> for GetResponse<'a> {
+    fn from(device: &'a get::Device) -> Self {
+        Self::new(device)
+    }
+}
+
+impl Display for GetResponse<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let device = self.device;
+
+        if let Some(private_key) = device.private_key {
+            let private_key = hex::encode(private_key);
+            writeln!(f, "{}={}", GetKey::PrivateKey, private_key)?;
+        }
+
+        writeln!(f, "{}={}", GetKey::ListenPort, device.listen_port)?;
+
+        if device.fwmark != 0 {
+            writeln!(f, "{}={}", GetKey::Fwmark, device.fwmark)?;
+        }
+
+        for peer in &device.peers {
+            write_peer(f, peer)?;
+        }
+
+        writeln!(f, "{}=0", GetKey::Errno)?;
+        writeln!(f)
+    }
+}
+
+fn write_peer(f: &mut std::fmt::Formatter<'_>, peer: &get::Peer) -> std::fmt::Result {
+    writeln!(f, "{}={}", GetKey::PublicKey, hex::encode(peer.public_key))?;
+    let preshared_key = hex::encode(peer.preshared_key);
+    writeln!(f, "{}={}", GetKey::PresharedKey, preshared_key)?;
+    writeln!(f, "{}={}", GetKey::ProtocolVersion, peer.protocol_version)?;
+
+    if let Some(endpoint) = peer.endpoint {
+        writeln!(f, "{}={}", GetKey::Endpoint, endpoint)?;
+    }
+
+    let last_handshake_time = peer.last_handshake_time;
+    writeln!(
+        f,
+        "{}={}",
+        GetKey::LastHandshakeTimeSec,
+        last_handshake_time.as_secs()
+    )?;
+    writeln!(
+        f,
+        "{}={}",
+        GetKey::LastHandshakeTimeNsec,
+        last_handshake_time.subsec_nanos()
+    )?;
+    writeln!(f, "{}={}", GetKey::TxBytes, peer.tx_bytes)?;
+    writeln!(f, "{}={}", GetKey::RxBytes, peer.rx_bytes)?;
+    writeln!(
+        f,
+        "{}={}",
+        GetKey::PersistentKeepaliveInterval,
+        peer.persistent_keepalive_interval
+    )?;
+
+    for allowed_ip in &peer.allowed_ips {
+        writeln!(
+            f,
+            "{}={}/{}",
+            GetKey::AllowedIp,
+            allowed_ip.ipaddr,
+            allowed_ip.cidr_mask
+        )?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::GetResponse;
+    use crate::get;
+    use crate::xplatform::parser::parse;
+    use std::convert::TryInto;
+    use std::time::Duration;
+
+    #[test]
+    fn serialize_basic() -> anyhow::Result<()> {
+        let expected = "\
+            private_key=18aa10c05a531f5c537a18426b376387fc2cbd701ae1b9b4271e327aaade9d4f\n\
+            listen_port=56137\n\
+            fwmark=51820\n\
+            public_key=913ea0e20e28c12b5c5f5a858b93a05e686dc3ce524e16f3143bbb1023679751\n\
+            preshared_key=0000000000000000000000000000000000000000000000000000000000000000\n\
+            protocol_version=1\n\
+            endpoint=192.168.64.73:51820\n\
+            last_handshake_time_sec=1590459201\n\
+            last_handshake_time_nsec=283546000\n\
+            tx_bytes=824\n\
+            rx_bytes=696\n\
+            persistent_keepalive_interval=110\n\
+            allowed_ip=10.24.24.3/32\n\
+            allowed_ip=fd00::/64\n\
+            public_key=58402e695ba1772b1cc9309755f043251ea77fdcf10fbe63989ceb7e19321376\n\
+            preshared_key=0000000000000000000000000000000000000000000000000000000000000000\n\
+            protocol_version=1\n\
+            last_handshake_time_sec=0\n\
+            last_handshake_time_nsec=0\n\
+            tx_bytes=0\n\
+            rx_bytes=0\n\
+            persistent_keepalive_interval=0\n\
+            errno=0\n\
+            \n";
+
+        let device = get::Device {
+            ifindex: 0,
+            ifname: "".to_string(),
+            private_key: Some(
+                hex::decode("18aa10c05a531f5c537a18426b376387fc2cbd701ae1b9b4271e327aaade9d4f")?
+                    .try_into()
+                    .unwrap(),
+            ),
+            public_key: None,
+            listen_port: 56137,
+            fwmark: 51820,
+            peers: vec![
+                get::Peer {
+                    public_key: hex::decode(
+                        "913ea0e20e28c12b5c5f5a858b93a05e686dc3ce524e16f3143bbb1023679751",
+                    )?
+                    .try_into()
+                    .unwrap(),
+                    preshared_key: [0u8; 32],
+                    endpoint: Some("192.168.64.73:51820".parse()?),
+                    last_handshake_time: Duration::new(1_590_459_201, 283_546_000),
+                    tx_bytes: 824,
+                    rx_bytes: 696,
+                    persistent_keepalive_interval: 110,
+                    allowed_ips: vec!["10.24.24.3/32".parse()?, "fd00::/64".parse()?],
+                    protocol_version: 1,
+                },
+                get::Peer {
+                    public_key: hex::decode(
+                        "58402e695ba1772b1cc9309755f043251ea77fdcf10fbe63989ceb7e19321376",
+                    )?
+                    .try_into()
+                    .unwrap(),
+                    preshared_key: [0u8; 32],
+                    endpoint: None,
+                    last_handshake_time: Duration::new(0, 0),
+                    tx_bytes: 0,
+                    rx_bytes: 0,
+                    persistent_keepalive_interval: 0,
+                    allowed_ips: vec![],
+                    protocol_version: 1,
+                },
+            ],
+        };
+
+        let actual = GetResponse::new(&device).to_string();
+        assert_eq!(actual, expected);
+
+        let parsed = parse(actual.lines().map(String::from).map(Ok))?;
+        assert_eq!(parsed, device);
+
+        Ok(())
+    }
+
+    #[test]
+    fn serialize_device_without_keys() -> anyhow::Result<()> {
+        let device = get::Device {
+            ifindex: 4,
+            ifname: "wgtest0".to_string(),
+            private_key: None,
+            public_key: None,
+            listen_port: 0,
+            fwmark: 0,
+            peers: vec![],
+        };
+
+        let actual = GetResponse::from(&device).to_string();
+        assert_eq!(actual, "listen_port=0\nerrno=0\n\n");
+
+        // The interface name and index aren't part of the protocol.
+        let parsed = parse(actual.lines().map(String::from).map(Ok))?;
+        assert_eq!(parsed.ifname, "");
+        assert_eq!(parsed.listen_port, 0);
+
+        Ok(())
+    }
+}
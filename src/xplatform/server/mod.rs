@@ -0,0 +1,365 @@
+//! The server side of the cross-platform configuration protocol, for programs
+//! that embed a userspace WireGuard implementation and have to answer
+//! [`Client`](crate::xplatform::Client) requests themselves.
+//!
+//! A [`Server`] accepts connections on a unix socket, which by convention lives
+//! at `/var/run/wireguard/<ifname>.sock`. Each `get=1` and `set=1` operation is
+//! handed to a [`Handler`], and its result is written back the way
+//! [wireguard-go] does it. A connection may carry any number of operations.
+//!
+//! [wireguard-go]: https://git.zx2c4.com/wireguard-go/tree/device/uapi.go
+
+mod request;
+mod response;
+
+use crate::get;
+use crate::xplatform::set;
+use request::{read_line, read_set_request};
+use response::{write_errno, write_get_response};
+use std::io::{self, BufReader, BufWriter, Write};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::Path;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::thread;
+
+/// Applies the operations a [`Server`] receives to a WireGuard device.
+///
+/// Failures are reported to the client as `errno=-N`. Errors created from a raw
+/// OS error, such as `io::Error::from_raw_os_error(libc::EADDRINUSE)`, keep
+/// their errno. Other errors are mapped from their [`io::ErrorKind`].
+pub trait Handler {
+    /// Answers a `get=1` operation with the current state of the device. Only
+    /// the keys of the protocol are sent, so `ifindex`, `ifname` and
+    /// `public_key` are ignored.
+    fn get(&mut self) -> io::Result<get::Device>;
+
+    /// Applies a `set=1` operation to the device.
+    fn set(&mut self, device: set::Device) -> io::Result<()>;
+}
+
+/// Serves the configuration protocol for a single device on a unix socket.
+///
+/// Every connection is handled on its own thread, so a client that keeps its
+/// connection open doesn't hold up the others. The [`Handler`] is locked for
+/// one operation at a time and never sees concurrent operations.
+pub struct Server<H> {
+    listener: UnixListener,
+    handler: Arc<Mutex<H>>,
+}
+
+impl<H: Handler> Server<H> {
+    /// Creates the socket file at `path` and listens on it.
+    pub fn bind<P: AsRef<Path>>(path: P, handler: H) -> io::Result<Self> {
+        Ok(Self::from_listener(UnixListener::bind(path)?, handler))
+    }
+
+    pub fn from_listener(listener: UnixListener, handler: H) -> Self {
+        Self {
+            listener,
+            handler: Arc::new(Mutex::new(handler)),
+        }
+    }
+
+    pub fn listener(&self) -> &UnixListener {
+        &self.listener
+    }
+
+    /// Locks the handler. Operations on every connection wait until the guard
+    /// is dropped.
+    pub fn handler(&self) -> MutexGuard<'_, H> {
+        self.handler.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Handles operations on `stream` until the client closes it or sends an
+    /// unknown operation. This blocks the calling thread for as long as the
+    /// client keeps the connection open.
+    pub fn handle_connection(&self, stream: UnixStream) -> io::Result<()> {
+        handle_connection(&self.handler, stream)
+    }
+}
+
+impl<H: Handler + Send + 'static> Server<H> {
+    /// Accepts connections until accepting fails, and handles each of them on
+    /// a new thread. Errors on an individual connection only end that
+    /// connection.
+    pub fn serve(&self) -> io::Result<()> {
+        loop {
+            let (stream, _) = self.listener.accept()?;
+            let handler = Arc::clone(&self.handler);
+            thread::spawn(move || {
+                // The client went away or sent something that isn't part of
+                // the protocol. Either way, there's nobody left to report it
+                // to.
+                let _ = handle_connection(&handler, stream);
+            });
+        }
+    }
+}
+
+fn handle_connection<H: Handler>(handler: &Mutex<H>, stream: UnixStream) -> io::Result<()> {
+    let mut reader = BufReader::new(&stream);
+    let mut writer = BufWriter::new(&stream);
+
+    while let Some(line) = read_line(&mut reader)? {
+        match line.as_str() {
+            "get=1" => {
+                // The operation line is followed by an empty line.
+                if read_line(&mut reader)?.as_deref() != Some("") {
+                    write_errno(&mut writer, libc::EINVAL)?;
+                    return writer.flush();
+                }
+
+                match lock(handler).and_then(|mut handler| handler.get()) {
+                    Ok(device) => write_get_response(&mut writer, &device)?,
+                    Err(err) => write_errno(&mut writer, errno(&err))?,
+                }
+            }
+            "set=1" => {
+                let errno = match read_set_request(&mut reader)? {
+                    Some(Ok(device)) => lock(handler)
+                        .and_then(|mut handler| handler.set(device))
+                        .err()
+                        .map_or(0, |err| errno(&err)),
+                    Some(Err(_)) => libc::EINVAL,
+                    // The connection was closed in the middle of the request,
+                    // so it's not applied.
+                    None => return Ok(()),
+                };
+                write_errno(&mut writer, errno)?;
+            }
+            _ => return Ok(()),
+        }
+
+        writer.flush()?;
+    }
+
+    Ok(())
+}
+
+/// A handler that panicked may have been left in any state, so later
+/// operations fail with `EIO` instead of being applied to it.
+fn lock<H>(handler: &Mutex<H>) -> io::Result<MutexGuard<'_, H>> {
+    handler
+        .lock()
+        .map_err(|_| io::Error::from_raw_os_error(libc::EIO))
+}
+
+/// The errno reported for a failed operation.
+fn errno(err: &io::Error) -> i32 {
+    if let Some(errno) = err.raw_os_error() {
+        return errno;
+    }
+
+    match err.kind() {
+        io::ErrorKind::InvalidInput | io::ErrorKind::InvalidData => libc::EINVAL,
+        io::ErrorKind::NotFound => libc::ENOENT,
+        io::ErrorKind::AddrInUse => libc::EADDRINUSE,
+        io::ErrorKind::PermissionDenied => libc::EPERM,
+        _ => libc::EIO,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::xplatform::error::{ServerError, SetDeviceError};
+    use crate::xplatform::fake::FakeDevice;
+    use crate::xplatform::Client;
+    use std::io::Read;
+    use std::net::Shutdown;
+    use std::time::Duration;
+
+    fn device() -> get::Device {
+        get::Device {
+            ifindex: 0,
+            ifname: "".to_string(),
+            private_key: Some([1u8; 32]),
+            public_key: None,
+            listen_port: 51820,
+            fwmark: 7,
+            peers: vec![get::Peer {
+                public_key: [2u8; 32],
+                preshared_key: [3u8; 32],
+                endpoint: Some("[::1]:51820".parse().unwrap()),
+                persistent_keepalive_interval: 25,
+                last_handshake_time: Duration::new(1_590_459_201, 283_546_000),
+                rx_bytes: 696,
+                tx_bytes: 824,
+                allowed_ips: vec![
+                    "10.24.24.3/32".parse().unwrap(),
+                    "fd00::/64".parse().unwrap(),
+                ],
+                protocol_version: 1,
+            }],
+        }
+    }
+
+    /// Serves `connections` connections in the background, one after another,
+    /// and returns the server afterwards.
+    fn spawn_server(
+        path: &Path,
+        handler: FakeDevice,
+        connections: usize,
+    ) -> io::Result<std::thread::JoinHandle<Server<FakeDevice>>> {
+        let server = Server::bind(path, handler)?;
+        Ok(std::thread::spawn(move || {
+            for _ in 0..connections {
+                let (stream, _) = server.listener().accept().unwrap();
+                server.handle_connection(stream).unwrap();
+            }
+            server
+        }))
+    }
+
+    #[test]
+    fn client_round_trip() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("wgtest0.sock");
+        let mut handler = FakeDevice::from_device(device());
+        handler.occupy_port(51822);
+        let server = spawn_server(&path, handler, 3)?;
+
+        let client = Client::create(&path);
+        assert_eq!(client.get()?, device());
+
+        let request = set::Device {
+            listen_port: Some(51821),
+            replace_peers: Some(true),
+            peers: vec![set::Peer::from_public_key([4u8; 32])
+                .update_only(true)
+                .endpoint("192.0.2.1:51820".parse()?)
+                .allowed_ips(vec![set::AllowedIp {
+                    ipaddr: "10.0.0.0".parse()?,
+                    cidr_mask: 8,
+                }])],
+            ..Default::default()
+        };
+        client.set(request)?;
+
+        let err = client
+            .set(set::Device {
+                listen_port: Some(51822),
+                ..Default::default()
+            })
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            SetDeviceError::ServerError(ServerError::AddrInUse)
+        ));
+
+        let server = server.join().unwrap();
+        let handler = server.handler();
+        let requests = handler.requests();
+        assert_eq!(requests.len(), 2);
+        assert_eq!(requests[0].listen_port, Some(51821));
+        assert_eq!(requests[0].replace_peers, Some(true));
+        assert_eq!(requests[0].peers[0].update_only, Some(true));
+        assert_eq!(requests[0].peers[0].allowed_ips.len(), 1);
+        assert_eq!(handler.device().unwrap().listen_port, 51821);
+
+        Ok(())
+    }
+
+    #[test]
+    fn several_operations_on_one_connection() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("wgtest0.sock");
+        let mut handler = FakeDevice::new();
+        handler.remove();
+        let server = spawn_server(&path, handler, 1)?;
+
+        let mut stream = UnixStream::connect(&path)?;
+        stream.write_all(b"get=1\n\nset=1\nlisten_port=abc\n\nset=1\nfwmark=1\n\nget=1\n\n")?;
+        stream.shutdown(Shutdown::Write)?;
+        let mut response = String::new();
+        stream.read_to_string(&mut response)?;
+
+        // Errors are written negated, the way wireguard-go writes them.
+        assert_eq!(
+            response,
+            format!(
+                "errno=-{}\n\nerrno=-{}\n\nerrno=-{}\n\nerrno=-{}\n\n",
+                libc::ENOENT,
+                libc::EINVAL,
+                libc::ENOENT,
+                libc::ENOENT
+            )
+        );
+        let server = server.join().unwrap();
+        let handler = server.handler();
+        assert_eq!(handler.requests().len(), 1);
+        assert_eq!(handler.requests()[0].fwmark, Some(1));
+
+        Ok(())
+    }
+
+    #[test]
+    fn invalid_crlf_request_is_skipped() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("wgtest0.sock");
+        let server = spawn_server(&path, FakeDevice::new(), 1)?;
+
+        let mut stream = UnixStream::connect(&path)?;
+        stream
+            .write_all(b"set=1\r\nlisten_port=abc\r\nfwmark=2\r\n\r\nset=1\r\nfwmark=1\r\n\r\n")?;
+        stream.shutdown(Shutdown::Write)?;
+        let mut response = String::new();
+        stream.read_to_string(&mut response)?;
+
+        assert_eq!(response, format!("errno=-{}\n\nerrno=0\n\n", libc::EINVAL));
+        let server = server.join().unwrap();
+        let handler = server.handler();
+        assert_eq!(handler.requests().len(), 1);
+        assert_eq!(handler.device().unwrap().fwmark, 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn unknown_operation_closes_connection() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("wgtest0.sock");
+        let server = spawn_server(&path, FakeDevice::new(), 1)?;
+
+        let mut stream = UnixStream::connect(&path)?;
+        stream.write_all(b"list=1\n\nget=1\n\n")?;
+        let mut response = String::new();
+        stream.read_to_string(&mut response)?;
+
+        assert_eq!(response, "");
+        server.join().unwrap();
+
+        Ok(())
+    }
+
+    #[test]
+    fn overlong_line_closes_connection() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("wgtest0.sock");
+        let server = Server::bind(&path, FakeDevice::new())?;
+
+        let mut stream = UnixStream::connect(&path)?;
+        stream.write_all(b"set=1\nlisten_port=")?;
+        stream.write_all(&[b'1'; 8192])?;
+        let (accepted, _) = server.listener().accept()?;
+
+        let err = server.handle_connection(accepted).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert!(server.handler().requests().is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn idle_connection_does_not_block_others() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("wgtest0.sock");
+        let server = Server::bind(&path, FakeDevice::from_device(device()))?;
+        std::thread::spawn(move || server.serve());
+
+        let _idle = UnixStream::connect(&path)?;
+        assert_eq!(Client::create(&path).get()?, device());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,68 @@
+use crate::xplatform::parser::{parse_set, ParseSetRequestError};
+use crate::xplatform::set;
+use std::io::{self, BufRead, Read};
+
+/// Reads the key/value lines of a set request up to and including the empty
+/// line that ends it. Returns `None` if the connection is closed before that
+/// line arrives.
+///
+/// The whole request is read even if one of its lines is invalid, so the next
+/// operation on the connection starts at the right place.
+pub(super) fn read_set_request(
+    reader: &mut impl BufRead,
+) -> io::Result<Option<Result<set::Device, ParseSetRequestError>>> {
+    match parse_set(lines(reader)) {
+        Ok(device) => Ok(Some(Ok(device))),
+        Err(ParseSetRequestError::ReadLineIoError(err)) => Err(err),
+        Err(ParseSetRequestError::MissingEndOfRequestNewline) => Ok(None),
+        Err(err) => drain(reader).map(|complete| complete.then_some(Err(err))),
+    }
+}
+
+/// Skips the rest of a request. Returns whether its end was found.
+fn drain(reader: &mut impl BufRead) -> io::Result<bool> {
+    for line in lines(reader) {
+        if line?.is_empty() {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+/// Far longer than any line of a valid request, which are at most a key and a
+/// hex-encoded key or an IPv6 endpoint long.
+const MAX_LINE_LEN: usize = 4096;
+
+/// Reads a line without its `\n` or `\r\n` ending, like [`BufRead::lines`].
+/// Returns `None` once the connection is closed. Lines longer than
+/// [`MAX_LINE_LEN`] bytes including their ending fail with
+/// [`io::ErrorKind::InvalidData`], so a client can't make the server buffer
+/// without bound.
+///
+/// Operation lines, the lines of a set request and the lines skipped after an
+/// invalid one are all read this way, so they agree on where a request ends.
+pub(super) fn read_line(reader: &mut impl BufRead) -> io::Result<Option<String>> {
+    let mut line = String::new();
+    let mut limited = reader.by_ref().take(MAX_LINE_LEN as u64 + 1);
+    if limited.read_line(&mut line)? == 0 {
+        return Ok(None);
+    }
+    if line.len() > MAX_LINE_LEN {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Line is longer than {} bytes", MAX_LINE_LEN),
+        ));
+    }
+
+    if line.ends_with('\n') {
+        line.pop();
+        if line.ends_with('\r') {
+            line.pop();
+        }
+    }
+    Ok(Some(line))
+}
+
+fn lines(reader: &mut impl BufRead) -> impl Iterator<Item = io::Result<String>> + '_ {
+    std::iter::from_fn(move || read_line(reader).transpose())
+}
@@ -0,0 +1,16 @@
+use crate::get;
+use crate::xplatform::protocol::GetKey;
+use crate::xplatform::GetResponse;
+use std::io::{self, Write};
+
+/// Writes a successful get response, which ends with `errno=0`.
+pub(super) fn write_get_response(writer: &mut impl Write, device: &get::Device) -> io::Result<()> {
+    write!(writer, "{}", GetResponse::new(device))
+}
+
+/// Writes the `errno=N` line that ends every response, followed by the empty
+/// line that terminates it. Like wireguard-go, errors are written as the
+/// negated errno, such as `errno=-22` for `EINVAL`.
+pub(super) fn write_errno(writer: &mut impl Write, errno: i32) -> io::Result<()> {
+    writeln!(writer, "{}={}\n", GetKey::Errno, errno.wrapping_neg())
+}
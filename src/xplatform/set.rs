@@ -5,7 +5,7 @@ use std::net::SocketAddr;
 
 /// Documentation of each field comes from:
 /// https://www.wireguard.com/xplatform/#configuration-protocol
-#[derive(Debug, Default, PartialEq, Eq)]
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
 pub struct Device {
     /// The value for this key should be a lowercase hex-encoded private key of
     /// the interface. The value may be an all zero string in the case of a set
@@ -30,27 +30,49 @@ pub struct Device {
     pub peers: Vec<Peer>,
 }
 
+impl Device {
+    /// The interface-level keys of the request, without its peers. This lets
+    /// peers be written one at a time after them.
+    pub(crate) fn interface_keys(&self) -> InterfaceKeys<'_> {
+        InterfaceKeys(self)
+    }
+}
+
 impl Display for Device {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        if let Some(private_key) = self.private_key {
+        self.interface_keys().fmt(f)?;
+
+        for peer in &self.peers {
+            peer.fmt(f)?;
+        }
+
+        Ok(())
+    }
+}
+
+pub(crate) struct InterfaceKeys<'a>(&'a Device);
+
+impl Display for InterfaceKeys<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let device = self.0;
+
+        if let Some(private_key) = device.private_key {
             let private_key = hex::encode(private_key);
             writeln!(f, "{}={}", SetKey::PrivateKey, private_key)?;
         }
 
-        if let Some(listen_port) = self.listen_port {
+        if let Some(listen_port) = device.listen_port {
             writeln!(f, "{}={}", SetKey::ListenPort, listen_port)?;
         }
 
-        if let Some(fwmark) = self.fwmark {
+        if let Some(fwmark) = device.fwmark {
             writeln!(f, "{}={}", SetKey::Fwmark, fwmark)?;
         }
 
-        if let Some(replace_peers) = self.replace_peers {
-            writeln!(f, "{}={}", SetKey::ReplacePeers, replace_peers)?;
-        }
-
-        for peer in &self.peers {
-            peer.fmt(f)?;
+        // Flags can only be set to `true` in the protocol, so `false` is
+        // left out like `None`.
+        if device.replace_peers == Some(true) {
+            writeln!(f, "{}=true", SetKey::ReplacePeers)?;
         }
 
         Ok(())
@@ -102,9 +124,20 @@ pub struct Peer {
     /// as part of a prior peer, the allowed IP entry will be removed from that
     /// peer and added to this peer.
     pub allowed_ips: Vec<AllowedIp>,
+
+    /// The value for this should be set to 1 if present. It selects the
+    /// version of the protocol used with the previously added peer entry. The
+    /// most recent version is used if this is left out.
+    pub protocol_version: Option<u32>,
 }
 
 impl Peer {
+    /// The peer-level keys of the peer, without its allowed IPs. This lets
+    /// allowed IPs be written one at a time after them.
+    pub(crate) fn peer_keys(&self) -> PeerKeys<'_> {
+        PeerKeys(self)
+    }
+
     pub fn from_public_key(public_key: [u8; 32]) -> Self {
         Self {
             public_key,
@@ -115,6 +148,7 @@ impl Peer {
             persistent_keepalive_interval: None,
             replace_allowed_ips: None,
             allowed_ips: vec![],
+            protocol_version: None,
         }
     }
 
@@ -152,39 +186,60 @@ impl Peer {
         self.allowed_ips = allowed_ips;
         self
     }
+
+    pub fn protocol_version(mut self, protocol_version: u32) -> Self {
+        self.protocol_version = Some(protocol_version);
+        self
+    }
 }
 
 impl Display for Peer {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        writeln!(f, "{}={}", SetKey::PublicKey, hex::encode(self.public_key))?;
+        self.peer_keys().fmt(f)?;
 
-        if let Some(remove) = self.remove {
-            writeln!(f, "{}={}", SetKey::Remove, remove)?;
+        for allowed_ip in &self.allowed_ips {
+            allowed_ip.fmt(f)?;
         }
 
-        if let Some(update_only) = self.update_only {
-            writeln!(f, "{}={}", SetKey::UpdateOnly, update_only)?;
+        Ok(())
+    }
+}
+
+pub(crate) struct PeerKeys<'a>(&'a Peer);
+
+impl Display for PeerKeys<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let peer = self.0;
+
+        writeln!(f, "{}={}", SetKey::PublicKey, hex::encode(peer.public_key))?;
+
+        if peer.remove == Some(true) {
+            writeln!(f, "{}=true", SetKey::Remove)?;
         }
 
-        if let Some(preshared_key) = self.preshared_key {
+        if peer.update_only == Some(true) {
+            writeln!(f, "{}=true", SetKey::UpdateOnly)?;
+        }
+
+        if let Some(protocol_version) = peer.protocol_version {
+            writeln!(f, "{}={}", SetKey::ProtocolVersion, protocol_version)?;
+        }
+
+        if let Some(preshared_key) = peer.preshared_key {
             let preshared_key = hex::encode(preshared_key);
             writeln!(f, "{}={}", SetKey::PresharedKey, preshared_key)?;
         }
 
-        if let Some(endpoint) = self.endpoint {
+        if let Some(endpoint) = peer.endpoint {
             writeln!(f, "{}={}", SetKey::Endpoint, endpoint)?;
         }
 
-        if let Some(interval) = self.persistent_keepalive_interval {
+        if let Some(interval) = peer.persistent_keepalive_interval {
             writeln!(f, "{}={}", SetKey::PersistentKeepaliveInterval, interval)?;
         }
 
-        if let Some(replace_allowed_ips) = self.replace_allowed_ips {
-            writeln!(f, "{}={}", SetKey::ReplaceAllowedIps, replace_allowed_ips)?;
-        }
-
-        for allowed_ip in &self.allowed_ips {
-            allowed_ip.fmt(f)?;
+        if peer.replace_allowed_ips == Some(true) {
+            writeln!(f, "{}=true", SetKey::ReplaceAllowedIps)?;
         }
 
         Ok(())
@@ -346,13 +401,31 @@ mod tests {
         assert_eq!(expected, actual);
     }
 
+    #[test]
+    fn serialize_leaves_out_false_flags() {
+        let public_key = [0x11; 32];
+        let set_request = Device {
+            replace_peers: Some(false),
+            peers: vec![Peer {
+                remove: Some(false),
+                update_only: Some(false),
+                replace_allowed_ips: Some(false),
+                ..Peer::from_public_key(public_key)
+            }],
+            ..Default::default()
+        };
+
+        let expected = format!("public_key={}\n", hex::encode(public_key));
+        assert_eq!(set_request.to_string(), expected);
+    }
+
     // Simple comparisons to make default, partial_eq, and debug derive code covered.
     #[test]
     fn cover_derives() {
         let device1 = Device::default();
         let device2 = Device::default();
         assert_eq!(device1, device2);
-        format!("{:?}", device1);
+        let _ = format!("{:?}", device1);
 
         let peer1 = Peer::from_public_key([
             0xb8, 0x59, 0x96, 0xfe, 0xcc, 0x9c, 0x7f, 0x1f, 0xc6, 0xd2, 0x57, 0x2a, 0x76, 0xed,
@@ -365,7 +438,7 @@ mod tests {
             0xa8, 0xe7, 0x5a, 0x33,
         ]);
         assert_eq!(peer1, peer2);
-        format!("{:?}", peer1);
+        let _ = format!("{:?}", peer1);
 
         let allowed_ip1 = AllowedIp {
             ipaddr: "::1".parse().unwrap(),
@@ -376,6 +449,6 @@ mod tests {
             cidr_mask: 64,
         };
         assert_eq!(allowed_ip1, allowed_ip2);
-        format!("{:?}", allowed_ip1);
+        let _ = format!("{:?}", allowed_ip1);
     }
 }
@@ -0,0 +1,52 @@
+#![cfg(feature = "tokio")]
+
+#[cfg(target_os = "linux")]
+use wireguard_uapi::{err::LinkDeviceError, set, AsyncRouteSocket, AsyncWgSocket, DeviceInterface};
+
+#[cfg(target_os = "linux")]
+fn get_random_ifname() -> String {
+    format!("wgtest{}", rand::random::<u16>())
+}
+
+#[cfg(target_os = "linux")]
+#[tokio::test]
+async fn async_set_and_get_device() -> anyhow::Result<()> {
+    let ifname = get_random_ifname();
+    let listen_port = rand::random::<u16>();
+
+    let mut wg = AsyncWgSocket::connect().await?;
+    let mut route = AsyncRouteSocket::connect().await?;
+
+    route.add_device(&ifname).await?;
+    let set_result = wg
+        .set_device(set::Device::from_ifname(&ifname).listen_port(listen_port))
+        .await;
+    let device = wg.get_device(DeviceInterface::from_name(&ifname)).await;
+    let names = route.list_device_names().await;
+    let devices = wg.get_all_devices(&mut route).await;
+    route.del_device(&ifname).await?;
+
+    set_result?;
+    assert_eq!(device?.listen_port, listen_port);
+    assert!(names?.contains(&ifname));
+    let (_, device) = devices?
+        .into_iter()
+        .find(|(name, _)| *name == ifname)
+        .expect("Device missing from get_all_devices");
+    assert_eq!(device?.listen_port, listen_port);
+
+    Ok(())
+}
+
+#[cfg(target_os = "linux")]
+#[tokio::test]
+async fn async_del_device_refuses_non_wireguard_links() -> anyhow::Result<()> {
+    let mut route = AsyncRouteSocket::connect().await?;
+
+    match route.del_device("lo").await {
+        Err(LinkDeviceError::NotWireGuard(_)) => {}
+        result => panic!("Expected NotWireGuard, got: {:?}", result),
+    }
+
+    Ok(())
+}
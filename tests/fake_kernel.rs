@@ -0,0 +1,290 @@
+#![cfg(all(target_os = "linux", feature = "fake"))]
+
+use std::convert::TryInto;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use wireguard_uapi::err::{GetDeviceError, SetDeviceError};
+use wireguard_uapi::fake::{FakeKernel, FakeTransport};
+use wireguard_uapi::{get, set, DeviceInterface, WgSocket};
+
+const IFNAME: &str = "wgtest0";
+
+fn key(index: u32) -> [u8; 32] {
+    let mut key = [0u8; 32];
+    key[..4].copy_from_slice(&index.to_be_bytes());
+    key[31] = 1;
+    key
+}
+
+fn parse_device_key(encoded: &str) -> anyhow::Result<[u8; 32]> {
+    Ok(base64::decode(encoded)?.as_slice().try_into()?)
+}
+
+fn setup() -> anyhow::Result<(FakeKernel, WgSocket<FakeTransport>)> {
+    let kernel = FakeKernel::new();
+    kernel.add_device(IFNAME);
+    let wg = WgSocket::from_transport(kernel.transport())?;
+    Ok((kernel, wg))
+}
+
+fn public_keys(device: &get::Device) -> Vec<[u8; 32]> {
+    device.peers.iter().map(|peer| peer.public_key).collect()
+}
+
+fn allowed_ips(peer: &get::Peer) -> Vec<(IpAddr, u8)> {
+    peer.allowed_ips
+        .iter()
+        .map(|allowed_ip| (allowed_ip.ipaddr, allowed_ip.cidr_mask))
+        .collect()
+}
+
+#[test]
+fn replace_peers_removes_previous_peers() -> anyhow::Result<()> {
+    let (_kernel, mut wg) = setup()?;
+    let (first, second, third) = (key(1), key(2), key(3));
+
+    wg.set_device(set::Device::from_ifname(IFNAME).peers(vec![
+        set::Peer::from_public_key(&first),
+        set::Peer::from_public_key(&second),
+    ]))?;
+    wg.set_device(
+        set::Device::from_ifname(IFNAME)
+            .flags(vec![set::WgDeviceF::ReplacePeers])
+            .peers(vec![set::Peer::from_public_key(&third)]),
+    )?;
+
+    let device = wg.get_device(DeviceInterface::from_name(IFNAME))?;
+    assert_eq!(public_keys(&device), vec![third]);
+
+    Ok(())
+}
+
+#[test]
+fn peer_flags() -> anyhow::Result<()> {
+    let (_kernel, mut wg) = setup()?;
+    let (first, second, third) = (key(1), key(2), key(3));
+    let ipaddr: IpAddr = "10.0.0.1".parse()?;
+    let other_ipaddr: IpAddr = "10.0.0.2".parse()?;
+
+    wg.set_device(set::Device::from_ifname(IFNAME).peers(vec![
+        set::Peer::from_public_key(&first).allowed_ips(vec![set::AllowedIp::from_ipaddr(&ipaddr)]),
+        set::Peer::from_public_key(&second),
+    ]))?;
+    wg.set_device(set::Device::from_ifname(IFNAME).peers(vec![
+        set::Peer::from_public_key(&first)
+            .flags(vec![set::WgPeerF::ReplaceAllowedIps])
+            .allowed_ips(vec![set::AllowedIp::from_ipaddr(&other_ipaddr)]),
+        set::Peer::from_public_key(&second).flags(vec![set::WgPeerF::RemoveMe]),
+        set::Peer::from_public_key(&third)
+            .flags(vec![set::WgPeerF::UpdateOnly])
+            .persistent_keepalive_interval(25),
+    ]))?;
+
+    let device = wg.get_device(DeviceInterface::from_name(IFNAME))?;
+    assert_eq!(public_keys(&device), vec![first]);
+    assert_eq!(allowed_ips(&device.peers[0]), vec![(other_ipaddr, 32)]);
+
+    Ok(())
+}
+
+#[test]
+fn allowed_ips_move_between_peers() -> anyhow::Result<()> {
+    let (_kernel, mut wg) = setup()?;
+    let (first, second) = (key(1), key(2));
+    let ipaddr: IpAddr = "10.1.2.3".parse()?;
+    let allowed_ip = set::AllowedIp {
+        ipaddr: &ipaddr,
+        cidr_mask: Some(24),
+    };
+
+    wg.set_device(set::Device::from_ifname(IFNAME).peers(vec![
+        set::Peer::from_public_key(&first).allowed_ips(vec![allowed_ip.clone()]),
+    ]))?;
+    wg.set_device(set::Device::from_ifname(IFNAME).peers(vec![
+        set::Peer::from_public_key(&second).allowed_ips(vec![allowed_ip]),
+    ]))?;
+
+    let device = wg.get_device(DeviceInterface::from_name(IFNAME))?;
+    assert_eq!(public_keys(&device), vec![first, second]);
+    assert!(device.peers[0].allowed_ips.is_empty());
+    assert_eq!(
+        allowed_ips(&device.peers[1]),
+        vec![("10.1.2.0".parse()?, 24)]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn private_key_derives_public_key_and_zero_key_removes_it() -> anyhow::Result<()> {
+    let (_kernel, mut wg) = setup()?;
+    let private_key = parse_device_key("EHhtoXVXpnXz31cx8nrAxQfvaRqe1vf343GVSyEtqUU=")?;
+    let public_key = parse_device_key("MhBzmIBrzw8b8iF2FH4ejh/7Vumn6Q/KoR0H5+o7mlY=")?;
+
+    wg.set_device(set::Device::from_ifname(IFNAME).private_key(&private_key))?;
+    let device = wg.get_device(DeviceInterface::from_name(IFNAME))?;
+    assert_eq!(device.private_key, Some(private_key));
+    assert_eq!(device.public_key, Some(public_key));
+
+    // The device can't be added as its own peer.
+    wg.set_device(
+        set::Device::from_ifname(IFNAME).peers(vec![set::Peer::from_public_key(&public_key)]),
+    )?;
+    assert!(wg
+        .get_device(DeviceInterface::from_name(IFNAME))?
+        .peers
+        .is_empty());
+
+    wg.set_device(set::Device::from_ifname(IFNAME).private_key(&[0u8; 32]))?;
+    let device = wg.get_device(DeviceInterface::from_name(IFNAME))?;
+    assert_eq!(device.private_key, None);
+    assert_eq!(device.public_key, None);
+
+    Ok(())
+}
+
+#[test]
+fn large_devices_are_chunked_and_dumped_in_several_messages() -> anyhow::Result<()> {
+    let (kernel, mut wg) = setup()?;
+    kernel.set_max_message_size(1024);
+
+    let keys: Vec<[u8; 32]> = (0..2000).map(key).collect();
+    let endpoint: SocketAddr = "[::1]:51820".parse()?;
+    let ipaddrs: Vec<IpAddr> = (0..300u32)
+        .map(|index| IpAddr::V4(Ipv4Addr::from(0x0a00_0000u32 + index * 256)))
+        .collect();
+
+    let peers = keys
+        .iter()
+        .enumerate()
+        .map(|(index, key)| {
+            let peer = set::Peer::from_public_key(key).endpoint(&endpoint);
+            // A single peer with more allowed IPs than fit into one message.
+            if index != 1000 {
+                return peer;
+            }
+            peer.allowed_ips(
+                ipaddrs
+                    .iter()
+                    .map(|ipaddr| set::AllowedIp {
+                        ipaddr,
+                        cidr_mask: Some(24),
+                    })
+                    .collect(),
+            )
+        })
+        .collect();
+    wg.set_device(set::Device::from_ifname(IFNAME).peers(peers))?;
+
+    let expected = kernel
+        .device(DeviceInterface::from_name(IFNAME))
+        .expect("The device exists");
+    assert_eq!(public_keys(&expected), keys);
+    assert_eq!(expected.peers[1000].allowed_ips.len(), ipaddrs.len());
+
+    let device = wg.get_device(DeviceInterface::from_name(IFNAME))?;
+    assert_eq!(device, expected);
+
+    let streamed = wg
+        .get_device_peers(DeviceInterface::from_name(IFNAME))?
+        .collect::<Result<Vec<_>, _>>()?;
+    assert_eq!(streamed, expected.peers);
+
+    Ok(())
+}
+
+#[test]
+fn tiny_message_size_limits_are_raised_to_the_minimum() -> anyhow::Result<()> {
+    let (kernel, mut wg) = setup()?;
+    kernel.set_max_message_size(8);
+
+    let keys: Vec<[u8; 32]> = (0..4).map(key).collect();
+    let endpoint: SocketAddr = "[::1]:51820".parse()?;
+    wg.set_device(
+        set::Device::from_ifname(IFNAME).peers(
+            keys.iter()
+                .map(|key| set::Peer::from_public_key(key).endpoint(&endpoint))
+                .collect(),
+        ),
+    )?;
+
+    let device = wg.get_device(DeviceInterface::from_name(IFNAME))?;
+    assert_eq!(public_keys(&device), keys);
+
+    Ok(())
+}
+
+#[test]
+fn devices_too_large_for_a_message_fail_with_emsgsize() -> anyhow::Result<()> {
+    let (kernel, mut wg) = setup()?;
+    // The kernel limits names to IFNAMSIZ, the fake kernel doesn't.
+    let ifindex = kernel.add_device(&"x".repeat(1000));
+    kernel.set_max_message_size(512);
+
+    match wg.get_device(DeviceInterface::from_index(ifindex)) {
+        Err(GetDeviceError::KernelError(err)) => assert_eq!(err.raw_os_error(), libc::EMSGSIZE),
+        result => panic!("Expected EMSGSIZE, got: {:?}", result),
+    }
+
+    Ok(())
+}
+
+#[test]
+fn rejected_fragment_names_its_peers() -> anyhow::Result<()> {
+    let (_kernel, mut wg) = setup()?;
+    wg.set_max_message_size(512);
+
+    let keys: Vec<[u8; 32]> = (0..20).map(key).collect();
+    let peers = keys
+        .iter()
+        .enumerate()
+        .map(|(index, key)| {
+            let peer = set::Peer::from_public_key(key);
+            // Only protocol version 1 exists.
+            if index == keys.len() - 1 {
+                return peer.protocol_version(2);
+            }
+            peer
+        })
+        .collect();
+
+    match wg.set_device(set::Device::from_ifname(IFNAME).peers(peers)) {
+        Err(SetDeviceError::FragmentRejected(err)) => {
+            assert!(err.count() > 1);
+            assert_eq!(err.index(), err.count() - 1);
+            assert_eq!(err.public_keys().last(), keys.last());
+            assert_eq!(err.kernel_error().raw_os_error(), libc::EPFNOSUPPORT);
+        }
+        result => panic!("Expected a rejected fragment, got: {:?}", result),
+    }
+
+    // The messages before the rejected one were applied.
+    let device = wg.get_device(DeviceInterface::from_name(IFNAME))?;
+    assert_eq!(public_keys(&device), keys[..keys.len() - 1]);
+
+    Ok(())
+}
+
+#[test]
+fn get_device_can_be_sent_and_polled_separately() -> anyhow::Result<()> {
+    let (_kernel, mut wg) = setup()?;
+    wg.set_device(set::Device::from_ifname(IFNAME).listen_port(51820))?;
+
+    let mut request = wg.send_get_device(DeviceInterface::from_name(IFNAME))?;
+    let device = wg.poll_get_device(&mut request)?;
+    assert_eq!(device.listen_port, 51820);
+
+    Ok(())
+}
+
+#[test]
+fn missing_device_returns_enodev() -> anyhow::Result<()> {
+    let (kernel, mut wg) = setup()?;
+    assert!(kernel.del_device(DeviceInterface::from_name(IFNAME)));
+
+    match wg.set_device(set::Device::from_ifname(IFNAME).listen_port(51820)) {
+        Err(SetDeviceError::KernelError(err)) => assert_eq!(err.raw_os_error(), libc::ENODEV),
+        result => panic!("Expected ENODEV, got: {:?}", result),
+    }
+
+    Ok(())
+}
@@ -2,7 +2,7 @@
 use {
     std::net::{IpAddr, Ipv6Addr},
     std::time::Duration,
-    wireguard_uapi::{get, set, DeviceInterface, RouteSocket, WgSocket},
+    wireguard_uapi::{get, link, set, DeviceInterface, RouteSocket, WgSocket},
 };
 
 #[cfg(target_os = "linux")]
@@ -18,7 +18,7 @@ fn parse_device_key(buf: &[u8]) -> [u8; 32] {
 }
 
 #[cfg(target_os = "linux")]
-fn create_set_allowed_ips(allowed_ips: &[get::AllowedIp]) -> Vec<set::AllowedIp> {
+fn create_set_allowed_ips(allowed_ips: &[get::AllowedIp]) -> Vec<set::AllowedIp<'_>> {
     allowed_ips
         .iter()
         .map(|allowed_ip| set::AllowedIp {
@@ -270,3 +270,165 @@ fn peer_update_only() -> anyhow::Result<()> {
 
     Ok(())
 }
+
+#[cfg(target_os = "linux")]
+#[test]
+fn device_report_includes_link_stats() -> anyhow::Result<()> {
+    let ifname = get_random_ifname();
+
+    let report = {
+        let mut wg = WgSocket::connect()?;
+        let mut route = RouteSocket::connect()?;
+
+        route.add_device(&ifname)?;
+        let report = wg.get_device_report(&mut route, DeviceInterface::from_name(&ifname));
+        route.del_device(&ifname)?;
+        report?
+    };
+
+    assert_eq!(report.device.ifname, ifname);
+    // The interface was never brought up, so nothing could have been sent.
+    assert_eq!(report.stats.tx_packets, 0);
+    assert_eq!(report.peer_tx_bytes(), 0);
+
+    Ok(())
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn create_device_returns_ifindex_and_ensures_existence() -> anyhow::Result<()> {
+    let ifname = get_random_ifname();
+
+    let mut wg = WgSocket::connect()?;
+    let mut route = RouteSocket::connect()?;
+
+    let link_device = link::Device::from_ifname(&ifname).mtu(1280);
+    let ifindex = route.create_device(&link_device)?;
+    let again = route.create_device(&link_device.clone().ensure_exists(true));
+    let duplicate = route.create_device(&link_device);
+    let device = wg.get_device(DeviceInterface::from_index(ifindex));
+    route.del_device(&ifname)?;
+
+    assert_eq!(again?, ifindex);
+    assert!(duplicate.is_err());
+    assert_eq!(device?.ifname, ifname);
+
+    Ok(())
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn del_device_by_index_removes_wireguard_device() -> anyhow::Result<()> {
+    let ifname = get_random_ifname();
+
+    let mut wg = WgSocket::connect()?;
+    let mut route = RouteSocket::connect()?;
+
+    let ifindex = route.create_device(&link::Device::from_ifname(&ifname))?;
+    route.del_device_by_index(ifindex)?;
+
+    assert!(wg.get_device(DeviceInterface::from_index(ifindex)).is_err());
+
+    Ok(())
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn del_device_refuses_non_wireguard_links() -> anyhow::Result<()> {
+    let mut route = RouteSocket::connect()?;
+
+    match route.del_device("lo") {
+        Err(wireguard_uapi::err::LinkDeviceError::NotWireGuard(_)) => {}
+        result => panic!("Expected NotWireGuard, got: {:?}", result),
+    }
+
+    Ok(())
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn get_all_devices_includes_every_device() -> anyhow::Result<()> {
+    let ifnames: Vec<String> = (0..6).map(|_| get_random_ifname()).collect();
+
+    let mut wg = WgSocket::connect()?;
+    let mut route = RouteSocket::connect()?;
+
+    for ifname in &ifnames {
+        route.add_device(ifname)?;
+    }
+    let devices = wg.get_all_devices(&mut route);
+    for ifname in &ifnames {
+        route.del_device(ifname)?;
+    }
+
+    let devices = devices?;
+    for ifname in &ifnames {
+        let (_, device) = devices
+            .iter()
+            .find(|(name, _)| name == ifname)
+            .expect("Device missing from get_all_devices");
+        assert_eq!(
+            &device.as_ref().expect("Unable to get device").ifname,
+            ifname
+        );
+    }
+
+    Ok(())
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn get_device_peers_matches_get_device() -> anyhow::Result<()> {
+    let ifname = get_random_ifname();
+    let public_keys: Vec<[u8; 32]> = (0..64u8).map(|i| [i + 1; 32]).collect();
+    // An allowed IP belongs to a single peer, so each peer gets its own range.
+    let allowed_ips: Vec<Vec<IpAddr>> = (0..public_keys.len() as u16)
+        .map(|peer| {
+            (1..=2u16.pow(10))
+                .map(|i| IpAddr::V6(Ipv6Addr::new(0, 0, 0, 0, 0, peer, i / 256, i % 256)))
+                .collect()
+        })
+        .collect();
+
+    let mut wg = WgSocket::connect()?;
+    let mut route = RouteSocket::connect()?;
+    route.add_device(&ifname)?;
+
+    let result = (|| -> anyhow::Result<_> {
+        // Give every peer enough allowed IPs that some of them get split across
+        // netlink messages.
+        let peers = public_keys
+            .iter()
+            .zip(&allowed_ips)
+            .map(|(public_key, allowed_ips)| {
+                set::Peer::from_public_key(public_key).allowed_ips(
+                    allowed_ips
+                        .iter()
+                        .map(set::AllowedIp::from_ipaddr)
+                        .collect(),
+                )
+            })
+            .collect();
+        wg.set_device(set::Device::from_ifname(&ifname).peers(peers))?;
+
+        let expected = wg.get_device(DeviceInterface::from_name(&ifname))?;
+        let (device_ifindex, streamed_peers) = {
+            let peer_iter = wg.get_device_peers(DeviceInterface::from_name(&ifname))?;
+            let ifindex = peer_iter.device().ifindex;
+            (ifindex, peer_iter.collect::<Result<Vec<_>, _>>()?)
+        };
+
+        Ok((expected, device_ifindex, streamed_peers))
+    })();
+    route.del_device(&ifname)?;
+
+    let (expected, device_ifindex, streamed_peers) = result?;
+    assert_eq!(device_ifindex, expected.ifindex);
+    assert!(expected
+        .peers
+        .iter()
+        .all(|peer| peer.allowed_ips.len() == 2usize.pow(10)));
+    assert_eq!(streamed_peers, expected.peers);
+
+    Ok(())
+}
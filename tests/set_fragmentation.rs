@@ -0,0 +1,260 @@
+#![cfg(all(target_os = "linux", feature = "fake"))]
+
+use proptest::prelude::*;
+use std::cell::RefCell;
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::rc::Rc;
+use wireguard_uapi::fake::{FakeKernel, FakeTransport};
+use wireguard_uapi::{get, set, DeviceInterface, Transport, WgSocket};
+
+const IFNAME: &str = "wgtest0";
+
+/// Large enough for every generated device to fit into a single message.
+const UNSPLIT_MESSAGE_SIZE: usize = 65_536;
+
+/// An owned description of a set request, since `set::Device` only borrows
+/// its keys and addresses.
+#[derive(Clone, Debug)]
+struct DeviceConfig {
+    replace_peers: bool,
+    private_key: Option<[u8; 32]>,
+    listen_port: Option<u16>,
+    fwmark: Option<u32>,
+    peers: Vec<PeerConfig>,
+}
+
+#[derive(Clone, Debug)]
+struct PeerConfig {
+    public_key: [u8; 32],
+    flags: Vec<set::WgPeerF>,
+    preshared_key: Option<[u8; 32]>,
+    endpoint: Option<SocketAddr>,
+    persistent_keepalive_interval: Option<u16>,
+    allowed_ips: Vec<(IpAddr, u8)>,
+}
+
+impl DeviceConfig {
+    fn to_set_device(&self) -> set::Device<'_> {
+        let mut device = set::Device::from_ifname(IFNAME).peers(
+            self.peers
+                .iter()
+                .map(PeerConfig::to_set_peer)
+                .collect::<Vec<_>>(),
+        );
+        if self.replace_peers {
+            device = device.flags(vec![set::WgDeviceF::ReplacePeers]);
+        }
+        if let Some(private_key) = &self.private_key {
+            device = device.private_key(private_key);
+        }
+        if let Some(listen_port) = self.listen_port {
+            device = device.listen_port(listen_port);
+        }
+        if let Some(fwmark) = self.fwmark {
+            device = device.fwmark(fwmark);
+        }
+        device
+    }
+}
+
+impl PeerConfig {
+    fn to_set_peer(&self) -> set::Peer<'_> {
+        let mut peer = set::Peer::from_public_key(&self.public_key)
+            .flags(self.flags.clone())
+            .allowed_ips(
+                self.allowed_ips
+                    .iter()
+                    .map(|(ipaddr, cidr_mask)| set::AllowedIp {
+                        ipaddr,
+                        cidr_mask: Some(*cidr_mask),
+                    })
+                    .collect(),
+            );
+        if let Some(preshared_key) = &self.preshared_key {
+            peer = peer.preshared_key(preshared_key);
+        }
+        if let Some(endpoint) = &self.endpoint {
+            peer = peer.endpoint(endpoint);
+        }
+        if let Some(persistent_keepalive_interval) = self.persistent_keepalive_interval {
+            peer = peer.persistent_keepalive_interval(persistent_keepalive_interval);
+        }
+        peer
+    }
+}
+
+/// Draws keys and addresses from small pools, so requests update and remove
+/// existing peers and move allowed IPs between them.
+fn key() -> impl Strategy<Value = [u8; 32]> {
+    (1..6u8).prop_map(|byte| [byte; 32])
+}
+
+fn allowed_ip() -> impl Strategy<Value = (IpAddr, u8)> {
+    prop_oneof![
+        (any::<u8>(), 24..=32u8).prop_map(|(host, cidr_mask)| {
+            (IpAddr::V4(Ipv4Addr::new(10, 0, 0, host)), cidr_mask)
+        }),
+        (any::<u8>(), 120..=128u8).prop_map(|(host, cidr_mask)| {
+            let ipaddr = Ipv6Addr::new(0xfd00, 0, 0, 0, 0, 0, 0, u16::from(host));
+            (IpAddr::V6(ipaddr), cidr_mask)
+        }),
+    ]
+}
+
+fn endpoint() -> impl Strategy<Value = SocketAddr> {
+    prop_oneof![
+        any::<u16>().prop_map(|port| SocketAddr::from(([192, 0, 2, 1], port))),
+        any::<u16>().prop_map(|port| SocketAddr::from((Ipv6Addr::LOCALHOST, port))),
+    ]
+}
+
+fn peer_flags() -> impl Strategy<Value = Vec<set::WgPeerF>> {
+    (any::<bool>(), any::<bool>(), any::<bool>()).prop_map(
+        |(remove_me, replace_allowed_ips, update_only)| {
+            let mut flags = vec![];
+            if remove_me {
+                flags.push(set::WgPeerF::RemoveMe);
+            }
+            if replace_allowed_ips {
+                flags.push(set::WgPeerF::ReplaceAllowedIps);
+            }
+            if update_only {
+                flags.push(set::WgPeerF::UpdateOnly);
+            }
+            flags
+        },
+    )
+}
+
+fn peer_config() -> impl Strategy<Value = PeerConfig> {
+    (
+        key(),
+        prop::bool::weighted(0.3).prop_flat_map(|with_flags| {
+            if with_flags {
+                peer_flags().boxed()
+            } else {
+                Just(vec![]).boxed()
+            }
+        }),
+        prop::option::of(any::<[u8; 32]>()),
+        prop::option::of(endpoint()),
+        prop::option::of(any::<u16>()),
+        prop::collection::vec(allowed_ip(), 0..60),
+    )
+        .prop_map(
+            |(public_key, flags, preshared_key, endpoint, keepalive, allowed_ips)| PeerConfig {
+                public_key,
+                flags,
+                preshared_key,
+                endpoint,
+                persistent_keepalive_interval: keepalive,
+                allowed_ips,
+            },
+        )
+}
+
+fn device_config() -> impl Strategy<Value = DeviceConfig> {
+    (
+        prop::bool::weighted(0.2),
+        prop::option::of(key()),
+        prop::option::of(any::<u16>()),
+        prop::option::of(any::<u32>()),
+        prop::collection::vec(peer_config(), 0..8),
+    )
+        .prop_map(
+            |(replace_peers, private_key, listen_port, fwmark, peers)| DeviceConfig {
+                replace_peers,
+                private_key,
+                listen_port,
+                fwmark,
+                peers,
+            },
+        )
+}
+
+/// Passes messages through to the fake kernel and records their sizes.
+struct RecordingTransport {
+    inner: FakeTransport,
+    sent: Rc<RefCell<Vec<usize>>>,
+}
+
+impl Transport for RecordingTransport {
+    fn send(&mut self, buf: &[u8]) -> io::Result<()> {
+        self.sent.borrow_mut().push(buf.len());
+        self.inner.send(buf)
+    }
+
+    fn recv(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.inner.recv(buf)
+    }
+
+    fn capped_acks(&self) -> bool {
+        self.inner.capped_acks()
+    }
+}
+
+/// Applies `initial` in one message and then `config` in messages of at most
+/// `max_message_size` bytes. Returns the resulting device and the sizes of
+/// the messages `config` was sent in.
+fn apply(
+    initial: &DeviceConfig,
+    config: &DeviceConfig,
+    max_message_size: usize,
+) -> anyhow::Result<(get::Device, Vec<usize>)> {
+    let kernel = FakeKernel::new();
+    kernel.add_device(IFNAME);
+    let sent = Rc::new(RefCell::new(vec![]));
+    let mut wg = WgSocket::from_transport(RecordingTransport {
+        inner: kernel.transport(),
+        sent: sent.clone(),
+    })?;
+
+    wg.set_max_message_size(UNSPLIT_MESSAGE_SIZE);
+    wg.set_device(initial.to_set_device())?;
+
+    sent.borrow_mut().clear();
+    wg.set_max_message_size(max_message_size);
+    wg.set_device(config.to_set_device())?;
+
+    let device = kernel
+        .device(DeviceInterface::from_name(IFNAME))
+        .expect("The device exists");
+    let sent = sent.borrow().clone();
+    Ok((device, sent))
+}
+
+proptest! {
+    #[test]
+    fn fragments_stay_within_the_limit(
+        config in device_config(),
+        max_message_size in 512..4096usize,
+    ) {
+        let initial = DeviceConfig {
+            replace_peers: false,
+            private_key: None,
+            listen_port: None,
+            fwmark: None,
+            peers: vec![],
+        };
+        let (_, sent) = apply(&initial, &config, max_message_size).unwrap();
+
+        prop_assert!(!sent.is_empty());
+        for size in sent {
+            prop_assert!(size <= max_message_size, "{} > {}", size, max_message_size);
+        }
+    }
+
+    #[test]
+    fn fragments_apply_like_the_unsplit_request(
+        initial in device_config(),
+        config in device_config(),
+        max_message_size in 512..2048usize,
+    ) {
+        let (unsplit, unsplit_sent) = apply(&initial, &config, UNSPLIT_MESSAGE_SIZE).unwrap();
+        prop_assert_eq!(unsplit_sent.len(), 1);
+
+        let (split, _) = apply(&initial, &config, max_message_size).unwrap();
+        prop_assert_eq!(split, unsplit);
+    }
+}
@@ -0,0 +1,300 @@
+#![cfg(all(unix, feature = "xplatform", feature = "fake"))]
+
+//! End-to-end tests of the cross-platform client against an in-process
+//! server. The server applies requests to a [`FakeDevice`] the way
+//! wireguard-go does, so the client can be tested without a userspace
+//! WireGuard implementation.
+
+use std::io::{Read, Write};
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::os::unix::net::UnixStream;
+use std::path::PathBuf;
+use std::sync::{Arc, MutexGuard};
+use tempfile::TempDir;
+use wireguard_uapi::get;
+use wireguard_uapi::xplatform::error::{GetDeviceError, ServerError, SetDeviceError};
+use wireguard_uapi::xplatform::fake::FakeDevice;
+use wireguard_uapi::xplatform::parser::ParseGetResponseError;
+use wireguard_uapi::xplatform::server::Server;
+use wireguard_uapi::xplatform::{set, Client, Discovery};
+
+const IFNAME: &str = "wgtest0";
+
+/// A server for a single device, running until the test process exits.
+struct TestServer {
+    dir: TempDir,
+    server: Arc<Server<FakeDevice>>,
+}
+
+impl TestServer {
+    fn spawn() -> anyhow::Result<Self> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join(format!("{}.sock", IFNAME));
+        let server = Arc::new(Server::bind(path, FakeDevice::new())?);
+        let serving = Arc::clone(&server);
+        std::thread::spawn(move || serving.serve());
+        Ok(Self { dir, server })
+    }
+
+    fn device(&self) -> MutexGuard<'_, FakeDevice> {
+        self.server.handler()
+    }
+
+    fn path(&self) -> PathBuf {
+        self.dir.path().join(format!("{}.sock", IFNAME))
+    }
+
+    fn client(&self) -> Client<PathBuf> {
+        Client::create(self.path())
+    }
+}
+
+fn key(index: u8) -> [u8; 32] {
+    [index; 32]
+}
+
+fn allowed_ip(host: u8) -> set::AllowedIp {
+    set::AllowedIp {
+        ipaddr: IpAddr::V4(Ipv4Addr::new(10, 0, 0, host)),
+        cidr_mask: 32,
+    }
+}
+
+fn allowed_hosts(peer: &get::Peer) -> Vec<IpAddr> {
+    peer.allowed_ips
+        .iter()
+        .map(|allowed_ip| allowed_ip.ipaddr)
+        .collect()
+}
+
+#[test]
+fn get_empty_device() -> anyhow::Result<()> {
+    let server = TestServer::spawn()?;
+
+    let device = server.client().get()?;
+    assert_eq!(device.private_key, None);
+    assert_eq!(device.listen_port, 0);
+    assert!(device.peers.is_empty());
+
+    Ok(())
+}
+
+#[test]
+fn set_interface_keys() -> anyhow::Result<()> {
+    let server = TestServer::spawn()?;
+    let client = server.client();
+
+    client.set(set::Device {
+        private_key: Some(key(1)),
+        listen_port: Some(51820),
+        fwmark: Some(7),
+        ..Default::default()
+    })?;
+    let device = client.get()?;
+    assert_eq!(device.private_key, Some(key(1)));
+    assert_eq!(device.listen_port, 51820);
+    assert_eq!(device.fwmark, 7);
+
+    // All zeros removes the private key, and 0 removes the fwmark.
+    client.set(set::Device {
+        private_key: Some([0u8; 32]),
+        fwmark: Some(0),
+        ..Default::default()
+    })?;
+    let device = client.get()?;
+    assert_eq!(device.private_key, None);
+    assert_eq!(device.listen_port, 51820);
+    assert_eq!(device.fwmark, 0);
+
+    Ok(())
+}
+
+#[test]
+fn set_and_update_multiple_peers() -> anyhow::Result<()> {
+    let server = TestServer::spawn()?;
+    let client = server.client();
+    let endpoint: SocketAddr = "[::1]:51820".parse()?;
+
+    client.set(set::Device {
+        peers: vec![
+            set::Peer::from_public_key(key(1))
+                .preshared_key(key(9))
+                .endpoint(endpoint)
+                .allowed_ips(vec![allowed_ip(1), allowed_ip(2)]),
+            set::Peer::from_public_key(key(2))
+                .persistent_keepalive_interval(25)
+                .allowed_ips(vec![allowed_ip(3)]),
+            set::Peer::from_public_key(key(3)),
+        ],
+        ..Default::default()
+    })?;
+
+    let device = client.get()?;
+    let public_keys: Vec<_> = device.peers.iter().map(|peer| peer.public_key).collect();
+    assert_eq!(public_keys, vec![key(1), key(2), key(3)]);
+    assert_eq!(device.peers[0].preshared_key, key(9));
+    assert_eq!(device.peers[0].endpoint, Some(endpoint));
+    assert_eq!(device.peers[1].persistent_keepalive_interval, 25);
+    assert_eq!(allowed_hosts(&device.peers[1]), vec![allowed_ip(3).ipaddr]);
+
+    client.set(set::Device {
+        peers: vec![
+            // Moves 10.0.0.2 from the first peer to the third.
+            set::Peer::from_public_key(key(3)).allowed_ips(vec![allowed_ip(2)]),
+            set::Peer::from_public_key(key(2))
+                .replace_allowed_ips(true)
+                .allowed_ips(vec![allowed_ip(4)]),
+            set::Peer::from_public_key(key(1)).remove(true),
+            set::Peer::from_public_key(key(4)).update_only(true),
+        ],
+        ..Default::default()
+    })?;
+
+    let device = client.get()?;
+    let public_keys: Vec<_> = device.peers.iter().map(|peer| peer.public_key).collect();
+    assert_eq!(public_keys, vec![key(2), key(3)]);
+    assert_eq!(allowed_hosts(&device.peers[0]), vec![allowed_ip(4).ipaddr]);
+    assert_eq!(allowed_hosts(&device.peers[1]), vec![allowed_ip(2).ipaddr]);
+
+    client.set(set::Device {
+        replace_peers: Some(true),
+        peers: vec![set::Peer::from_public_key(key(5))],
+        ..Default::default()
+    })?;
+    let device = client.get()?;
+    assert_eq!(device.peers.len(), 1);
+    assert_eq!(device.peers[0].public_key, key(5));
+
+    Ok(())
+}
+
+#[test]
+fn set_many_peers_from_iterator() -> anyhow::Result<()> {
+    let server = TestServer::spawn()?;
+    let client = server.client();
+
+    let peers = (1..=250u8)
+        .map(|index| set::Peer::from_public_key(key(index)).allowed_ips(vec![allowed_ip(index)]));
+    client.set_peers(set::Device::default(), peers)?;
+
+    let device = client.get()?;
+    assert_eq!(device.peers.len(), 250);
+    assert!(device
+        .peers
+        .iter()
+        .all(|peer| allowed_hosts(peer) == vec![allowed_ip(peer.public_key[0]).ipaddr]));
+
+    Ok(())
+}
+
+#[test]
+fn error_replies() -> anyhow::Result<()> {
+    let server = TestServer::spawn()?;
+    let client = server.client();
+    server.device().occupy_port(51820);
+
+    // Keys before the failing one stay applied.
+    let err = client
+        .set(set::Device {
+            private_key: Some(key(1)),
+            listen_port: Some(51820),
+            ..Default::default()
+        })
+        .unwrap_err();
+    assert!(matches!(
+        err,
+        SetDeviceError::ServerError(ServerError::AddrInUse)
+    ));
+    assert_eq!(client.get()?.private_key, Some(key(1)));
+
+    server.device().remove();
+    let err = client.get().unwrap_err();
+    assert!(matches!(
+        err,
+        GetDeviceError::ParseGetDevice(ParseGetResponseError::ServerError(ServerError::NotFound))
+    ));
+    let err = client.set(set::Device::default()).unwrap_err();
+    assert!(matches!(
+        err,
+        SetDeviceError::ServerError(ServerError::NotFound)
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn persistent_connection() -> anyhow::Result<()> {
+    let server = TestServer::spawn()?;
+    let client = server.client();
+    let mut connection = client.connection();
+
+    connection.set(set::Device {
+        listen_port: Some(51820),
+        peers: vec![set::Peer::from_public_key(key(1))],
+        ..Default::default()
+    })?;
+    assert_eq!(connection.get()?.peers.len(), 1);
+
+    // A failed operation closes the connection, and the next one reconnects.
+    server.device().occupy_port(51821);
+    assert!(connection
+        .set(set::Device {
+            listen_port: Some(51821),
+            ..Default::default()
+        })
+        .is_err());
+    assert!(!connection.is_connected());
+    assert_eq!(connection.get()?.listen_port, 51820);
+
+    // Other clients can connect while the persistent connection stays open.
+    assert_eq!(client.get()?.listen_port, 51820);
+    assert!(connection.is_connected());
+
+    Ok(())
+}
+
+#[test]
+fn unknown_operation_closes_connection() -> anyhow::Result<()> {
+    let server = TestServer::spawn()?;
+
+    let mut stream = UnixStream::connect(server.path())?;
+    stream.write_all(b"get=2\n\n")?;
+    let mut response = String::new();
+    stream.read_to_string(&mut response)?;
+    assert_eq!(response, "");
+
+    Ok(())
+}
+
+#[test]
+fn discover_server() -> anyhow::Result<()> {
+    let server = TestServer::spawn()?;
+
+    let discovered = Discovery::new().socket_dir(server.dir.path()).discover()?;
+    let client = &discovered.clients[IFNAME];
+    assert_eq!(client.get()?.listen_port, 0);
+
+    Ok(())
+}
+
+#[cfg(feature = "tokio")]
+#[tokio::test]
+async fn async_client() -> anyhow::Result<()> {
+    use wireguard_uapi::xplatform::AsyncClient;
+
+    let server = TestServer::spawn()?;
+    let client = AsyncClient::create(server.path());
+
+    client
+        .set(set::Device {
+            listen_port: Some(51821),
+            peers: vec![set::Peer::from_public_key(key(1))],
+            ..Default::default()
+        })
+        .await?;
+    let device = client.get().await?;
+    assert_eq!(device.listen_port, 51821);
+    assert_eq!(device.peers.len(), 1);
+
+    Ok(())
+}